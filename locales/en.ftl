@@ -0,0 +1,226 @@
+# English messages, the fallback for every other catalog.
+# Messages are Telegram HTML, string arguments are escaped by the bot.
+# Multi-line messages keep their line breaks, arguments are pre-formatted
+# strings except counts, which select the plural forms.
+
+## /start, /help and the command menu
+
+start-greeting = Hello with start
+help-header = <b>Simple commands</b>
+cmd-help = shows this message.
+cmd-start = register user
+cmd-chart = get chart
+cmd-price = handle a price
+cmd-addcurrency = add currencies, e.g. /addcurrency btc eth sol
+cmd-removecurrency = remove currency
+cmd-priceall = print all user currencies
+cmd-notify = turn the daily digest on or off, e.g. /notify off
+cmd-language = choose the bot language
+cmd-settings = digest, time zone, currency and other settings
+cmd-alert = notify me when a coin reaches a price, e.g. /alert btc 70000
+cmd-alerts = list and remove price alerts
+cmd-cancel = cancel the current question
+
+## /language
+
+language-choose = Choose your language
+language-set = Language set to English
+
+## Errors shared by the commands
+
+user-error = Error getting user
+chat-error = Error getting the group settings, nothing was changed
+rate-limited = ⏳ Slow down, please. Try again in { $secs } s
+rate-banned = 🚫 Too many requests, the bot ignores you for { $minutes } min
+
+## /price and /chart
+
+price-info =
+    💰Coin: <b>{ $symbol }</b>
+    💵Price { $fiat }: <code>{ $price }</code>
+    📊Change per 24 hour: { $change }
+    📈High price(24 hour): <code>{ $high }</code>
+    📉Low price(24 hour): <code>{ $low }</code>
+price-market-cap = 🏦Market cap: <code>{ $cap }</code>
+price-not-found = Error fetching data for { $symbol }: Currency not found
+price-error = Error fetching price for { $symbol }, try again later
+chart-title = Price Chart for { $symbol } in 24 hours
+chart-title-week = Price Chart for { $symbol } in 7 days
+chart-title-month = Price Chart for { $symbol } in 30 days
+
+## /priceall and the daily digest
+
+priceall-empty = You don't have any currency, type /addcurency curency-name
+priceall-error = Error, maybe you don't have any valid currency
+priceall-header = 📈<b>Your coins, { $fiat }</b>
+digest-unsubscribe = To turn off notifications send /notify off
+
+## /addcurrency and /removecurrency
+
+addcurrency-usage =
+    Type /addcurrency [currency_name ...]
+    Example: /addcurrency btc eth sol
+currency-added = Added <b>{ $symbols }</b>
+currency-already-added = { $symbol } is already in your list
+currency-unknown = Unknown coin { $symbol }
+currency-unknown-suggest = Unknown coin { $symbol }, did you mean { $suggestions }?
+currency-ambiguous = Several coins use the symbol { $symbol }, pick one below
+currency-list-full =
+    Your list is full ({ $max ->
+        [one] { $max } coin
+       *[other] { $max } coins
+    })
+currency-list-full-rejected =
+    Your list is full ({ $max ->
+        [one] { $max } coin
+       *[other] { $max } coins
+    }), not added: { $symbols }
+asset-unknown = Unknown coin, try /addcurrency again
+asset-added = Added <b>{ $symbol }</b> ({ $name })
+currency-removed = Removed "{ $symbol }"
+addcurrency-ask = Which coins should I add? Send symbols, e.g. btc eth sol
+removecurrency-choose = Tap the coins to remove
+removecurrency-empty = Your list is empty, add coins with /addcurrency
+
+## /notify
+
+notify-on = successfully turned on
+notify-off = successfully turned off
+notify-state-on = The daily digest is on, send /notify off to turn it off
+notify-state-off = The daily digest is off, send /notify on to turn it on
+notify-usage = Type /notify on or /notify off
+
+
+## /settings
+
+settings-title = ⚙️<b>Settings</b>
+settings-digest-on = 🔔 Daily digest: on, at { $time } ({ $timezone })
+settings-digest-off = 🔔 Daily digest: off
+settings-fiat = 💵 Prices in: { $fiat }
+settings-language = 🗣 Language: { $language }
+settings-theme = 🎨 Charts: { $theme }
+settings-expanders = 🔗 Answers to messages: { $expanders }
+settings-server-time = server time
+settings-default = default
+settings-none = none
+settings-choose-time = Choose when the digest is sent
+settings-choose-timezone = Choose your time zone
+settings-choose-fiat = Choose the currency of the prices
+settings-choose-expanders = Choose what the bot answers in messages
+theme-light = light
+theme-dark = dark
+expander-currency = coin amounts
+expander-twitter = Twitter links
+expander-eden = Magic Eden links
+settings-button-digest-on = 🔔 Turn the digest on
+settings-button-digest-off = 🔕 Turn the digest off
+settings-button-time = 🕚 Digest time
+settings-button-timezone = 🌍 Time zone
+settings-button-fiat = 💵 Currency
+settings-button-language = 🗣 Language
+settings-button-theme-light = ☀️ Light charts
+settings-button-theme-dark = 🌙 Dark charts
+settings-button-expanders = 🔗 Answers to messages
+settings-button-back = ⬅️ Back
+settings-button-mention-on = 💬 Answer only when mentioned
+settings-button-mention-off = 💬 Answer every message
+
+## Group chats
+
+group-admin-only = Only the chat admins can change the group watchlist and settings
+group-private-only = This command works in a private chat with me: @{ $bot }
+settings-group-title = ⚙️<b>Settings of { $title }</b>
+settings-mention-on = 💬 Answers: only when mentioned or replied to
+settings-mention-off = 💬 Answers: to every message
+settings-cooldown = ⏱ Pause between answers: { $cooldown }
+settings-cooldown-off = off
+settings-cooldown-secs = { $secs } s
+
+## Compact numbers
+
+number-thousand = { $number }K
+number-million = { $number }M
+number-billion = { $number }B
+number-trillion = { $number }T
+
+## Link previews
+
+twitter-info =
+    📨Twitter
+    Name: <a href="https://twitter.com/{ $username }">{ $username }</a>
+    Followers: <b>{ $followers }</b>
+    Tweets: <b>{ $tweets }</b>
+    Created: { $created }
+twitter-created-unknown = unknown
+# The bucket table cells are plain text
+eden-floor = 💎Floor: <b>{ $floor } sol</b>
+eden-price-column = Price, sol
+eden-count-column = Items
+eden-up-to = up to { $price }
+eden-over = over { $price }
+parse-partial = ⏳<i>Some details could not be loaded and were left out</i>
+
+## Inline mode
+
+inline-price-title = { $symbol } price
+inline-price-description = Price, 24 hour change, high and low
+inline-chart-title = { $symbol } chart for 24 hours
+inline-convert-title = Value in USD
+
+## Buttons under price cards, charts and /priceall
+
+button-refresh = 🔄 Refresh
+button-chart-24h = 24h
+button-chart-7d = 7d
+button-chart-30d = 30d
+button-add = ➕ Add to watchlist
+button-remove = ➖ Remove from watchlist
+button-alert = 🔔 Set alert
+button-cancel = ✖ Cancel
+button-done = ✔ Done
+
+## Dialogues, /alert and /alerts
+
+dialogue-cancelled = Cancelled
+dialogue-nothing = Nothing to cancel
+alert-ask-symbol = Which coin should I watch? Send a symbol, e.g. btc
+alert-unknown = Unknown coin { $symbol }, send another symbol
+alert-ask-price =
+    { $symbol } is { $price } now
+    At what price should I notify you?
+alert-invalid-price = Send the price as a number, e.g. 70000
+alert-created = 🔔 I will notify you when { $symbol } { $direction } { $target }
+alert-limit =
+    You already have { $max ->
+        [one] { $max } alert
+       *[other] { $max } alerts
+    }, remove one in /alerts
+alerts-empty = You have no price alerts, set one with /alert
+alerts-header = 🔔<b>Your price alerts</b>
+alert-triggered =
+    🔔 <b>{ $symbol }</b> { $direction } { $target }
+    Price now: { $price }
+
+## Channel summaries, admin commands
+
+channel-header = 📊<b>Market summary, { $fiat }</b>
+channel-gainers = 🚀 Top gainers: { $movers }
+channel-losers = 📉 Top losers: { $movers }
+channel-usage =
+    Type /addchannel @channel HH:MM coins, e.g.
+    /addchannel @mychannel 09:00 btc eth sol
+    Optional: a time zone (Europe/Kiev), a currency (eur), pin and nochart
+channel-too-many = Up to { $max } coins per summary
+channel-not-found = Can't find { $channel }, is the bot a member?
+channel-not-channel = { $channel } is not a channel
+channel-not-admin = Make the bot an administrator of { $channel } first
+channel-added = Scheduled a summary to { $title } at { $time }, id <code>{ $id }</code>
+channels-empty = No channel summaries, add one with /addchannel
+channels-header = 📣<b>Channel summaries</b>
+channels-item = <code>{ $id }</code> { $title }, { $time }: { $symbols } ({ $flags })
+channel-flag-chart = chart
+channel-flag-pin = pinned
+removechannel-usage = Type /removechannel id, the ids are listed by /channels
+channel-removed = Summary removed
+channel-unknown = Unknown summary { $id }, see /channels
+channel-posted = Summary posted
@@ -0,0 +1,226 @@
+# Русские сообщения, недостающие берутся из en.ftl.
+
+## /start, /help и меню команд
+
+start-greeting = Привет! Добавь монеты через /addcurrency и смотри цены через /priceall
+help-header = <b>Команды</b>
+cmd-help = показать это сообщение
+cmd-start = регистрация
+cmd-chart = график цены за сутки
+cmd-price = цена монеты
+cmd-addcurrency = добавить монеты, например /addcurrency btc eth sol
+cmd-removecurrency = удалить монету
+cmd-priceall = цены всех ваших монет
+cmd-notify = включить или выключить рассылку, например /notify off
+cmd-language = выбрать язык бота
+cmd-settings = рассылка, часовой пояс, валюта и другие настройки
+cmd-alert = сообщить, когда монета достигнет цены, например /alert btc 70000
+cmd-alerts = список оповещений о цене
+cmd-cancel = отменить текущий вопрос
+
+## /language
+
+language-choose = Выберите язык
+language-set = Язык изменён на русский
+
+## Общие ошибки команд
+
+user-error = Не удалось получить пользователя
+chat-error = Не удалось получить настройки группы, ничего не изменено
+rate-limited = ⏳ Не так быстро, пожалуйста. Попробуйте снова через { $secs } с
+rate-banned = 🚫 Слишком много запросов, бот не отвечает вам { $minutes } мин
+
+## /price и /chart
+
+price-info =
+    💰Монета: <b>{ $symbol }</b>
+    💵Цена { $fiat }: <code>{ $price }</code>
+    📊Изменение за 24 часа: { $change }
+    📈Максимум (24 часа): <code>{ $high }</code>
+    📉Минимум (24 часа): <code>{ $low }</code>
+price-market-cap = 🏦Капитализация: <code>{ $cap }</code>
+price-not-found = Не удалось получить данные для { $symbol }: монета не найдена
+price-error = Не удалось получить цену { $symbol }, попробуйте позже
+chart-title = График цены { $symbol } за 24 часа
+chart-title-week = График цены { $symbol } за 7 дней
+chart-title-month = График цены { $symbol } за 30 дней
+
+## /priceall и ежедневная рассылка
+
+priceall-empty = У вас нет монет, добавьте их через /addcurrency название
+priceall-error = Ошибка, возможно у вас нет ни одной подходящей монеты
+priceall-header = 📈<b>Ваши монеты, { $fiat }</b>
+digest-unsubscribe = Для отключения уведомлений напишите /notify off
+
+## /addcurrency и /removecurrency
+
+addcurrency-usage =
+    Напишите /addcurrency [название ...]
+    Пример: /addcurrency btc eth sol
+currency-added = Добавили валюту <b>{ $symbols }</b>
+currency-already-added = { $symbol } уже есть в вашем списке
+currency-unknown = Неизвестная монета { $symbol }
+currency-unknown-suggest = Неизвестная монета { $symbol }, может быть { $suggestions }?
+currency-ambiguous = Символ { $symbol } используют несколько монет, выберите нужную
+currency-list-full =
+    Ваш список заполнен ({ $max ->
+        [one] { $max } монета
+        [few] { $max } монеты
+       *[many] { $max } монет
+    })
+currency-list-full-rejected =
+    Ваш список заполнен ({ $max ->
+        [one] { $max } монета
+        [few] { $max } монеты
+       *[many] { $max } монет
+    }), не добавлены: { $symbols }
+asset-unknown = Неизвестная монета, попробуйте /addcurrency ещё раз
+asset-added = Добавили валюту <b>{ $symbol }</b> ({ $name })
+currency-removed = Удалили валюту "{ $symbol }"
+addcurrency-ask = Какие монеты добавить? Напишите символы, например btc eth sol
+removecurrency-choose = Нажмите на монеты, которые нужно убрать
+removecurrency-empty = Ваш список пуст, добавьте монеты через /addcurrency
+
+## /notify
+
+notify-on = Ежедневная рассылка включена
+notify-off = Ежедневная рассылка выключена
+notify-state-on = Ежедневная рассылка включена, для отключения напишите /notify off
+notify-state-off = Ежедневная рассылка выключена, для включения напишите /notify on
+notify-usage = Напишите /notify on или /notify off
+
+
+## /settings
+
+settings-title = ⚙️<b>Настройки</b>
+settings-digest-on = 🔔 Ежедневная рассылка: включена, в { $time } ({ $timezone })
+settings-digest-off = 🔔 Ежедневная рассылка: выключена
+settings-fiat = 💵 Цены в: { $fiat }
+settings-language = 🗣 Язык: { $language }
+settings-theme = 🎨 Графики: { $theme }
+settings-expanders = 🔗 Ответы на сообщения: { $expanders }
+settings-server-time = время сервера
+settings-default = по умолчанию
+settings-none = нет
+settings-choose-time = Выберите время рассылки
+settings-choose-timezone = Выберите часовой пояс
+settings-choose-fiat = Выберите валюту цен
+settings-choose-expanders = Выберите, на что бот отвечает в сообщениях
+theme-light = светлые
+theme-dark = тёмные
+expander-currency = суммы монет
+expander-twitter = ссылки Twitter
+expander-eden = ссылки Magic Eden
+settings-button-digest-on = 🔔 Включить рассылку
+settings-button-digest-off = 🔕 Выключить рассылку
+settings-button-time = 🕚 Время рассылки
+settings-button-timezone = 🌍 Часовой пояс
+settings-button-fiat = 💵 Валюта
+settings-button-language = 🗣 Язык
+settings-button-theme-light = ☀️ Светлые графики
+settings-button-theme-dark = 🌙 Тёмные графики
+settings-button-expanders = 🔗 Ответы на сообщения
+settings-button-back = ⬅️ Назад
+settings-button-mention-on = 💬 Отвечать только на упоминания
+settings-button-mention-off = 💬 Отвечать на все сообщения
+
+## Группы
+
+group-admin-only = Список и настройки группы могут менять только администраторы чата
+group-private-only = Эта команда работает в личном чате со мной: @{ $bot }
+settings-group-title = ⚙️<b>Настройки { $title }</b>
+settings-mention-on = 💬 Ответы: только на упоминания и ответы боту
+settings-mention-off = 💬 Ответы: на все сообщения
+settings-cooldown = ⏱ Пауза между ответами: { $cooldown }
+settings-cooldown-off = нет
+settings-cooldown-secs = { $secs } с
+
+## Сокращённые числа
+
+number-thousand = { $number } тыс.
+number-million = { $number } млн
+number-billion = { $number } млрд
+number-trillion = { $number } трлн
+
+## Превью ссылок
+
+twitter-info =
+    📨Twitter
+    Название: <a href="https://twitter.com/{ $username }">{ $username }</a>
+    Подписчики: <b>{ $followers }</b>
+    Твитов: <b>{ $tweets }</b>
+    Создан: { $created }
+twitter-created-unknown = время не найдено
+# Ячейки таблицы цен без разметки
+eden-floor = 💎Флор: <b>{ $floor } sol</b>
+eden-price-column = Цена, sol
+eden-count-column = Предметов
+eden-up-to = до { $price }
+eden-over = больше { $price }
+parse-partial = ⏳<i>Часть данных не удалось загрузить, она пропущена</i>
+
+## Инлайн-режим
+
+inline-price-title = Цена { $symbol }
+inline-price-description = Цена, изменение за 24 часа, максимум и минимум
+inline-chart-title = График { $symbol } за 24 часа
+inline-convert-title = Стоимость в USD
+
+## Кнопки под ценой, графиком и /priceall
+
+button-refresh = 🔄 Обновить
+button-chart-24h = 24ч
+button-chart-7d = 7д
+button-chart-30d = 30д
+button-add = ➕ В список
+button-remove = ➖ Убрать из списка
+button-alert = 🔔 Оповещение
+button-cancel = ✖ Отмена
+button-done = ✔ Готово
+
+## Диалоги, /alert и /alerts
+
+dialogue-cancelled = Отменено
+dialogue-nothing = Нечего отменять
+alert-ask-symbol = За какой монетой следить? Напишите символ, например btc
+alert-unknown = Неизвестная монета { $symbol }, напишите другой символ
+alert-ask-price =
+    { $symbol } сейчас стоит { $price }
+    При какой цене вам сообщить?
+alert-invalid-price = Напишите цену числом, например 70000
+alert-created = 🔔 Сообщу, когда { $symbol } { $direction } { $target }
+alert-limit =
+    У вас уже { $max ->
+        [one] { $max } оповещение
+        [few] { $max } оповещения
+       *[many] { $max } оповещений
+    }, удалите одно в /alerts
+alerts-empty = У вас нет оповещений о цене, создайте через /alert
+alerts-header = 🔔<b>Ваши оповещения о цене</b>
+alert-triggered =
+    🔔 <b>{ $symbol }</b> { $direction } { $target }
+    Цена сейчас: { $price }
+
+## Сводки в каналах, команды администратора
+
+channel-header = 📊<b>Сводка рынка, { $fiat }</b>
+channel-gainers = 🚀 Лидеры роста: { $movers }
+channel-losers = 📉 Лидеры падения: { $movers }
+channel-usage =
+    Введите /addchannel @канал ЧЧ:ММ монеты, например
+    /addchannel @mychannel 09:00 btc eth sol
+    Дополнительно: часовой пояс (Europe/Kiev), валюта (eur), pin и nochart
+channel-too-many = Не больше { $max } монет в сводке
+channel-not-found = Не удалось найти { $channel }, бот в нём состоит?
+channel-not-channel = { $channel } не канал
+channel-not-admin = Сначала сделайте бота администратором { $channel }
+channel-added = Сводка для { $title } в { $time } запланирована, id <code>{ $id }</code>
+channels-empty = Сводок в каналах нет, добавьте через /addchannel
+channels-header = 📣<b>Сводки в каналах</b>
+channels-item = <code>{ $id }</code> { $title }, { $time }: { $symbols } ({ $flags })
+channel-flag-chart = график
+channel-flag-pin = закрепляется
+removechannel-usage = Введите /removechannel id, id показывает /channels
+channel-removed = Сводка удалена
+channel-unknown = Сводка { $id } не найдена, см. /channels
+channel-posted = Сводка опубликована
@@ -0,0 +1,226 @@
+# Українські повідомлення, відсутні беруться з en.ftl.
+
+## /start, /help та меню команд
+
+start-greeting = Привіт! Додай монети через /addcurrency і дивись ціни через /priceall
+help-header = <b>Команди</b>
+cmd-help = показати це повідомлення
+cmd-start = реєстрація
+cmd-chart = графік ціни за добу
+cmd-price = ціна монети
+cmd-addcurrency = додати монети, наприклад /addcurrency btc eth sol
+cmd-removecurrency = видалити монету
+cmd-priceall = ціни всіх ваших монет
+cmd-notify = увімкнути або вимкнути розсилку, наприклад /notify off
+cmd-language = обрати мову бота
+cmd-settings = розсилка, часовий пояс, валюта та інші налаштування
+cmd-alert = повідомити, коли монета досягне ціни, наприклад /alert btc 70000
+cmd-alerts = список сповіщень про ціну
+cmd-cancel = скасувати поточне питання
+
+## /language
+
+language-choose = Оберіть мову
+language-set = Мову змінено на українську
+
+## Спільні помилки команд
+
+user-error = Не вдалося отримати користувача
+chat-error = Не вдалося отримати налаштування групи, нічого не змінено
+rate-limited = ⏳ Не так швидко, будь ласка. Спробуйте знову через { $secs } с
+rate-banned = 🚫 Забагато запитів, бот не відповідає вам { $minutes } хв
+
+## /price та /chart
+
+price-info =
+    💰Монета: <b>{ $symbol }</b>
+    💵Ціна { $fiat }: <code>{ $price }</code>
+    📊Зміна за 24 години: { $change }
+    📈Максимум (24 години): <code>{ $high }</code>
+    📉Мінімум (24 години): <code>{ $low }</code>
+price-market-cap = 🏦Капіталізація: <code>{ $cap }</code>
+price-not-found = Не вдалося отримати дані для { $symbol }: монету не знайдено
+price-error = Не вдалося отримати ціну { $symbol }, спробуйте пізніше
+chart-title = Графік ціни { $symbol } за 24 години
+chart-title-week = Графік ціни { $symbol } за 7 днів
+chart-title-month = Графік ціни { $symbol } за 30 днів
+
+## /priceall та щоденна розсилка
+
+priceall-empty = У вас немає монет, додайте їх через /addcurrency назва
+priceall-error = Помилка, можливо у вас немає жодної відповідної монети
+priceall-header = 📈<b>Ваші монети, { $fiat }</b>
+digest-unsubscribe = Щоб вимкнути сповіщення, напишіть /notify off
+
+## /addcurrency та /removecurrency
+
+addcurrency-usage =
+    Напишіть /addcurrency [назва ...]
+    Приклад: /addcurrency btc eth sol
+currency-added = Додали валюту <b>{ $symbols }</b>
+currency-already-added = { $symbol } вже є у вашому списку
+currency-unknown = Невідома монета { $symbol }
+currency-unknown-suggest = Невідома монета { $symbol }, можливо { $suggestions }?
+currency-ambiguous = Символ { $symbol } використовують кілька монет, оберіть потрібну
+currency-list-full =
+    Ваш список заповнений ({ $max ->
+        [one] { $max } монета
+        [few] { $max } монети
+       *[many] { $max } монет
+    })
+currency-list-full-rejected =
+    Ваш список заповнений ({ $max ->
+        [one] { $max } монета
+        [few] { $max } монети
+       *[many] { $max } монет
+    }), не додані: { $symbols }
+asset-unknown = Невідома монета, спробуйте /addcurrency ще раз
+asset-added = Додали валюту <b>{ $symbol }</b> ({ $name })
+currency-removed = Видалили валюту "{ $symbol }"
+addcurrency-ask = Які монети додати? Напишіть символи, наприклад btc eth sol
+removecurrency-choose = Натисніть на монети, які потрібно прибрати
+removecurrency-empty = Ваш список порожній, додайте монети через /addcurrency
+
+## /notify
+
+notify-on = Щоденну розсилку увімкнено
+notify-off = Щоденну розсилку вимкнено
+notify-state-on = Щоденну розсилку увімкнено, щоб вимкнути, напишіть /notify off
+notify-state-off = Щоденну розсилку вимкнено, щоб увімкнути, напишіть /notify on
+notify-usage = Напишіть /notify on або /notify off
+
+
+## /settings
+
+settings-title = ⚙️<b>Налаштування</b>
+settings-digest-on = 🔔 Щоденна розсилка: увімкнена, о { $time } ({ $timezone })
+settings-digest-off = 🔔 Щоденна розсилка: вимкнена
+settings-fiat = 💵 Ціни в: { $fiat }
+settings-language = 🗣 Мова: { $language }
+settings-theme = 🎨 Графіки: { $theme }
+settings-expanders = 🔗 Відповіді на повідомлення: { $expanders }
+settings-server-time = час сервера
+settings-default = за замовчуванням
+settings-none = немає
+settings-choose-time = Оберіть час розсилки
+settings-choose-timezone = Оберіть часовий пояс
+settings-choose-fiat = Оберіть валюту цін
+settings-choose-expanders = Оберіть, на що бот відповідає в повідомленнях
+theme-light = світлі
+theme-dark = темні
+expander-currency = суми монет
+expander-twitter = посилання Twitter
+expander-eden = посилання Magic Eden
+settings-button-digest-on = 🔔 Увімкнути розсилку
+settings-button-digest-off = 🔕 Вимкнути розсилку
+settings-button-time = 🕚 Час розсилки
+settings-button-timezone = 🌍 Часовий пояс
+settings-button-fiat = 💵 Валюта
+settings-button-language = 🗣 Мова
+settings-button-theme-light = ☀️ Світлі графіки
+settings-button-theme-dark = 🌙 Темні графіки
+settings-button-expanders = 🔗 Відповіді на повідомлення
+settings-button-back = ⬅️ Назад
+settings-button-mention-on = 💬 Відповідати лише на згадки
+settings-button-mention-off = 💬 Відповідати на всі повідомлення
+
+## Групи
+
+group-admin-only = Список і налаштування групи можуть змінювати лише адміністратори чату
+group-private-only = Ця команда працює в особистому чаті зі мною: @{ $bot }
+settings-group-title = ⚙️<b>Налаштування { $title }</b>
+settings-mention-on = 💬 Відповіді: лише на згадки та відповіді боту
+settings-mention-off = 💬 Відповіді: на всі повідомлення
+settings-cooldown = ⏱ Пауза між відповідями: { $cooldown }
+settings-cooldown-off = немає
+settings-cooldown-secs = { $secs } с
+
+## Скорочені числа
+
+number-thousand = { $number } тис.
+number-million = { $number } млн
+number-billion = { $number } млрд
+number-trillion = { $number } трлн
+
+## Превʼю посилань
+
+twitter-info =
+    📨Twitter
+    Назва: <a href="https://twitter.com/{ $username }">{ $username }</a>
+    Підписники: <b>{ $followers }</b>
+    Твітів: <b>{ $tweets }</b>
+    Створено: { $created }
+twitter-created-unknown = час не знайдено
+# Клітинки таблиці цін без розмітки
+eden-floor = 💎Флор: <b>{ $floor } sol</b>
+eden-price-column = Ціна, sol
+eden-count-column = Предметів
+eden-up-to = до { $price }
+eden-over = більше { $price }
+parse-partial = ⏳<i>Частину даних не вдалося завантажити, її пропущено</i>
+
+## Інлайн-режим
+
+inline-price-title = Ціна { $symbol }
+inline-price-description = Ціна, зміна за 24 години, максимум і мінімум
+inline-chart-title = Графік { $symbol } за 24 години
+inline-convert-title = Вартість у USD
+
+## Кнопки під ціною, графіком і /priceall
+
+button-refresh = 🔄 Оновити
+button-chart-24h = 24г
+button-chart-7d = 7д
+button-chart-30d = 30д
+button-add = ➕ До списку
+button-remove = ➖ Прибрати зі списку
+button-alert = 🔔 Сповіщення
+button-cancel = ✖ Скасувати
+button-done = ✔ Готово
+
+## Діалоги, /alert та /alerts
+
+dialogue-cancelled = Скасовано
+dialogue-nothing = Нічого скасовувати
+alert-ask-symbol = За якою монетою стежити? Напишіть символ, наприклад btc
+alert-unknown = Невідома монета { $symbol }, напишіть інший символ
+alert-ask-price =
+    { $symbol } зараз коштує { $price }
+    За якої ціни вам повідомити?
+alert-invalid-price = Напишіть ціну числом, наприклад 70000
+alert-created = 🔔 Повідомлю, коли { $symbol } { $direction } { $target }
+alert-limit =
+    У вас вже { $max ->
+        [one] { $max } сповіщення
+        [few] { $max } сповіщення
+       *[many] { $max } сповіщень
+    }, видаліть одне в /alerts
+alerts-empty = У вас немає сповіщень про ціну, створіть через /alert
+alerts-header = 🔔<b>Ваші сповіщення про ціну</b>
+alert-triggered =
+    🔔 <b>{ $symbol }</b> { $direction } { $target }
+    Ціна зараз: { $price }
+
+## Зведення в каналах, команди адміністратора
+
+channel-header = 📊<b>Зведення ринку, { $fiat }</b>
+channel-gainers = 🚀 Лідери зростання: { $movers }
+channel-losers = 📉 Лідери падіння: { $movers }
+channel-usage =
+    Введіть /addchannel @канал ГГ:ХХ монети, наприклад
+    /addchannel @mychannel 09:00 btc eth sol
+    Додатково: часовий пояс (Europe/Kiev), валюта (eur), pin і nochart
+channel-too-many = Не більше { $max } монет у зведенні
+channel-not-found = Не вдалося знайти { $channel }, бот у ньому є?
+channel-not-channel = { $channel } не канал
+channel-not-admin = Спершу зробіть бота адміністратором { $channel }
+channel-added = Зведення для { $title } о { $time } заплановано, id <code>{ $id }</code>
+channels-empty = Зведень у каналах немає, додайте через /addchannel
+channels-header = 📣<b>Зведення в каналах</b>
+channels-item = <code>{ $id }</code> { $title }, { $time }: { $symbols } ({ $flags })
+channel-flag-chart = графік
+channel-flag-pin = закріплюється
+removechannel-usage = Введіть /removechannel id, id показує /channels
+channel-removed = Зведення видалено
+channel-unknown = Зведення { $id } не знайдено, див. /channels
+channel-posted = Зведення опубліковано
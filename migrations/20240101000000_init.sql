@@ -0,0 +1,29 @@
+-- Users and their watchlists, mirrors the MongoDB `user` and `asset` collections.
+-- Timestamps are unix milliseconds and flags are 0/1 so the same schema works
+-- on SQLite and Postgres through the sqlx Any driver.
+CREATE TABLE IF NOT EXISTS users (
+    user_id BIGINT PRIMARY KEY,
+    username TEXT NOT NULL,
+    notification BIGINT NOT NULL DEFAULT 0,
+    created_at BIGINT NOT NULL,
+    updated_at BIGINT NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS user_currencies (
+    user_id BIGINT NOT NULL REFERENCES users (user_id) ON DELETE CASCADE,
+    symbol TEXT NOT NULL,
+    cmc_id BIGINT,
+    position BIGINT NOT NULL,
+    PRIMARY KEY (user_id, symbol)
+);
+
+CREATE TABLE IF NOT EXISTS assets (
+    cmc_id BIGINT PRIMARY KEY,
+    symbol TEXT NOT NULL,
+    name TEXT NOT NULL,
+    slug TEXT NOT NULL,
+    asset_rank BIGINT,
+    updated_at BIGINT NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS assets_symbol ON assets (symbol);
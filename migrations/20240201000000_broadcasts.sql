@@ -0,0 +1,9 @@
+-- Unfinished digest and /sendall runs. `pending` is a JSON array of user ids,
+-- `digest` = 1 means the message is built per user and `text` is unused.
+CREATE TABLE IF NOT EXISTS broadcasts (
+    id TEXT PRIMARY KEY,
+    digest BIGINT NOT NULL DEFAULT 0,
+    text TEXT NOT NULL DEFAULT '',
+    pending TEXT NOT NULL,
+    updated_at BIGINT NOT NULL
+);
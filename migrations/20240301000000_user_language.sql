@@ -0,0 +1,2 @@
+-- Language of the bot replies, an ISO 639-1 code or '' until the user picks one.
+ALTER TABLE users ADD COLUMN language TEXT NOT NULL DEFAULT '';
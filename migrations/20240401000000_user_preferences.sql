@@ -0,0 +1,9 @@
+-- Preferences changed with /settings. `notification` holds the digest flag and
+-- `language` the language, empty text means the default of the bot.
+ALTER TABLE users ADD COLUMN digest_time TEXT NOT NULL DEFAULT '';
+ALTER TABLE users ADD COLUMN timezone TEXT NOT NULL DEFAULT '';
+ALTER TABLE users ADD COLUMN fiat TEXT NOT NULL DEFAULT 'USD';
+ALTER TABLE users ADD COLUMN chart_theme TEXT NOT NULL DEFAULT 'light';
+ALTER TABLE users ADD COLUMN expand_currency BIGINT NOT NULL DEFAULT 1;
+ALTER TABLE users ADD COLUMN expand_twitter BIGINT NOT NULL DEFAULT 1;
+ALTER TABLE users ADD COLUMN expand_eden BIGINT NOT NULL DEFAULT 1;
@@ -0,0 +1,18 @@
+-- Open dialogues, one per chat. `state` is the JSON of `DialogueState`.
+CREATE TABLE IF NOT EXISTS dialogues (
+    chat_id BIGINT PRIMARY KEY,
+    state TEXT NOT NULL,
+    updated_at BIGINT NOT NULL
+);
+
+-- Price alerts, `above` = 1 fires when the price rises to `target`.
+CREATE TABLE IF NOT EXISTS alerts (
+    id TEXT PRIMARY KEY,
+    user_id BIGINT NOT NULL,
+    symbol TEXT NOT NULL,
+    fiat TEXT NOT NULL,
+    target DOUBLE PRECISION NOT NULL,
+    above BIGINT NOT NULL,
+    created_at BIGINT NOT NULL
+);
+CREATE INDEX IF NOT EXISTS alerts_user_id ON alerts (user_id);
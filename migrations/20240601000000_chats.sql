@@ -0,0 +1,13 @@
+-- Group chats. The watchlist, its CoinMarketCap ids and the preferences are
+-- JSON, groups are few and always loaded whole.
+CREATE TABLE IF NOT EXISTS chats (
+    chat_id BIGINT PRIMARY KEY,
+    title TEXT NOT NULL,
+    currency TEXT NOT NULL,
+    cmc_ids TEXT NOT NULL,
+    preferences TEXT NOT NULL,
+    mention_only BIGINT NOT NULL,
+    cooldown_secs BIGINT,
+    created_at BIGINT NOT NULL,
+    updated_at BIGINT NOT NULL
+);
@@ -0,0 +1,15 @@
+-- Scheduled channel posts. `symbols` is JSON, `chart` and `pin` are 0 or 1.
+CREATE TABLE IF NOT EXISTS channel_posts (
+    id TEXT PRIMARY KEY,
+    chat_id BIGINT NOT NULL,
+    title TEXT NOT NULL,
+    symbols TEXT NOT NULL,
+    time TEXT NOT NULL,
+    timezone TEXT,
+    fiat TEXT NOT NULL,
+    language TEXT NOT NULL,
+    chart BIGINT NOT NULL,
+    pin BIGINT NOT NULL,
+    last_message_id BIGINT,
+    created_at BIGINT NOT NULL
+);
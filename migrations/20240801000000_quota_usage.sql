@@ -0,0 +1,8 @@
+-- Upstream API usage, one row per provider and UTC day
+CREATE TABLE IF NOT EXISTS quota_usage (
+    provider TEXT NOT NULL,
+    day TEXT NOT NULL,
+    calls BIGINT NOT NULL,
+    credits BIGINT NOT NULL,
+    PRIMARY KEY (provider, day)
+);
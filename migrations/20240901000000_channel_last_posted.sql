@@ -0,0 +1,2 @@
+-- Time of the latest scheduled post, a schedule is published once per due time
+ALTER TABLE channel_posts ADD COLUMN last_posted_at BIGINT;
@@ -0,0 +1,522 @@
+use crate::commands::currency::parse_symbols;
+use crate::commands::keyboards::DialogueAction;
+use crate::config::Config;
+use crate::i18n::Lang;
+use crate::models::alert::Alert;
+use crate::models::dialogue::DialogueState;
+use crate::models::preferences::Fiat;
+use crate::storage::{AlertRepository, UserRepository};
+use crate::tools::html;
+use crate::tools::metrics::Upstream;
+use crate::tools::numbers;
+use crate::tools::supervisor::JobResult;
+use log::{debug, info, warn};
+use reqwest::Url;
+use std::collections::{BTreeSet, HashMap};
+use std::error::Error;
+use std::sync::Arc;
+use teloxide::prelude::*;
+use teloxide::types::{InlineKeyboardButton, InlineKeyboardMarkup, ParseMode};
+use tokio::time;
+
+/// Most alerts a user can have at once
+pub const MAX_ALERTS: usize = 10;
+
+/// Callback data prefix of the remove buttons under /alerts
+pub const ALERTS_CALLBACK_PREFIX: &str = "alerts:remove:";
+
+/// Prices keyed by coin and fiat currency
+type Prices = HashMap<(String, Fiat), f64>;
+
+/// Reply to a step of the alert dialogue
+///
+/// # Fields
+///
+/// * `text` - Reply in Telegram HTML
+/// * `next` - Question asked by the reply, `Idle` once the dialogue is over
+///
+#[derive(Debug, PartialEq)]
+pub struct AlertReply {
+    pub text: String,
+    pub next: DialogueState,
+}
+
+impl AlertReply {
+    fn new(text: String, next: DialogueState) -> Self {
+        Self { text, next }
+    }
+
+    fn done(text: String) -> Self {
+        Self::new(text, DialogueState::Idle)
+    }
+}
+
+/// Current prices of coins from CryptoCompare
+///
+/// # Arguments
+///
+/// * `symbols` - Coins, upper case
+/// * `fiats` - Currencies of the prices
+/// * `config` - Bot configuration
+///
+/// # Returns
+///
+/// * `Result<Prices, ...>` - Price per coin and fiat, unknown coins are left out
+pub async fn fetch_prices(
+    symbols: &[String],
+    fiats: &[Fiat],
+    config: &Config,
+) -> Result<Prices, Box<dyn Error + Send + Sync>> {
+    let fsyms = symbols.join(",");
+    let tsyms = fiats
+        .iter()
+        .map(|fiat| fiat.code())
+        .collect::<Vec<_>>()
+        .join(",");
+    let url = Url::parse_with_params(
+        "https://min-api.cryptocompare.com/data/pricemulti",
+        &[("fsyms", fsyms.as_str()), ("tsyms", tsyms.as_str())],
+    )?;
+
+    let http = config.http();
+    let response = http.send(Upstream::CryptoCompare, http.get(url)).await?;
+    if !response.status().is_success() {
+        return Err(format!(
+            "Error fetching prices for {}: Status code {}",
+            fsyms,
+            response.status().as_u16()
+        )
+        .into());
+    }
+
+    let response_json = response.json::<serde_json::Value>().await?;
+    let mut prices = Prices::new();
+    for symbol in symbols {
+        for fiat in fiats {
+            if let Some(price) = response_json[symbol][fiat.code()].as_f64() {
+                prices.insert((symbol.clone(), *fiat), price);
+            }
+        }
+    }
+    Ok(prices)
+}
+
+/// Current price of one coin, `None` if it is unknown
+async fn current_price(
+    symbol: &str,
+    fiat: Fiat,
+    config: &Config,
+) -> Result<Option<f64>, Box<dyn Error + Send + Sync>> {
+    let prices = fetch_prices(&[symbol.to_string()], &[fiat], config).await?;
+    Ok(prices.get(&(symbol.to_string(), fiat)).copied())
+}
+
+/// Amount with the sign of the fiat currency
+fn amount(value: f64, fiat: Fiat, lang: Lang) -> String {
+    format!("{} {}", fiat.sign(), numbers::price(value, lang))
+}
+
+/// `≥` for alerts on a rise, `≤` on a fall
+fn direction(alert: &Alert) -> &'static str {
+    if alert.above {
+        "≥"
+    } else {
+        "≤"
+    }
+}
+
+/// /alert command handler
+///
+/// `/alert` asks for the coin, `/alert btc` for the target price and
+/// `/alert btc 70000` sets the alert right away.
+///
+/// # Arguments
+///
+/// * `user_id` - User id
+/// * `args` - Text after the command
+/// * `lang` - Reply language
+/// * `fiat` - Currency of the target price
+/// * `alerts` - Alert storage
+/// * `config` - Bot configuration
+///
+/// # Returns
+///
+/// * `AlertReply` - Reply and the next question
+pub async fn alert_command(
+    user_id: i64,
+    args: &str,
+    lang: Lang,
+    fiat: Fiat,
+    alerts: &Arc<dyn AlertRepository>,
+    config: &Config,
+) -> AlertReply {
+    let mut words = args.split_whitespace();
+    let symbol = match words.next() {
+        Some(symbol) => symbol,
+        None => {
+            return AlertReply::new(lang.tr("alert-ask-symbol"), DialogueState::AlertSymbol);
+        }
+    };
+
+    let reply = alert_symbol_step(user_id, symbol, lang, fiat, alerts, config).await;
+    let target = words.collect::<Vec<_>>().join(" ");
+    match reply.next {
+        DialogueState::AlertPrice { symbol } if !target.is_empty() => {
+            alert_price_step(user_id, &symbol, &target, lang, fiat, alerts, config).await
+        }
+        _ => reply,
+    }
+}
+
+/// Coin of the alert, asks for the target price next
+///
+/// # Arguments
+///
+/// * `user_id` - User id
+/// * `text` - Answer with the coin
+/// * `lang` - Reply language
+/// * `fiat` - Currency of the prices
+/// * `alerts` - Alert storage, for the `MAX_ALERTS` limit
+/// * `config` - Bot configuration
+///
+/// # Returns
+///
+/// * `AlertReply` - The current price and the question for the target, or
+///   the question again for an unknown coin
+pub async fn alert_symbol_step(
+    user_id: i64,
+    text: &str,
+    lang: Lang,
+    fiat: Fiat,
+    alerts: &Arc<dyn AlertRepository>,
+    config: &Config,
+) -> AlertReply {
+    let symbol = match parse_symbols(text)
+        .into_iter()
+        .find(|symbol| symbol.chars().all(|c| c.is_ascii_alphanumeric()))
+    {
+        Some(symbol) => symbol,
+        None => return AlertReply::new(lang.tr("alert-ask-symbol"), DialogueState::AlertSymbol),
+    };
+
+    match alerts.get_user_alerts(user_id).await {
+        Ok(current) if current.len() >= MAX_ALERTS => {
+            return AlertReply::done(
+                lang.tr_with("alert-limit", &[("max", (MAX_ALERTS as i64).into())]),
+            );
+        }
+        Ok(_) => {}
+        Err(err) => return AlertReply::done(html::escape(&err.to_string())),
+    }
+
+    match current_price(&symbol, fiat, config).await {
+        Ok(Some(price)) => AlertReply::new(
+            lang.tr_with(
+                "alert-ask-price",
+                &[
+                    ("symbol", symbol.clone().into()),
+                    ("price", amount(price, fiat, lang).into()),
+                ],
+            ),
+            DialogueState::AlertPrice { symbol },
+        ),
+        Ok(None) => AlertReply::new(
+            lang.tr_with("alert-unknown", &[("symbol", symbol.into())]),
+            DialogueState::AlertSymbol,
+        ),
+        Err(err) => {
+            debug!("alert price error {}", err);
+            AlertReply::done(lang.tr_with("price-error", &[("symbol", symbol.into())]))
+        }
+    }
+}
+
+/// Target price of the alert, saves the alert
+///
+/// # Arguments
+///
+/// * `user_id` - User id
+/// * `symbol` - Coin of the alert
+/// * `text` - Answer with the target price
+/// * `lang` - Reply language, picks the number separators
+/// * `fiat` - Currency of the target
+/// * `alerts` - Alert storage
+/// * `config` - Bot configuration
+///
+/// # Returns
+///
+/// * `AlertReply` - Confirmation, or the question again for an invalid price
+pub async fn alert_price_step(
+    user_id: i64,
+    symbol: &str,
+    text: &str,
+    lang: Lang,
+    fiat: Fiat,
+    alerts: &Arc<dyn AlertRepository>,
+    config: &Config,
+) -> AlertReply {
+    let target = match numbers::parse_amount(text, lang) {
+        Some(target) => target,
+        None => {
+            return AlertReply::new(
+                lang.tr("alert-invalid-price"),
+                DialogueState::AlertPrice {
+                    symbol: symbol.to_string(),
+                },
+            );
+        }
+    };
+
+    let current = match current_price(symbol, fiat, config).await {
+        Ok(Some(current)) => current,
+        Ok(None) => {
+            return AlertReply::done(lang.tr_with("alert-unknown", &[("symbol", symbol.into())]))
+        }
+        Err(err) => {
+            debug!("alert price error {}", err);
+            return AlertReply::done(lang.tr_with("price-error", &[("symbol", symbol.into())]));
+        }
+    };
+
+    let alert = Alert::new(user_id, symbol.to_string(), fiat, target, current);
+    match alerts.add_alert(&alert).await {
+        Ok(()) => AlertReply::done(lang.tr_with(
+            "alert-created",
+            &[
+                ("symbol", alert.symbol.clone().into()),
+                ("direction", direction(&alert).into()),
+                ("target", amount(target, fiat, lang).into()),
+            ],
+        )),
+        Err(err) => AlertReply::done(html::escape(&err.to_string())),
+    }
+}
+
+/// /alerts command handler, lists the alerts with a remove button each
+///
+/// # Arguments
+///
+/// * `user_id` - User id
+/// * `lang` - Reply language
+/// * `alerts` - Alert storage
+///
+/// # Returns
+///
+/// * `(String, Option<InlineKeyboardMarkup>)` - List of alerts, no buttons without alerts
+pub async fn alerts_command(
+    user_id: i64,
+    lang: Lang,
+    alerts: &Arc<dyn AlertRepository>,
+) -> (String, Option<InlineKeyboardMarkup>) {
+    let user_alerts = match alerts.get_user_alerts(user_id).await {
+        Ok(user_alerts) => user_alerts,
+        Err(err) => return (html::escape(&err.to_string()), None),
+    };
+    if user_alerts.is_empty() {
+        return (lang.tr("alerts-empty"), None);
+    }
+
+    let mut text = lang.tr("alerts-header");
+    let mut rows = vec![];
+    for alert in user_alerts.iter() {
+        let label = format!(
+            "{} {} {}",
+            alert.symbol,
+            direction(alert),
+            amount(alert.target, alert.fiat, lang)
+        );
+        text += &format!("\n{}", html::escape(&label));
+        rows.push(vec![InlineKeyboardButton::callback(
+            format!("✖ {}", label),
+            DialogueAction::RemoveAlert(alert.id.clone()).data(),
+        )]);
+    }
+    (text, Some(InlineKeyboardMarkup::new(rows)))
+}
+
+/// Remove button under /alerts
+///
+/// # Arguments
+///
+/// * `user_id` - User pressing the button, only their alerts are removed
+/// * `id` - Alert id
+/// * `lang` - Reply language
+/// * `alerts` - Alert storage
+///
+/// # Returns
+///
+/// * `(String, Option<InlineKeyboardMarkup>)` - The list without the alert
+pub async fn remove_alert_command(
+    user_id: i64,
+    id: &str,
+    lang: Lang,
+    alerts: &Arc<dyn AlertRepository>,
+) -> (String, Option<InlineKeyboardMarkup>) {
+    let owned = alerts
+        .get_user_alerts(user_id)
+        .await
+        .map(|user_alerts| user_alerts.iter().any(|alert| alert.id == id))
+        .unwrap_or(false);
+    if owned {
+        if let Err(err) = alerts.delete_alert(id).await {
+            warn!("Error removing alert {}: {}", id, err);
+        }
+    }
+    alerts_command(user_id, lang, alerts).await
+}
+
+/// Alerts whose target the prices reached
+fn triggered<'a>(alerts: &'a [Alert], prices: &Prices) -> Vec<(&'a Alert, f64)> {
+    alerts
+        .iter()
+        .filter_map(|alert| {
+            let price = *prices.get(&(alert.symbol.clone(), alert.fiat))?;
+            alert.is_triggered(price).then_some((alert, price))
+        })
+        .collect()
+}
+
+/// Send the triggered alerts and remove them
+async fn check_alerts(
+    bot: &Bot,
+    alerts: &Arc<dyn AlertRepository>,
+    users: &Arc<dyn UserRepository>,
+    config: &Config,
+) -> JobResult {
+    let all = alerts.get_alerts().await?;
+    if all.is_empty() {
+        return Ok(());
+    }
+    let symbols: Vec<String> = all
+        .iter()
+        .map(|alert| alert.symbol.clone())
+        .collect::<BTreeSet<_>>()
+        .into_iter()
+        .collect();
+    let fiats: Vec<Fiat> = Fiat::ALL
+        .into_iter()
+        .filter(|fiat| all.iter().any(|alert| alert.fiat == *fiat))
+        .collect();
+    let prices = fetch_prices(&symbols, &fiats, config).await?;
+
+    for (alert, price) in triggered(&all, &prices) {
+        // Removed first, so an alert is never sent twice
+        if !alerts.delete_alert(&alert.id).await? {
+            continue;
+        }
+        let lang = users
+            .get_language(alert.user_id)
+            .await
+            .ok()
+            .flatten()
+            .unwrap_or_default();
+        let text = lang.tr_with(
+            "alert-triggered",
+            &[
+                ("symbol", alert.symbol.clone().into()),
+                ("direction", direction(alert).into()),
+                ("target", amount(alert.target, alert.fiat, lang).into()),
+                ("price", amount(price, alert.fiat, lang).into()),
+            ],
+        );
+        let sent = bot
+            .send_message(UserId(alert.user_id as u64), text)
+            .parse_mode(ParseMode::Html)
+            .await;
+        match sent {
+            Ok(_) => info!("Alert {} sent", alert.id),
+            Err(err) => debug!("Error sending alert {}: {}", alert.id, err),
+        }
+    }
+    Ok(())
+}
+
+/// Price alert job, run by the supervisor
+///
+/// Checks every alert each `schedule.alert_check_secs` with one price request.
+pub async fn alert_job(
+    bot: Bot,
+    alerts: Arc<dyn AlertRepository>,
+    users: Arc<dyn UserRepository>,
+    config: Arc<Config>,
+) -> JobResult {
+    loop {
+        time::sleep(config.alert_check_interval()).await;
+        check_alerts(&bot, &alerts, &users, &config).await?;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::memory::MemoryStorage;
+
+    #[test]
+    fn test_triggered() {
+        let alerts = vec![
+            Alert::new(1, "BTC".to_string(), Fiat::Usd, 70000.0, 65000.0),
+            Alert::new(1, "BTC".to_string(), Fiat::Usd, 60000.0, 65000.0),
+            Alert::new(2, "ETH".to_string(), Fiat::Eur, 3000.0, 2500.0),
+        ];
+        let mut prices = Prices::new();
+        prices.insert(("BTC".to_string(), Fiat::Usd), 70100.0);
+        prices.insert(("ETH".to_string(), Fiat::Usd), 3100.0);
+
+        let fired: Vec<f64> = triggered(&alerts, &prices)
+            .iter()
+            .map(|(alert, _)| alert.target)
+            .collect();
+        assert_eq!(fired, vec![70000.0]);
+    }
+
+    #[tokio::test]
+    async fn test_alert_dialogue_steps() {
+        let alerts: Arc<dyn AlertRepository> = Arc::new(MemoryStorage::new());
+        let config = Config::default();
+
+        let reply = alert_command(1, "", Lang::En, Fiat::Usd, &alerts, &config).await;
+        assert_eq!(reply.next, DialogueState::AlertSymbol);
+        let reply = alert_symbol_step(1, "<b>", Lang::En, Fiat::Usd, &alerts, &config).await;
+        assert_eq!(reply.next, DialogueState::AlertSymbol);
+
+        // The price is checked before any request
+        let reply = alert_price_step(1, "BTC", "soon", Lang::En, Fiat::Usd, &alerts, &config).await;
+        assert_eq!(
+            reply,
+            AlertReply::new(
+                Lang::En.tr("alert-invalid-price"),
+                DialogueState::AlertPrice {
+                    symbol: "BTC".to_string()
+                }
+            )
+        );
+
+        for i in 0..MAX_ALERTS {
+            let alert = Alert::new(1, format!("C{}", i), Fiat::Usd, 1.0, 2.0);
+            alerts.add_alert(&alert).await.unwrap();
+        }
+        let reply = alert_symbol_step(1, "btc", Lang::En, Fiat::Usd, &alerts, &config).await;
+        assert_eq!(reply.next, DialogueState::Idle);
+        assert!(reply.text.contains("10"));
+    }
+
+    #[tokio::test]
+    async fn test_alerts_list() {
+        let alerts: Arc<dyn AlertRepository> = Arc::new(MemoryStorage::new());
+        let (text, keyboard) = alerts_command(1, Lang::En, &alerts).await;
+        assert_eq!(text, Lang::En.tr("alerts-empty"));
+        assert!(keyboard.is_none());
+
+        let alert = Alert::new(1, "btc".to_string(), Fiat::Eur, 70000.0, 65000.0);
+        alerts.add_alert(&alert).await.unwrap();
+        let (text, keyboard) = alerts_command(1, Lang::En, &alerts).await;
+        assert!(text.ends_with("BTC ≥ € 70,000.00"));
+        assert_eq!(keyboard.unwrap().inline_keyboard.len(), 1);
+
+        // Someone else's button removes nothing
+        remove_alert_command(2, &alert.id, Lang::En, &alerts).await;
+        assert_eq!(alerts.get_alerts().await.unwrap().len(), 1);
+        let (text, _) = remove_alert_command(1, &alert.id, Lang::En, &alerts).await;
+        assert_eq!(text, Lang::En.tr("alerts-empty"));
+    }
+}
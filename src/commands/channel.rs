@@ -0,0 +1,571 @@
+use crate::commands::chart::{render_chart, ChartPeriod};
+use crate::commands::group::is_chat_admin;
+use crate::commands::price_all::{fetch_quotes, prices_table, Quote};
+use crate::commands::send_all::until_next_minute;
+use crate::config::Config;
+use crate::i18n::Lang;
+use crate::models::channel::ChannelPost;
+use crate::models::preferences::{parse_time, ChartTheme, Fiat};
+use crate::storage::{ChannelRepository, StorageResult};
+use crate::tools::html;
+use crate::tools::numbers;
+use crate::tools::supervisor::JobResult;
+use chrono::{DateTime, Days, Local, TimeZone, Utc};
+use chrono_tz::Tz;
+use log::{info, warn};
+use mongodb::bson;
+use std::collections::HashMap;
+use std::sync::Arc;
+use teloxide::prelude::*;
+use teloxide::types::{InputFile, Me, MessageId, ParseMode, Recipient};
+use tokio::time;
+
+/// Gainers and losers listed under a channel summary
+const TOP_MOVERS: usize = 3;
+
+/// Arguments of /addchannel
+///
+/// `/addchannel @channel 09:00 btc eth sol Europe/Kiev eur pin nochart`, the
+/// time zone, fiat currency and flags are optional and in any order.
+///
+/// # Fields
+///
+/// * `target` - `@username` or id of the channel
+/// * `time` - Local post time, `HH:MM`
+/// * `timezone` - IANA time zone of `time`
+/// * `fiat` - Currency of the prices
+/// * `symbols` - Coins of the summary
+/// * `chart` - Post a chart, off with `nochart`
+/// * `pin` - Pin the latest summary, on with `pin`
+///
+#[derive(Clone, Debug, PartialEq)]
+pub struct ChannelArgs {
+    pub target: String,
+    pub time: String,
+    pub timezone: Option<String>,
+    pub fiat: Fiat,
+    pub symbols: Vec<String>,
+    pub chart: bool,
+    pub pin: bool,
+}
+
+/// Arguments of /addchannel, `None` if the channel, time or coins are missing
+pub fn parse_channel_args(args: &str) -> Option<ChannelArgs> {
+    let mut words = args
+        .split(|c: char| c.is_whitespace() || c == ',')
+        .filter(|word| !word.is_empty());
+    let target = words.next()?;
+    let valid_target = target
+        .strip_prefix('@')
+        .map(|username| !username.is_empty())
+        .unwrap_or_else(|| target.parse::<i64>().is_ok());
+    if !valid_target {
+        return None;
+    }
+    let (hour, minute) = parse_time(words.next()?)?;
+
+    let mut parsed = ChannelArgs {
+        target: target.to_string(),
+        time: format!("{:02}:{:02}", hour, minute),
+        timezone: None,
+        fiat: Fiat::default(),
+        symbols: vec![],
+        chart: true,
+        pin: false,
+    };
+    for word in words {
+        if word.eq_ignore_ascii_case("pin") {
+            parsed.pin = true;
+        } else if word.eq_ignore_ascii_case("nochart") {
+            parsed.chart = false;
+        } else if let Some(fiat) = Fiat::from_code(word) {
+            parsed.fiat = fiat;
+        } else if let Ok(tz) = word.parse::<Tz>() {
+            parsed.timezone = Some(tz.name().to_string());
+        } else if word.chars().all(|c| c.is_ascii_alphanumeric()) {
+            let symbol = word.to_uppercase();
+            if !parsed.symbols.contains(&symbol) {
+                parsed.symbols.push(symbol);
+            }
+        } else {
+            return None;
+        }
+    }
+    (!parsed.symbols.is_empty()).then_some(parsed)
+}
+
+/// Coins with the biggest rise and fall over 24 hours
+///
+/// # Returns
+///
+/// * `(Vec<&Quote>, Vec<&Quote>)` - Up to `count` risen coins, biggest first,
+///   and up to `count` fallen ones, biggest first
+pub fn top_movers(quotes: &[Quote], count: usize) -> (Vec<&Quote>, Vec<&Quote>) {
+    let mut changed: Vec<(&Quote, f64)> = quotes
+        .iter()
+        .filter_map(|quote| Some((quote, quote.change_24h?)))
+        .collect();
+    changed.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap_or(std::cmp::Ordering::Equal));
+
+    let gainers = changed
+        .iter()
+        .filter(|(_, change)| *change > 0.0)
+        .take(count)
+        .map(|(quote, _)| *quote)
+        .collect();
+    let losers = changed
+        .iter()
+        .rev()
+        .filter(|(_, change)| *change < 0.0)
+        .take(count)
+        .map(|(quote, _)| *quote)
+        .collect();
+    (gainers, losers)
+}
+
+/// Text of a channel summary: the /priceall table and the top movers
+pub fn channel_text(quotes: &[Quote], fiat: Fiat, lang: Lang) -> String {
+    let header = lang.tr_with("channel-header", &[("fiat", fiat.code().into())]);
+    let mut text = prices_table(quotes, &header, lang);
+
+    let movers = |quotes: Vec<&Quote>| {
+        quotes
+            .iter()
+            .map(|quote| {
+                let change = numbers::percent_change(quote.change_24h.unwrap_or_default(), lang);
+                format!("{} {}", quote.symbol, change)
+            })
+            .collect::<Vec<String>>()
+            .join(", ")
+    };
+    let (gainers, losers) = top_movers(quotes, TOP_MOVERS);
+    let mut lines: Vec<String> = vec![];
+    if !gainers.is_empty() {
+        lines.push(lang.tr_with("channel-gainers", &[("movers", movers(gainers).into())]));
+    }
+    if !losers.is_empty() {
+        lines.push(lang.tr_with("channel-losers", &[("movers", movers(losers).into())]));
+    }
+    if !lines.is_empty() {
+        text += "\n\n";
+        text += &lines.join("\n");
+    }
+    text
+}
+
+/// Publish a channel summary
+///
+/// Posts the chart of the first coin, if on, and the summary. With `pin` the
+/// summary replaces the previously pinned one.
+///
+/// # Arguments
+///
+/// * `bot` - Bot
+/// * `post` - Schedule to publish
+/// * `channels` - Schedule storage, for the pinned message
+/// * `config` - Bot configuration
+///
+pub async fn publish(
+    bot: &Bot,
+    post: &ChannelPost,
+    channels: &Arc<dyn ChannelRepository>,
+    config: &Config,
+) -> StorageResult<()> {
+    let chat_id = ChatId(post.chat_id);
+    let lang = post.language;
+    let quotes = fetch_quotes(&post.symbols, &HashMap::new(), post.fiat, config)
+        .await
+        .map_err(|err| err.to_string())?;
+    if quotes.is_empty() {
+        return Err(format!("No prices for {}", post.symbols.join(",")).into());
+    }
+
+    if let (true, Some(symbol)) = (post.chart, post.symbols.first()) {
+        let chart = render_chart(symbol, ChartPeriod::Day, ChartTheme::Light, lang, config)
+            .await
+            .map_err(|err| err.to_string());
+        match chart {
+            Ok(filename) => {
+                let sent = bot.send_photo(chat_id, InputFile::file(&filename)).await;
+                std::fs::remove_file(&filename)?;
+                sent?;
+            }
+            // The summary goes out without a chart
+            Err(err) => warn!("Error drawing the chart of {}: {}", symbol, err),
+        }
+    }
+
+    let sent = bot
+        .send_message(chat_id, channel_text(&quotes, post.fiat, lang))
+        .parse_mode(ParseMode::Html)
+        .disable_web_page_preview(true)
+        .await?;
+
+    if post.pin {
+        if let Some(last) = post.last_message_id {
+            // The previous post may be unpinned or deleted already
+            if let Err(err) = bot
+                .unpin_chat_message(chat_id)
+                .message_id(MessageId(last))
+                .await
+            {
+                warn!("Error unpinning post {} of {}: {}", last, post.chat_id, err);
+            }
+        }
+        bot.pin_chat_message(chat_id, sent.id)
+            .disable_notification(true)
+            .await?;
+        channels.set_last_message(&post.id, sent.id.0).await?;
+    }
+    Ok(())
+}
+
+/// /addchannel admin command handler
+///
+/// The bot has to be an administrator of the channel.
+///
+/// # Arguments
+///
+/// * `bot` - Bot
+/// * `me` - The bot user, for the admin check
+/// * `args` - See `ChannelArgs`
+/// * `lang` - Language of the reply and of the posts
+/// * `channels` - Schedule storage
+/// * `config` - Bot configuration, for the coin limit
+///
+/// # Returns
+///
+/// * `String` - The new schedule or why it was not added
+pub async fn add_channel_command(
+    bot: &Bot,
+    me: &Me,
+    args: &str,
+    lang: Lang,
+    channels: &Arc<dyn ChannelRepository>,
+    config: &Config,
+) -> String {
+    let args = match parse_channel_args(args) {
+        Some(args) => args,
+        None => return lang.tr("channel-usage"),
+    };
+    if args.symbols.len() > config.max_watchlist_size {
+        return lang.tr_with(
+            "channel-too-many",
+            &[("max", config.max_watchlist_size.into())],
+        );
+    }
+
+    let recipient = match args.target.parse::<i64>() {
+        Ok(id) => Recipient::Id(ChatId(id)),
+        Err(_) => Recipient::ChannelUsername(args.target.clone()),
+    };
+    let chat = match bot.get_chat(recipient).await {
+        Ok(chat) if chat.is_channel() => chat,
+        Ok(_) => {
+            return lang.tr_with("channel-not-channel", &[("channel", args.target.into())]);
+        }
+        Err(err) => {
+            warn!("Error getting channel {}: {}", args.target, err);
+            return lang.tr_with("channel-not-found", &[("channel", args.target.into())]);
+        }
+    };
+    if !is_chat_admin(bot, chat.id, me.id).await {
+        return lang.tr_with("channel-not-admin", &[("channel", args.target.into())]);
+    }
+
+    let title = chat.title().unwrap_or(&args.target).to_string();
+    let mut post = ChannelPost::new(chat.id.0, title, args.symbols, args.time, lang);
+    post.timezone = args.timezone;
+    post.fiat = args.fiat;
+    post.chart = args.chart;
+    post.pin = args.pin;
+    match channels.add_channel_post(&post).await {
+        Ok(()) => lang.tr_with(
+            "channel-added",
+            &[
+                ("title", post.title.into()),
+                ("time", post.time.into()),
+                ("id", post.id.into()),
+            ],
+        ),
+        Err(err) => html::escape(&err.to_string()),
+    }
+}
+
+/// /channels admin command handler, lists the schedules with their ids
+pub async fn channels_command(lang: Lang, channels: &Arc<dyn ChannelRepository>) -> String {
+    let posts = match channels.get_channel_posts().await {
+        Ok(posts) => posts,
+        Err(err) => return html::escape(&err.to_string()),
+    };
+    if posts.is_empty() {
+        return lang.tr("channels-empty");
+    }
+
+    let mut text = lang.tr("channels-header");
+    for post in posts {
+        let mut flags = vec![post.fiat.code().to_string()];
+        if post.chart {
+            flags.push(lang.tr("channel-flag-chart"));
+        }
+        if post.pin {
+            flags.push(lang.tr("channel-flag-pin"));
+        }
+        let time = match &post.timezone {
+            Some(timezone) => format!("{} {}", post.time, timezone),
+            None => post.time.clone(),
+        };
+        text += "\n";
+        text += &lang.tr_with(
+            "channels-item",
+            &[
+                ("id", post.id.into()),
+                ("title", post.title.into()),
+                ("time", time.into()),
+                ("symbols", post.symbols.join(" ").into()),
+                ("flags", flags.join(", ").into()),
+            ],
+        );
+    }
+    text
+}
+
+/// /removechannel admin command handler
+pub async fn remove_channel_command(
+    id: &str,
+    lang: Lang,
+    channels: &Arc<dyn ChannelRepository>,
+) -> String {
+    let id = id.trim();
+    if id.is_empty() {
+        return lang.tr("removechannel-usage");
+    }
+    match channels.delete_channel_post(id).await {
+        Ok(true) => lang.tr("channel-removed"),
+        Ok(false) => lang.tr_with("channel-unknown", &[("id", id.into())]),
+        Err(err) => html::escape(&err.to_string()),
+    }
+}
+
+/// /postchannel admin command handler, publishes a schedule right away
+pub async fn post_channel_command(
+    bot: &Bot,
+    id: &str,
+    lang: Lang,
+    channels: &Arc<dyn ChannelRepository>,
+    config: &Config,
+) -> String {
+    let id = id.trim();
+    let post = match channels.get_channel_posts().await {
+        Ok(posts) => posts.into_iter().find(|post| post.id == id),
+        Err(err) => return html::escape(&err.to_string()),
+    };
+    let post = match post {
+        Some(post) => post,
+        None => return lang.tr_with("channel-unknown", &[("id", id.into())]),
+    };
+    match publish(bot, &post, channels, config).await {
+        Ok(()) => lang.tr("channel-posted"),
+        Err(err) => html::escape(&err.to_string()),
+    }
+}
+
+/// Returns true if the schedule was not published since its latest post time
+///
+/// A minute missed by a slow loop or a restart is caught up once, a schedule
+/// added after the post time waits for the next day.
+pub fn post_due(post: &ChannelPost, now: DateTime<Utc>) -> bool {
+    let since = post.last_posted_at.unwrap_or(post.created_at);
+    last_post_time(post, now).is_some_and(|due| since.timestamp_millis() < due.timestamp_millis())
+}
+
+/// Latest post time of the schedule up to `now`
+///
+/// The time is local to the schedule's time zone, or the server's without
+/// one. A time skipped by a DST change has no post that day.
+fn last_post_time(post: &ChannelPost, now: DateTime<Utc>) -> Option<DateTime<Utc>> {
+    let at = parse_time(&post.time)?;
+    match post.tz() {
+        Some(tz) => last_local_time(now, &tz, at),
+        None => last_local_time(now, &Local, at),
+    }
+}
+
+fn last_local_time<T: TimeZone>(
+    now: DateTime<Utc>,
+    tz: &T,
+    (hour, minute): (u32, u32),
+) -> Option<DateTime<Utc>> {
+    let today = now.with_timezone(tz).date_naive();
+    (0..=2).find_map(|days| {
+        let local = (today - Days::new(days)).and_hms_opt(hour, minute, 0)?;
+        let due = tz
+            .from_local_datetime(&local)
+            .earliest()?
+            .with_timezone(&Utc);
+        (due <= now).then_some(due)
+    })
+}
+
+/// Channel post job, run by the supervisor
+///
+/// Wakes at the start of every minute and publishes the schedules that are
+/// due. The post time is stored before publishing, so a failed post is not
+/// retried every minute and a restart never posts twice.
+pub async fn channel_job(
+    bot: Bot,
+    channels: Arc<dyn ChannelRepository>,
+    config: Arc<Config>,
+) -> JobResult {
+    loop {
+        time::sleep(until_next_minute(Utc::now())).await;
+        let now = Utc::now();
+        for post in channels.get_channel_posts().await? {
+            if !post_due(&post, now) {
+                continue;
+            }
+            let posted_at = bson::DateTime::from_millis(now.timestamp_millis());
+            if let Err(err) = channels.set_last_posted(&post.id, posted_at).await {
+                warn!("Error storing channel post {}: {}", post.id, err);
+                continue;
+            }
+            match publish(&bot, &post, &channels, &config).await {
+                Ok(()) => info!("Channel post {} published", post.id),
+                Err(err) => warn!("Error publishing channel post {}: {}", post.id, err),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::memory::MemoryStorage;
+    use crate::storage::Storage;
+
+    fn quote(symbol: &str, change_24h: Option<f64>) -> Quote {
+        Quote {
+            symbol: symbol.to_string(),
+            price: 1.0,
+            change_24h,
+            slug: None,
+        }
+    }
+
+    #[test]
+    fn test_parse_channel_args() {
+        assert_eq!(
+            parse_channel_args("@market 9:00 btc, eth Europe/Kiev eur pin nochart btc"),
+            Some(ChannelArgs {
+                target: "@market".to_string(),
+                time: "09:00".to_string(),
+                timezone: Some("Europe/Kiev".to_string()),
+                fiat: Fiat::Eur,
+                symbols: vec!["BTC".to_string(), "ETH".to_string()],
+                chart: false,
+                pin: true,
+            })
+        );
+        let args = parse_channel_args("-1001234567890 18:30 sol").unwrap();
+        assert_eq!(args.target, "-1001234567890");
+        assert!(args.chart && !args.pin);
+
+        assert_eq!(parse_channel_args(""), None);
+        assert_eq!(parse_channel_args("market 09:00 btc"), None);
+        assert_eq!(parse_channel_args("@market 25:00 btc"), None);
+        assert_eq!(parse_channel_args("@market 09:00"), None);
+        assert_eq!(parse_channel_args("@market 09:00 btc Mars/Olympus"), None);
+    }
+
+    #[test]
+    fn test_top_movers() {
+        let quotes = vec![
+            quote("BTC", Some(2.5)),
+            quote("ETH", Some(-4.0)),
+            quote("SOL", Some(8.1)),
+            quote("DOGE", None),
+            quote("XRP", Some(-0.5)),
+            quote("ADA", Some(0.0)),
+        ];
+        let (gainers, losers) = top_movers(&quotes, 1);
+        assert_eq!(gainers, vec![&quotes[2]]);
+        assert_eq!(losers, vec![&quotes[1]]);
+
+        let (gainers, losers) = top_movers(&quotes, 3);
+        let symbols = |quotes: Vec<&Quote>| -> Vec<String> {
+            quotes.iter().map(|quote| quote.symbol.clone()).collect()
+        };
+        assert_eq!(symbols(gainers), vec!["SOL", "BTC"]);
+        assert_eq!(symbols(losers), vec!["ETH", "XRP"]);
+
+        let text = channel_text(&quotes[..1], Fiat::Usd, Lang::En);
+        assert!(text.contains("Top gainers: BTC 🟢 +2.50%"));
+        assert!(!text.contains("losers"));
+
+        // Markers stay out of the table, its columns line up by characters
+        let text = channel_text(&quotes[..2], Fiat::Usd, Lang::En);
+        let table = &text[text.find("<pre>").unwrap() + 5..text.find("</pre>").unwrap()];
+        let widths: Vec<usize> = table.lines().map(|line| line.chars().count()).collect();
+        assert_eq!(widths[0], widths[1]);
+        assert!(table.contains("+2.50%") && !table.contains('🟢'));
+    }
+
+    #[tokio::test]
+    async fn test_channels_commands() {
+        use chrono::TimeZone;
+
+        let storage = Storage::new(MemoryStorage::new());
+        assert_eq!(
+            channels_command(Lang::En, &storage.channels).await,
+            Lang::En.tr("channels-empty")
+        );
+
+        let mut post = ChannelPost::new(
+            -1001,
+            "Market".to_string(),
+            vec!["BTC".to_string()],
+            "09:00".to_string(),
+            Lang::En,
+        );
+        post.timezone = Some("UTC".to_string());
+        storage.channels.add_channel_post(&post).await.unwrap();
+        let text = channels_command(Lang::En, &storage.channels).await;
+        assert!(text.contains(&post.id));
+        assert!(text.contains("09:00 UTC"));
+
+        // Added at 10:00, the first post is the next morning
+        post.created_at = bson::DateTime::from_millis(
+            Utc.with_ymd_and_hms(2024, 5, 6, 10, 0, 0)
+                .unwrap()
+                .timestamp_millis(),
+        );
+        let now = Utc.with_ymd_and_hms(2024, 5, 7, 9, 0, 20).unwrap();
+        assert!(!post_due(&post, now - chrono::Duration::minutes(1)));
+        assert!(post_due(&post, now));
+        // A missed minute is caught up
+        assert!(post_due(&post, now + chrono::Duration::minutes(7)));
+
+        storage
+            .channels
+            .set_last_posted(
+                &post.id,
+                bson::DateTime::from_millis(now.timestamp_millis()),
+            )
+            .await
+            .unwrap();
+        let post = storage.channels.get_channel_posts().await.unwrap()[0].clone();
+        assert!(!post_due(&post, now));
+        assert!(!post_due(&post, now + chrono::Duration::hours(12)));
+        assert!(post_due(&post, now + chrono::Duration::days(1)));
+
+        assert_eq!(
+            remove_channel_command(&post.id, Lang::En, &storage.channels).await,
+            Lang::En.tr("channel-removed")
+        );
+        assert!(
+            remove_channel_command(&post.id, Lang::En, &storage.channels)
+                .await
+                .contains("Unknown")
+        );
+    }
+}
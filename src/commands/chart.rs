@@ -1,38 +1,172 @@
+use crate::commands::keyboards::chart_keyboard;
+use crate::config::Config;
+use crate::i18n::Lang;
+use crate::models::preferences::ChartTheme;
+use crate::tools::metrics::Upstream;
+use crate::tools::numbers;
+use crate::tools::shutdown::Shutdown;
 use chrono::{DateTime, TimeZone};
 use chrono_tz::Tz;
 use plotters::prelude::*;
-use reqwest::header;
 use serde_json::Value;
+use std::sync::Arc;
 use teloxide::prelude::*;
-use teloxide::types::InputFile;
-use tokio::spawn;
+use teloxide::types::{InputFile, InputMedia, InputMediaPhoto};
+
+/// Time span of a chart
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ChartPeriod {
+    Day,
+    Week,
+    Month,
+}
+
+impl ChartPeriod {
+    /// Every period, in the order of the chart buttons
+    pub const ALL: [ChartPeriod; 3] = [ChartPeriod::Day, ChartPeriod::Week, ChartPeriod::Month];
+
+    /// Code used in callback data
+    pub fn code(self) -> &'static str {
+        match self {
+            ChartPeriod::Day => "24h",
+            ChartPeriod::Week => "7d",
+            ChartPeriod::Month => "30d",
+        }
+    }
+
+    /// Period of a callback data code
+    pub fn from_code(code: &str) -> Option<ChartPeriod> {
+        ChartPeriod::ALL
+            .into_iter()
+            .find(|period| period.code() == code)
+    }
+
+    /// Binance kline interval and the number of klines covering the period
+    fn klines(self) -> (&'static str, usize) {
+        match self {
+            ChartPeriod::Day => ("1h", 24),
+            ChartPeriod::Week => ("4h", 42),
+            ChartPeriod::Month => ("1d", 30),
+        }
+    }
+
+    /// Every how many klines an x label is drawn, and its format
+    fn labels(self) -> (usize, &'static str) {
+        match self {
+            ChartPeriod::Day => (2, "%H:00"),
+            ChartPeriod::Week => (6, "%d.%m"),
+            ChartPeriod::Month => (3, "%d.%m"),
+        }
+    }
+
+    /// Catalog key of the chart caption
+    fn title_key(self) -> &'static str {
+        match self {
+            ChartPeriod::Day => "chart-title",
+            ChartPeriod::Week => "chart-title-week",
+            ChartPeriod::Month => "chart-title-month",
+        }
+    }
+}
 
 /// /chart command handler
-/// Sends a chart of the specified currency
-pub async fn chart_command(bot: Bot, msg: Message, currency: String) {
-    spawn(async move {
+/// Sends a chart of the specified currency, as a tracked task so shutdown waits for it
+pub async fn chart_command(
+    bot: Bot,
+    msg: Message,
+    currency: String,
+    lang: Lang,
+    theme: ChartTheme,
+    config: Arc<Config>,
+    shutdown: Shutdown,
+) {
+    shutdown.spawn(async move {
         // Дожидаемся завершения отправки фото
-        if let Err(err) = send_chart(bot.clone(), msg.clone(), currency.clone()).await {
+        if let Err(err) = send_chart(
+            &bot,
+            msg.chat.id,
+            &currency,
+            ChartPeriod::Day,
+            theme,
+            lang,
+            &config,
+        )
+        .await
+        {
+            log::error!("Error sending photo: {}", err);
+        }
+    });
+}
+
+/// Chart asked for with a button
+///
+/// # Fields
+///
+/// * `currency` - The currency to draw
+/// * `period` - Time span of the chart
+/// * `theme` - Colours of the chart
+#[derive(Clone, Debug)]
+pub struct ChartRequest {
+    pub currency: String,
+    pub period: ChartPeriod,
+    pub theme: ChartTheme,
+}
+
+/// Chart button handler
+///
+/// Under a chart the photo is redrawn in place. A text message can't become a
+/// photo, so under a price card the chart is sent as a new message.
+pub async fn chart_button_command(
+    bot: Bot,
+    message: Message,
+    request: ChartRequest,
+    lang: Lang,
+    config: Arc<Config>,
+    shutdown: Shutdown,
+) {
+    shutdown.spawn(async move {
+        let ChartRequest {
+            currency,
+            period,
+            theme,
+        } = request;
+        let result = if message.photo().is_some() {
+            edit_chart(&bot, &message, &currency, period, theme, lang, &config).await
+        } else {
+            send_chart(
+                &bot,
+                message.chat.id,
+                &currency,
+                period,
+                theme,
+                lang,
+                &config,
+            )
+            .await
+        };
+        if let Err(err) = result {
             log::error!("Error sending photo: {}", err);
         }
     });
 }
 
 /// get info about the specified currency from binance api
-async fn get_chart(currency: &String) -> Result<Vec<f64>, Box<dyn std::error::Error>> {
+///
+/// Returns the open time in milliseconds and the close price of each kline
+async fn get_chart(
+    currency: &str,
+    period: ChartPeriod,
+    config: &Config,
+) -> Result<Vec<(i64, f64)>, Box<dyn std::error::Error>> {
     let symbol = currency.to_uppercase() + "USDT";
-    let interval = "1h";
+    let (interval, limit) = period.klines();
     let url = format!(
-        "https://api.binance.com/api/v3/klines?symbol={}&interval={}",
-        symbol, interval
+        "https://api.binance.com/api/v3/klines?symbol={}&interval={}&limit={}",
+        symbol, interval, limit
     );
 
-    let client = reqwest::Client::new();
-    let response = client
-        .get(&url)
-        .header(header::USER_AGENT, "rust")
-        .send()
-        .await?;
+    let http = config.http();
+    let response = http.send(Upstream::Binance, http.get(&url)).await?;
 
     if !response.status().is_success() {
         return Err(format!(
@@ -45,84 +179,150 @@ async fn get_chart(currency: &String) -> Result<Vec<f64>, Box<dyn std::error::Er
 
     let response_json = response.json::<Vec<Vec<Value>>>().await?;
 
-    let mut data = Vec::with_capacity(200000);
+    let mut data = Vec::with_capacity(limit);
     for kline in response_json {
+        let open_time = kline[0].as_i64().ok_or("Failed to convert to i64")?;
         let close_value = kline[4].as_str().ok_or("Failed to convert to str")?;
         let close = close_value.parse::<f64>()?;
-        data.push(close);
+        data.push((open_time, close));
     }
 
-    //Ok(data.iter().map(|&d| d as u32).collect())
     Ok(data)
 }
 
 // test don't work on github actions
 // #[tokio::test]
 // async fn test_get_chart() {
-//     let data = get_chart(&"btc".to_string()).await.unwrap();
+//     let data = get_chart("btc", ChartPeriod::Day, &config).await.unwrap();
 //     assert!(data.len() > 1);
 // }
 
-/// Send a chart of the specified currency
+/// Send a chart of the specified currency, with the period buttons
 async fn send_chart(
-    bot: Bot,
-    msg: Message,
-    currency: String,
+    bot: &Bot,
+    chat_id: ChatId,
+    currency: &str,
+    period: ChartPeriod,
+    theme: ChartTheme,
+    lang: Lang,
+    config: &Config,
 ) -> Result<(), Box<dyn std::error::Error>> {
-    let data_all = get_chart(&currency).await?;
-    let data: Vec<f64> = data_all.iter().rev().take(24).copied().rev().collect(); // get last 30 days
-    let filename = format!("chart_{}.png", chrono::Utc::now().timestamp());
+    let filename = render_chart(currency, period, theme, lang, config).await?;
 
-    let timezone: Tz = "Europe/Kiev".parse()?;
+    // Дожидаемся завершения отправки фото
+    let sent = bot
+        .send_photo(chat_id, InputFile::file(&filename))
+        .reply_markup(chart_keyboard(currency, period, lang))
+        .await;
+    std::fs::remove_file(&filename)?;
+    sent?;
+
+    Ok(())
+}
+
+/// Redraw a chart message for another period
+async fn edit_chart(
+    bot: &Bot,
+    message: &Message,
+    currency: &str,
+    period: ChartPeriod,
+    theme: ChartTheme,
+    lang: Lang,
+    config: &Config,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let filename = render_chart(currency, period, theme, lang, config).await?;
+
+    let media = InputMedia::Photo(InputMediaPhoto::new(InputFile::file(&filename)));
+    let edited = bot
+        .edit_message_media(message.chat.id, message.id, media)
+        .reply_markup(chart_keyboard(currency, period, lang))
+        .await;
+    std::fs::remove_file(&filename)?;
+    edited?;
 
-    let last_day = chrono::Utc::now() - chrono::Duration::days(1);
+    Ok(())
+}
+
+/// Render the chart of a currency
+///
+/// # Arguments
+///
+/// * `currency` - The currency to draw
+/// * `period` - Time span of the chart
+/// * `theme` - Colours of the chart
+/// * `lang` - Language of the caption and the price labels
+/// * `config` - Bot configuration
+///
+/// # Returns
+///
+/// * `Result<String, Box<dyn std::error::Error>>` - Name of the PNG file, the
+///   caller removes it once it is sent
+pub async fn render_chart(
+    currency: &str,
+    period: ChartPeriod,
+    theme: ChartTheme,
+    lang: Lang,
+    config: &Config,
+) -> Result<String, Box<dyn std::error::Error>> {
+    let klines = get_chart(currency, period, config).await?;
+    let data: Vec<f64> = klines.iter().map(|(_, close)| *close).collect();
+    // Inline queries render several charts at once
+    let filename = format!(
+        "chart_{}_{}.png",
+        currency.to_lowercase(),
+        chrono::Utc::now().timestamp_millis()
+    );
+
+    let timezone: Tz = "Europe/Kiev".parse()?;
+    let (label_every, label_format) = period.labels();
 
     let mut x_labels: Vec<(u32, String)> = vec![];
 
-    for (i, _) in data.iter().enumerate() {
-        if i % 2 == 0 {
-            let timestamp = last_day.timestamp() as u32 + i as u32 * 60 * 60;
-            let naive_dt = chrono::NaiveDateTime::from_timestamp_opt(timestamp as i64, 0)
+    for (i, (open_time, _)) in klines.iter().enumerate() {
+        if i % label_every == 0 {
+            let naive_dt = chrono::NaiveDateTime::from_timestamp_millis(*open_time)
                 .ok_or("Failed to convert timestamp")?;
             let localized_dt: DateTime<Tz> = timezone.from_utc_datetime(&naive_dt);
-            let hour = localized_dt.format("%H").to_string();
-            x_labels.push((i as u32, hour + ":00"));
+            x_labels.push((i as u32, localized_dt.format(label_format).to_string()));
         } else {
             x_labels.push((i as u32, "".to_string()));
         }
     }
 
-    build_chart(x_labels, currency, data, filename.clone()).await?;
-
-    let filename_clone = filename.clone();
+    let caption = lang.tr_with(
+        period.title_key(),
+        &[("symbol", currency.to_string().into())],
+    );
+    build_chart(x_labels, caption, data, filename.clone(), theme, lang).await?;
 
-    spawn(async move {
-        // Дожидаемся завершения отправки фото
-        if let Err(err) = send_chart_file(&bot, &msg, &filename_clone).await {
-            println!("Error sending photo: {}", err);
-        }
-    });
+    Ok(filename)
+}
 
-    Ok(())
+/// Background, text and line colours of a theme
+fn palette(theme: ChartTheme) -> (RGBColor, RGBColor, RGBColor) {
+    match theme {
+        ChartTheme::Light => (WHITE, BLACK, BLUE),
+        ChartTheme::Dark => (RGBColor(24, 26, 32), WHITE, RGBColor(240, 185, 11)),
+    }
 }
 
 /// Builds a chart and saves it to a file
 async fn build_chart(
     x_labels: Vec<(u32, String)>,
-    currency: String,
+    caption: String,
     data: Vec<f64>,
     filename: String,
+    theme: ChartTheme,
+    lang: Lang,
 ) -> Result<(), Box<dyn std::error::Error>> {
+    let (background, text, line) = palette(theme);
     let root = BitMapBackend::new(&filename, (640, 480)).into_drawing_area();
-    root.fill(&WHITE)?;
+    root.fill(&background)?;
     let mut chart = ChartBuilder::on(&root)
-        .caption(
-            format!("Price Chart for {} in 24 hours", currency),
-            ("sans-serif", 30).into_font(),
-        )
+        .caption(caption, ("sans-serif", 30).into_font().color(&text))
         .margin(10)
         .set_label_area_size(LabelAreaPosition::Bottom, 40.0)
-        .set_label_area_size(LabelAreaPosition::Right, 40.0)
+        .set_label_area_size(LabelAreaPosition::Right, 80.0)
         .build_cartesian_2d(
             0..data.len() as u32,
             (*data
@@ -135,38 +335,29 @@ async fn build_chart(
                     .ok_or("Failed to find the maximum value")?),
         )?;
 
-    chart
-        .configure_mesh()
-        .x_labels(30)
-        .x_label_formatter(&|x| {
-            x_labels
-                .get(*x as usize)
-                .map(|(_, label)| label.clone())
-                .unwrap_or_default()
-        })
-        .draw()?;
+    let x_label_formatter = |x: &u32| {
+        x_labels
+            .get(*x as usize)
+            .map(|(_, label)| label.clone())
+            .unwrap_or_default()
+    };
+    let y_label_formatter = |y: &f64| numbers::price(*y, lang);
+    let mut mesh = chart.configure_mesh();
+    mesh.x_labels(30)
+        .x_label_formatter(&x_label_formatter)
+        .y_label_formatter(&y_label_formatter)
+        .axis_style(text)
+        .bold_line_style(text.mix(0.2))
+        .light_line_style(text.mix(0.1));
+    // The default labels are black
+    if theme == ChartTheme::Dark {
+        mesh.label_style(("sans-serif", 15).into_font().color(&text));
+    }
+    mesh.draw()?;
 
     chart.draw_series(LineSeries::new(
         (0..).zip(data.iter()).map(|(x, y)| (x, *y)),
-        &BLUE,
+        &line,
     ))?;
     Ok(())
 }
-
-/// Sends a chart file to the user
-async fn send_chart_file(
-    bot: &Bot,
-    msg: &Message,
-    filename: &String,
-) -> Result<(), Box<dyn std::error::Error>> {
-    let chart_file = InputFile::file(filename);
-
-    let send_photo_future = bot.send_photo(msg.chat.id, chart_file);
-
-    // let filename_clone = filename.clone();
-
-    send_photo_future.await?;
-    std::fs::remove_file(filename)?;
-
-    Ok(())
-}
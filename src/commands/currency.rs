@@ -1,21 +1,252 @@
-use crate::db::DatabaseManager;
+use crate::config::Config;
+use crate::i18n::Lang;
+use crate::storage::watchlist::{UserWatchlist, Watchlist};
+use crate::storage::UserRepository;
+use crate::tools::asset_registry::AssetRegistry;
+use crate::tools::html;
+use std::sync::Arc;
+use teloxide::types::{InlineKeyboardButton, InlineKeyboardMarkup};
+
+/// Callback data prefix of the asset disambiguation buttons
+pub const ASSET_CALLBACK_PREFIX: &str = "asset:";
+
+/// Reply of the /addcurrency command
+pub enum AddCurrencyReply {
+    /// Plain text response
+    Text(String),
+    /// Several assets share the symbol, the user has to pick one
+    Choose(String, InlineKeyboardMarkup),
+}
+
+/// Split command arguments into unique upper case symbols
+///
+/// `/addcurrency btc, eth sol` gives `["BTC", "ETH", "SOL"]`
+pub fn parse_symbols(text: &str) -> Vec<String> {
+    let mut symbols: Vec<String> = Vec::new();
+    for symbol in text
+        .split(|c: char| c.is_whitespace() || c == ',')
+        .filter(|symbol| !symbol.is_empty())
+        .map(|symbol| symbol.to_uppercase())
+    {
+        if !symbols.contains(&symbol) {
+            symbols.push(symbol);
+        }
+    }
+    symbols
+}
 
 /// Add currency command to user currency list in db
 ///
 /// # Arguments
 ///
-/// * `msg` - Message
-/// * `currency` - Currency
-/// * `db` - DatabaseManager
+/// * `user_id` - User id
+/// * `currencies` - One or more currencies separated by spaces or commas
+/// * `lang` - Reply language
+/// * `db` - User storage
+/// * `registry` - AssetRegistry
+/// * `config` - Bot configuration
+///
+/// # Returns
+///
+/// * `AddCurrencyReply` - Response message, with a keyboard if a symbol is ambiguous
+pub async fn add_currency_command(
+    user_id: i64,
+    currencies: String,
+    lang: Lang,
+    db: Arc<dyn UserRepository>,
+    registry: AssetRegistry,
+    config: &Config,
+) -> AddCurrencyReply {
+    let watchlist = UserWatchlist { users: db, user_id };
+    add_currency(&watchlist, currencies, lang, &registry, config).await
+}
+
+/// Add currencies to a user or group watchlist
+///
+/// Every symbol is validated against the asset registry. Unknown symbols are
+/// rejected with suggestions, ambiguous ones are offered as buttons and the
+/// watchlist never grows past `Config::max_watchlist_size`.
+///
+/// # Arguments
+///
+/// * `watchlist` - List to change
+/// * `currencies` - One or more currencies separated by spaces or commas
+/// * `lang` - Reply language
+/// * `registry` - AssetRegistry
+/// * `config` - Bot configuration
+///
+/// # Returns
+///
+/// * `AddCurrencyReply` - Response message, with a keyboard if a symbol is ambiguous
+pub async fn add_currency(
+    watchlist: &dyn Watchlist,
+    currencies: String,
+    lang: Lang,
+    registry: &AssetRegistry,
+    config: &Config,
+) -> AddCurrencyReply {
+    let current = match watchlist.symbols().await {
+        Some(current) => current,
+        None => return AddCurrencyReply::Text(lang.tr("user-error")),
+    };
+
+    let max_size = config.max_watchlist_size;
+    let mut lines: Vec<String> = Vec::new();
+    let mut added: Vec<String> = Vec::new();
+    let mut rejected_full: Vec<String> = Vec::new();
+    let mut buttons: Vec<Vec<InlineKeyboardButton>> = Vec::new();
+
+    let listed = |symbol: &str, added: &[String]| {
+        current
+            .iter()
+            .chain(added)
+            .any(|currency| currency.eq_ignore_ascii_case(symbol))
+    };
+
+    for symbol in parse_symbols(&currencies) {
+        if listed(&symbol, &added) {
+            lines.push(lang.tr_with(
+                "currency-already-added",
+                &[("symbol", symbol.clone().into())],
+            ));
+            continue;
+        }
+
+        // Without a loaded registry there is nothing to validate against
+        let assets = if registry.is_empty() {
+            vec![]
+        } else {
+            registry.resolve(&symbol)
+        };
+
+        let (symbol, cmc_id) = match assets.as_slice() {
+            [] if registry.is_empty() => (symbol, None),
+            [] => {
+                let suggestions: Vec<String> = registry
+                    .suggest(&symbol, 3)
+                    .into_iter()
+                    .map(|asset| asset.symbol)
+                    .collect();
+                if suggestions.is_empty() {
+                    lines.push(lang.tr_with("currency-unknown", &[("symbol", symbol.into())]));
+                } else {
+                    lines.push(lang.tr_with(
+                        "currency-unknown-suggest",
+                        &[
+                            ("symbol", symbol.into()),
+                            ("suggestions", suggestions.join(", ").into()),
+                        ],
+                    ));
+                }
+                continue;
+            }
+            [asset] => (asset.symbol.clone(), Some(asset.cmc_id)),
+            _ => {
+                lines
+                    .push(lang.tr_with("currency-ambiguous", &[("symbol", symbol.clone().into())]));
+                buttons.extend(assets.iter().take(8).map(|asset| {
+                    vec![InlineKeyboardButton::callback(
+                        asset.label(),
+                        format!("{}{}", ASSET_CALLBACK_PREFIX, asset.cmc_id),
+                    )]
+                }));
+                continue;
+            }
+        };
+
+        // A name or slug resolves to a symbol that may be listed already
+        if listed(&symbol, &added) {
+            lines.push(lang.tr_with(
+                "currency-already-added",
+                &[("symbol", symbol.clone().into())],
+            ));
+            continue;
+        }
+
+        match watchlist.add(symbol.clone(), cmc_id, max_size).await {
+            Ok(true) => added.push(symbol),
+            Ok(false) => rejected_full.push(symbol),
+            Err(err) => lines.push(html::escape(&err.to_string())),
+        }
+    }
+
+    if !added.is_empty() {
+        lines.insert(
+            0,
+            lang.tr_with("currency-added", &[("symbols", added.join(", ").into())]),
+        );
+    }
+    if !rejected_full.is_empty() {
+        lines.push(lang.tr_with(
+            "currency-list-full-rejected",
+            &[
+                ("max", max_size.into()),
+                ("symbols", rejected_full.join(", ").into()),
+            ],
+        ));
+    }
+    if lines.is_empty() {
+        lines.push(lang.tr("addcurrency-usage"));
+    }
+
+    let text = lines.join("\n");
+    if buttons.is_empty() {
+        AddCurrencyReply::Text(text)
+    } else {
+        AddCurrencyReply::Choose(text, InlineKeyboardMarkup::new(buttons))
+    }
+}
+
+/// Add the asset picked from the disambiguation keyboard
+///
+/// # Arguments
+///
+/// * `user_id` - User id
+/// * `cmc_id` - CoinMarketCap id of the picked asset
+/// * `lang` - Reply language
+/// * `db` - User storage
+/// * `registry` - AssetRegistry
+/// * `config` - Bot configuration
 ///
 /// # Returns
 ///
 /// * `String` - Response message
-pub async fn add_currency_command(user_id: i64, currency: String, db: DatabaseManager) -> String {
-    let result = db.change_user_currency(user_id, currency.clone()).await;
+pub async fn select_asset_command(
+    user_id: i64,
+    cmc_id: i64,
+    lang: Lang,
+    db: Arc<dyn UserRepository>,
+    registry: AssetRegistry,
+    config: &Config,
+) -> String {
+    let watchlist = UserWatchlist { users: db, user_id };
+    select_asset(&watchlist, cmc_id, lang, &registry, config).await
+}
+
+/// Add the asset picked from the disambiguation keyboard to a user or group watchlist
+pub async fn select_asset(
+    watchlist: &dyn Watchlist,
+    cmc_id: i64,
+    lang: Lang,
+    registry: &AssetRegistry,
+    config: &Config,
+) -> String {
+    let asset = match registry.get(cmc_id) {
+        Some(asset) => asset,
+        None => return lang.tr("asset-unknown"),
+    };
+
+    let max_size = config.max_watchlist_size;
+    let result = watchlist
+        .add(asset.symbol.clone(), Some(asset.cmc_id), max_size)
+        .await;
     match result {
-        Ok(()) => format!("Добавили валюту {:?}", currency),
-        Err(err) => err.to_string(),
+        Ok(false) => lang.tr_with("currency-list-full", &[("max", max_size.into())]),
+        Ok(true) => lang.tr_with(
+            "asset-added",
+            &[("symbol", asset.symbol.into()), ("name", asset.name.into())],
+        ),
+        Err(err) => html::escape(&err.to_string()),
     }
 }
 
@@ -23,22 +254,29 @@ pub async fn add_currency_command(user_id: i64, currency: String, db: DatabaseMa
 ///
 /// # Arguments
 ///
-/// * `msg` - Message
+/// * `user_id` - User id
 /// * `currency` - Currency
-/// * `db` - DatabaseManager
+/// * `lang` - Reply language
+/// * `db` - User storage
 ///
 /// # Returns
 ///
 /// * `String` - Response message
-
 pub async fn remove_currency_command(
     user_id: i64,
     currency: String,
-    db: DatabaseManager,
+    lang: Lang,
+    db: Arc<dyn UserRepository>,
 ) -> String {
-    let result = db.remove_user_currency(user_id, currency.clone()).await;
+    let watchlist = UserWatchlist { users: db, user_id };
+    remove_currency(&watchlist, currency, lang).await
+}
+
+/// Remove a currency from a user or group watchlist
+pub async fn remove_currency(watchlist: &dyn Watchlist, currency: String, lang: Lang) -> String {
+    let result = watchlist.remove(currency.clone()).await;
     match result {
-        Ok(()) => format!("Удалили валюту {:?}", currency),
-        Err(err) => err.to_string(),
+        Ok(()) => lang.tr_with("currency-removed", &[("symbol", currency.into())]),
+        Err(err) => html::escape(&err.to_string()),
     }
 }
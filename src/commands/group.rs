@@ -0,0 +1,238 @@
+use crate::i18n::Lang;
+use crate::models::chat::Chat;
+use crate::models::preferences::Preference;
+use crate::storage::{ChatRepository, StorageResult};
+use crate::tools::html;
+use log::warn;
+use mongodb::bson;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use teloxide::prelude::*;
+
+/// Quiet time of the groups after an answer to a plain message
+///
+/// Only the automatic answers are limited, commands are always answered.
+#[derive(Clone, Default)]
+pub struct Cooldowns {
+    answered: Arc<Mutex<HashMap<i64, Instant>>>,
+}
+
+impl Cooldowns {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Start the quiet time of a chat, `false` while the last one still runs
+    ///
+    /// # Arguments
+    ///
+    /// * `chat_id` - Group chat id
+    /// * `cooldown` - Quiet time of the chat, zero for none
+    ///
+    pub fn try_start(&self, chat_id: i64, cooldown: Duration) -> bool {
+        let now = Instant::now();
+        let mut answered = self.answered.lock().unwrap();
+        if let Some(last) = answered.get(&chat_id) {
+            if now.duration_since(*last) < cooldown {
+                return false;
+            }
+        }
+        answered.insert(chat_id, now);
+        true
+    }
+}
+
+/// Returns true for group and supergroup chats
+pub fn is_group(chat: &teloxide::types::Chat) -> bool {
+    chat.is_group() || chat.is_supergroup()
+}
+
+/// Returns true if a group message is meant for the bot
+///
+/// # Arguments
+///
+/// * `text` - Text of the message
+/// * `username` - Username of the bot, without `@`
+/// * `reply_to_bot` - Whether the message replies to the bot
+///
+pub fn addressed_to_bot(text: &str, username: &str, reply_to_bot: bool) -> bool {
+    let mention = format!("@{}", username.to_lowercase());
+    reply_to_bot
+        || text
+            .split_whitespace()
+            .any(|word| word.to_lowercase().starts_with(&mention))
+}
+
+/// Settings of a group, new ones get the language of the first sender
+///
+/// # Arguments
+///
+/// * `chats` - Chat storage
+/// * `chat` - Telegram chat of the update
+/// * `lang` - Language of the sender
+///
+/// # Returns
+///
+/// * `StorageResult<Chat>` - Stored chat with the current title, a new one not
+///   stored until changed. Errors are never replaced by a new chat, saving it
+///   would wipe the stored watchlist and settings.
+pub async fn group_chat(
+    chats: &Arc<dyn ChatRepository>,
+    chat: &teloxide::types::Chat,
+    lang: Lang,
+) -> StorageResult<Chat> {
+    let title = chat.title().unwrap_or_default().to_string();
+    match chats.get_chat(chat.id.0).await {
+        Ok(Some(stored)) => Ok(Chat { title, ..stored }),
+        Ok(None) => {
+            let mut new = Chat::new(chat.id.0, title);
+            new.preferences.language = Some(lang);
+            Ok(new)
+        }
+        Err(err) => {
+            warn!("Error getting chat {}: {}", chat.id, err);
+            Err(err)
+        }
+    }
+}
+
+/// Returns true if the user is an owner or administrator of the chat
+pub async fn is_chat_admin(bot: &Bot, chat_id: ChatId, user_id: UserId) -> bool {
+    match bot.get_chat_member(chat_id, user_id).await {
+        Ok(member) => member.is_privileged(),
+        Err(err) => {
+            warn!(
+                "Error getting member {} of chat {}: {}",
+                user_id, chat_id, err
+            );
+            false
+        }
+    }
+}
+
+/// /notify in a group, turns the group digest on or off
+///
+/// # Arguments
+///
+/// * `chat` - Settings of the group, changed in place
+/// * `state` - `on` or `off`, empty to show the current state
+/// * `lang` - Language of the reply
+/// * `chats` - Chat storage
+///
+/// # Returns
+///
+/// * `String` - Result of the change, the current state or the usage
+pub async fn group_notify_command(
+    chat: &mut Chat,
+    state: &str,
+    lang: Lang,
+    chats: &Arc<dyn ChatRepository>,
+) -> String {
+    let on = match state.trim().to_lowercase().as_str() {
+        "" if chat.preferences.digest => return lang.tr("notify-state-on"),
+        "" => return lang.tr("notify-state-off"),
+        "on" => true,
+        "off" => false,
+        _ => return lang.tr("notify-usage"),
+    };
+
+    chat.preferences.apply(&Preference::Digest(on));
+    chat.updated_at = bson::DateTime::now();
+    match chats.save_chat(chat).await {
+        Ok(_) if on => lang.tr("notify-on"),
+        Ok(_) => lang.tr("notify-off"),
+        Err(err) => html::escape(&err.to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::memory::MemoryStorage;
+    use crate::storage::Storage;
+    use async_trait::async_trait;
+
+    /// Chat storage that is down
+    struct Unavailable;
+
+    #[async_trait]
+    impl ChatRepository for Unavailable {
+        async fn get_chat(&self, _: i64) -> StorageResult<Option<Chat>> {
+            Err("storage down".into())
+        }
+
+        async fn save_chat(&self, _: &Chat) -> StorageResult<()> {
+            Err("storage down".into())
+        }
+
+        async fn add_chat_currency(
+            &self,
+            _: &Chat,
+            _: String,
+            _: Option<i64>,
+            _: usize,
+        ) -> StorageResult<bool> {
+            Err("storage down".into())
+        }
+
+        async fn remove_chat_currency(&self, _: i64, _: String) -> StorageResult<()> {
+            Err("storage down".into())
+        }
+
+        async fn get_digest_chats(&self) -> StorageResult<Vec<Chat>> {
+            Err("storage down".into())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_group_chat_storage_error() {
+        let chats: Arc<dyn ChatRepository> = Arc::new(Unavailable);
+        let chat: teloxide::types::Chat = serde_json::from_value(serde_json::json!({
+            "id": -100,
+            "type": "group",
+            "title": "Traders",
+        }))
+        .unwrap();
+        assert!(group_chat(&chats, &chat, Lang::En).await.is_err());
+    }
+
+    #[test]
+    fn test_cooldowns() {
+        let cooldowns = Cooldowns::new();
+        assert!(cooldowns.try_start(-100, Duration::from_secs(30)));
+        assert!(!cooldowns.try_start(-100, Duration::from_secs(30)));
+        // Other groups and zero cooldowns are not limited
+        assert!(cooldowns.try_start(-200, Duration::from_secs(30)));
+        assert!(cooldowns.try_start(-100, Duration::ZERO));
+    }
+
+    #[test]
+    fn test_addressed_to_bot() {
+        assert!(addressed_to_bot("@CoinBot btc", "coinbot", false));
+        assert!(addressed_to_bot(
+            "price of eth, @coinbot?",
+            "CoinBot",
+            false
+        ));
+        assert!(addressed_to_bot("btc", "coinbot", true));
+        assert!(!addressed_to_bot("btc eth", "coinbot", false));
+        assert!(!addressed_to_bot("coinbot btc", "coinbot", false));
+    }
+
+    #[tokio::test]
+    async fn test_group_notify() {
+        let storage = Storage::new(MemoryStorage::new());
+        let mut chat = Chat::new(-1001, "Traders".to_string());
+
+        let reply = group_notify_command(&mut chat, "on", Lang::En, &storage.chats).await;
+        assert_eq!(reply, Lang::En.tr("notify-on"));
+        let stored = storage.chats.get_chat(-1001).await.unwrap().unwrap();
+        assert!(stored.preferences.digest);
+
+        let reply = group_notify_command(&mut chat, "", Lang::En, &storage.chats).await;
+        assert_eq!(reply, Lang::En.tr("notify-state-on"));
+        let reply = group_notify_command(&mut chat, "maybe", Lang::En, &storage.chats).await;
+        assert_eq!(reply, Lang::En.tr("notify-usage"));
+    }
+}
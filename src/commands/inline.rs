@@ -0,0 +1,380 @@
+use crate::commands::chart::{render_chart, ChartPeriod};
+use crate::commands::currency::parse_symbols;
+use crate::commands::price::price_card;
+use crate::config::Config;
+use crate::i18n::Lang;
+use crate::models::preferences::{ChartTheme, Fiat};
+use crate::tools::asset_registry::AssetRegistry;
+use crate::tools::parse_currency::parse_currency;
+use futures::future::join_all;
+use log::{debug, warn};
+use regex::Regex;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use teloxide::prelude::*;
+use teloxide::types::{
+    InlineQueryResult, InlineQueryResultArticle, InlineQueryResultCachedPhoto, InputFile,
+    InputMessageContent, InputMessageContentText, ParseMode,
+};
+
+/// Most coins looked up for one inline query
+const MAX_INLINE_SYMBOLS: usize = 3;
+
+/// Time an uploaded chart is reused, it only changes once an hour
+const CHART_TTL: Duration = Duration::from_secs(10 * 60);
+
+/// What the user typed after `@bot`
+#[derive(Debug, PartialEq)]
+pub enum InlineRequest {
+    /// `0.5 btc`, the amount converted to USD
+    Convert(String),
+    /// `btc eth`, a price card and a chart per coin
+    Prices(Vec<String>),
+    /// Nothing to look up yet
+    Empty,
+}
+
+/// Parse an inline query
+///
+/// # Arguments
+///
+/// * `query` - Text after the bot username
+///
+/// # Returns
+///
+/// * `InlineRequest` - Conversion for `amount symbol`, otherwise up to
+///   `MAX_INLINE_SYMBOLS` unique symbols
+pub fn parse_inline_query(query: &str) -> InlineRequest {
+    let query = query.trim();
+    let conversion = Regex::new(r"^[0-9][0-9.,]*\s+[a-zA-Z]+$").expect("valid regex");
+    if conversion.is_match(query) {
+        return InlineRequest::Convert(query.to_string());
+    }
+
+    let symbols: Vec<String> = parse_symbols(query)
+        .into_iter()
+        .filter(|symbol| symbol.chars().all(|c| c.is_ascii_alphanumeric()))
+        .take(MAX_INLINE_SYMBOLS)
+        .collect();
+    if symbols.is_empty() {
+        InlineRequest::Empty
+    } else {
+        InlineRequest::Prices(symbols)
+    }
+}
+
+/// Answers and uploaded charts shared by the inline queries
+///
+/// Telegram sends a query per keystroke, so every query is tracked per user
+/// and only the latest one is answered (see [`InlineCache::is_latest`]).
+/// Answers are kept for `inline.cache_secs`, charts for `CHART_TTL`.
+#[derive(Clone)]
+pub struct InlineCache {
+    ttl: Duration,
+    state: Arc<Mutex<CacheState>>,
+}
+
+#[derive(Default)]
+struct CacheState {
+    answers: HashMap<(Lang, String), (Instant, Vec<InlineQueryResult>)>,
+    charts: HashMap<(Lang, String), (Instant, String)>,
+    latest: HashMap<u64, String>,
+}
+
+impl InlineCache {
+    /// Cache keeping answers for `ttl`
+    pub fn new(ttl: Duration) -> Self {
+        Self {
+            ttl,
+            state: Arc::new(Mutex::new(CacheState::default())),
+        }
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, CacheState> {
+        self.state
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    /// Remember `query_id` as the newest query of a user
+    pub fn track(&self, user_id: u64, query_id: &str) {
+        self.lock().latest.insert(user_id, query_id.to_string());
+    }
+
+    /// Returns true if the user sent no query after `query_id`
+    pub fn is_latest(&self, user_id: u64, query_id: &str) -> bool {
+        self.lock().latest.get(&user_id).map(String::as_str) == Some(query_id)
+    }
+
+    /// Forget a user once the latest query is answered
+    pub fn finish(&self, user_id: u64, query_id: &str) {
+        let mut state = self.lock();
+        if state.latest.get(&user_id).map(String::as_str) == Some(query_id) {
+            state.latest.remove(&user_id);
+        }
+    }
+
+    /// Cached answer to the same query in the same language
+    pub fn answer(&self, lang: Lang, query: &str) -> Option<Vec<InlineQueryResult>> {
+        let state = self.lock();
+        state
+            .answers
+            .get(&(lang, normalize(query)))
+            .filter(|(stored, _)| stored.elapsed() < self.ttl)
+            .map(|(_, results)| results.clone())
+    }
+
+    /// Cache an answer, dropping the expired ones
+    pub fn store_answer(&self, lang: Lang, query: &str, results: Vec<InlineQueryResult>) {
+        let ttl = self.ttl;
+        let mut state = self.lock();
+        state
+            .answers
+            .retain(|_, (stored, _)| stored.elapsed() < ttl);
+        state
+            .answers
+            .insert((lang, normalize(query)), (Instant::now(), results));
+    }
+
+    /// Telegram file id of an uploaded chart
+    pub fn chart(&self, lang: Lang, symbol: &str) -> Option<String> {
+        let state = self.lock();
+        state
+            .charts
+            .get(&(lang, symbol.to_string()))
+            .filter(|(stored, _)| stored.elapsed() < CHART_TTL)
+            .map(|(_, file_id)| file_id.clone())
+    }
+
+    /// Remember the file id of an uploaded chart
+    pub fn store_chart(&self, lang: Lang, symbol: &str, file_id: String) {
+        let mut state = self.lock();
+        state
+            .charts
+            .retain(|_, (stored, _)| stored.elapsed() < CHART_TTL);
+        state
+            .charts
+            .insert((lang, symbol.to_string()), (Instant::now(), file_id));
+    }
+}
+
+/// Queries differing in case or spacing share an answer
+fn normalize(query: &str) -> String {
+    query
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+        .to_lowercase()
+}
+
+/// Inline query handler
+///
+/// # Arguments
+///
+/// * `bot` - Bot, used to upload the charts
+/// * `query` - Text after the bot username
+/// * `lang` - Language of the results
+/// * `cache` - InlineCache
+/// * `registry` - AssetRegistry, used for the links on the price cards
+/// * `config` - Bot configuration
+///
+/// # Returns
+///
+/// * `Vec<InlineQueryResult>` - Price cards and charts, or a conversion, empty
+///   if nothing was found
+pub async fn inline_command(
+    bot: &Bot,
+    query: &str,
+    lang: Lang,
+    cache: &InlineCache,
+    registry: &AssetRegistry,
+    config: &Config,
+) -> Vec<InlineQueryResult> {
+    if let Some(results) = cache.answer(lang, query) {
+        return results;
+    }
+
+    let results = match parse_inline_query(query) {
+        InlineRequest::Empty => return vec![],
+        InlineRequest::Convert(text) => {
+            match parse_currency(&text, lang, Fiat::default(), registry, config).await {
+                Ok(conversion) => conversion
+                    .map(|conversion| vec![convert_result(&text, conversion, lang)])
+                    .unwrap_or_default(),
+                Err(err) => {
+                    warn!("Error converting {}: {}", text, err);
+                    vec![]
+                }
+            }
+        }
+        InlineRequest::Prices(symbols) => {
+            // Results are posted to other chats, so they skip the user's fiat and theme
+            let lookups = symbols.iter().map(|symbol| async move {
+                let (card, chart) = futures::join!(
+                    price_card(symbol.clone(), lang, Fiat::default(), registry, config),
+                    chart_file_id(bot, symbol, lang, cache, config)
+                );
+                let mut results = vec![];
+                if let Some(card) = card {
+                    results.push(price_result(symbol, card, lang));
+                    if let Some(file_id) = chart {
+                        results.push(chart_result(symbol, file_id, lang));
+                    }
+                }
+                results
+            });
+            join_all(lookups).await.into_iter().flatten().collect()
+        }
+    };
+
+    cache.store_answer(lang, query, results.clone());
+    results
+}
+
+/// Article sending the price card of a coin
+fn price_result(symbol: &str, card: String, lang: Lang) -> InlineQueryResult {
+    let content = InputMessageContentText::new(card)
+        .parse_mode(ParseMode::Html)
+        .disable_web_page_preview(true);
+    InlineQueryResultArticle::new(
+        format!("price:{}", symbol),
+        lang.tr_with("inline-price-title", &[("symbol", symbol.into())]),
+        InputMessageContent::Text(content),
+    )
+    .description(lang.tr("inline-price-description"))
+    .into()
+}
+
+/// Photo of the 24h chart of a coin
+fn chart_result(symbol: &str, file_id: String, lang: Lang) -> InlineQueryResult {
+    InlineQueryResultCachedPhoto::new(format!("chart:{}", symbol), file_id)
+        .title(lang.tr_with("inline-chart-title", &[("symbol", symbol.into())]))
+        .into()
+}
+
+/// Article sending the USD value of `amount symbol`
+fn convert_result(query: &str, conversion: String, lang: Lang) -> InlineQueryResult {
+    let content = InputMessageContentText::new(conversion).parse_mode(ParseMode::Html);
+    InlineQueryResultArticle::new(
+        "convert",
+        lang.tr("inline-convert-title"),
+        InputMessageContent::Text(content),
+    )
+    .description(query.to_string())
+    .into()
+}
+
+/// File id of the 24h chart of a coin
+///
+/// Inline results can only show photos Telegram already has, so the chart is
+/// uploaded to `inline.chart_chat_id` once and its file id reused.
+/// `None` without a chart chat or if the chart can't be drawn.
+async fn chart_file_id(
+    bot: &Bot,
+    symbol: &str,
+    lang: Lang,
+    cache: &InlineCache,
+    config: &Config,
+) -> Option<String> {
+    let chat_id = config.inline.chart_chat_id?;
+    if let Some(file_id) = cache.chart(lang, symbol) {
+        return Some(file_id);
+    }
+
+    let filename = match render_chart(
+        symbol,
+        ChartPeriod::Day,
+        ChartTheme::default(),
+        lang,
+        config,
+    )
+    .await
+    {
+        Ok(filename) => filename,
+        Err(err) => {
+            debug!("chart error {}: {}", symbol, err);
+            return None;
+        }
+    };
+    let sent = bot
+        .send_photo(ChatId(chat_id), InputFile::file(&filename))
+        .disable_notification(true)
+        .await;
+    if let Err(err) = std::fs::remove_file(&filename) {
+        warn!("Error removing {}: {}", filename, err);
+    }
+    let message = match sent {
+        Ok(message) => message,
+        Err(err) => {
+            warn!("Error uploading the inline chart of {}: {}", symbol, err);
+            return None;
+        }
+    };
+
+    let file_id = message.photo()?.last()?.file.id.clone();
+    cache.store_chart(lang, symbol, file_id.clone());
+    Some(file_id)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_inline_query() {
+        assert_eq!(
+            parse_inline_query(" 0.5 btc "),
+            InlineRequest::Convert("0.5 btc".to_string())
+        );
+        assert_eq!(
+            parse_inline_query("btc eth, btc"),
+            InlineRequest::Prices(vec!["BTC".to_string(), "ETH".to_string()])
+        );
+        assert_eq!(
+            parse_inline_query("btc eth sol doge"),
+            InlineRequest::Prices(vec![
+                "BTC".to_string(),
+                "ETH".to_string(),
+                "SOL".to_string()
+            ])
+        );
+        assert_eq!(parse_inline_query("  "), InlineRequest::Empty);
+        assert_eq!(parse_inline_query("<b>"), InlineRequest::Empty);
+    }
+
+    #[test]
+    fn test_debounce() {
+        let cache = InlineCache::new(Duration::from_secs(30));
+        cache.track(1, "a");
+        cache.track(2, "x");
+        cache.track(1, "b");
+        assert!(!cache.is_latest(1, "a"));
+        assert!(cache.is_latest(1, "b"));
+        assert!(cache.is_latest(2, "x"));
+
+        // An older query finishing late keeps the newer one tracked
+        cache.finish(1, "a");
+        assert!(cache.is_latest(1, "b"));
+        cache.finish(1, "b");
+        assert!(!cache.is_latest(1, "b"));
+    }
+
+    #[test]
+    fn test_answer_cache() {
+        let cache = InlineCache::new(Duration::from_secs(30));
+        let results = vec![price_result("BTC", "card".to_string(), Lang::En)];
+        cache.store_answer(Lang::En, "BTC  eth", results);
+        assert_eq!(cache.answer(Lang::En, "btc eth").map(|r| r.len()), Some(1));
+        assert!(cache.answer(Lang::Ru, "btc eth").is_none());
+        assert!(cache.answer(Lang::En, "btc").is_none());
+
+        let expired = InlineCache::new(Duration::ZERO);
+        expired.store_answer(Lang::En, "btc", vec![]);
+        assert!(expired.answer(Lang::En, "btc").is_none());
+
+        cache.store_chart(Lang::En, "BTC", "file".to_string());
+        assert_eq!(cache.chart(Lang::En, "BTC"), Some("file".to_string()));
+        assert!(cache.chart(Lang::Uk, "BTC").is_none());
+    }
+}
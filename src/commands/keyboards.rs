@@ -0,0 +1,364 @@
+use crate::commands::alert::ALERTS_CALLBACK_PREFIX;
+use crate::commands::chart::ChartPeriod;
+use crate::i18n::Lang;
+use crate::storage::UserRepository;
+use std::sync::Arc;
+use teloxide::types::{InlineKeyboardButton, InlineKeyboardMarkup};
+
+/// Callback data prefix of the buttons under price cards and charts
+pub const PRICE_CALLBACK_PREFIX: &str = "price:";
+/// Callback data prefix of the buttons under /priceall
+pub const PRICE_ALL_CALLBACK_PREFIX: &str = "priceall:";
+/// Callback data prefix of the buttons under argument-less /removecurrency
+pub const REMOVE_CALLBACK_PREFIX: &str = "removecurrency:";
+/// Callback data of the Cancel button under dialogue questions
+pub const CANCEL_CALLBACK_DATA: &str = "dialogue:cancel";
+
+/// Remove buttons per row under /priceall
+const REMOVE_BUTTONS_PER_ROW: usize = 3;
+
+/// Button pressed under a price card or a chart
+#[derive(Clone, Debug, PartialEq)]
+pub enum PriceAction {
+    /// Fetch the price again
+    Refresh(String),
+    /// Draw the chart of a period
+    Chart(String, ChartPeriod),
+    /// Add the coin to the watchlist
+    Add(String),
+    /// Remove the coin from the watchlist
+    Remove(String),
+    /// Set a price alert
+    Alert(String),
+}
+
+impl PriceAction {
+    /// Callback data of the button, e.g. `price:chart:BTC:7d`
+    pub fn data(&self) -> String {
+        let action = match self {
+            PriceAction::Refresh(symbol) => format!("refresh:{}", symbol),
+            PriceAction::Chart(symbol, period) => format!("chart:{}:{}", symbol, period.code()),
+            PriceAction::Add(symbol) => format!("add:{}", symbol),
+            PriceAction::Remove(symbol) => format!("remove:{}", symbol),
+            PriceAction::Alert(symbol) => format!("alert:{}", symbol),
+        };
+        format!("{}{}", PRICE_CALLBACK_PREFIX, action)
+    }
+
+    /// Action of a callback data, `None` if it is not a price button
+    pub fn parse(data: &str) -> Option<PriceAction> {
+        let mut parts = data.strip_prefix(PRICE_CALLBACK_PREFIX)?.split(':');
+        let action = parts.next()?;
+        let symbol = parts.next().filter(|symbol| !symbol.is_empty())?;
+        let symbol = symbol.to_uppercase();
+        let parsed = match (action, parts.next()) {
+            ("refresh", None) => PriceAction::Refresh(symbol),
+            ("chart", Some(period)) => PriceAction::Chart(symbol, ChartPeriod::from_code(period)?),
+            ("add", None) => PriceAction::Add(symbol),
+            ("remove", None) => PriceAction::Remove(symbol),
+            ("alert", None) => PriceAction::Alert(symbol),
+            _ => return None,
+        };
+        match parts.next() {
+            Some(_) => None,
+            None => Some(parsed),
+        }
+    }
+}
+
+/// Button pressed under /priceall
+#[derive(Clone, Debug, PartialEq)]
+pub enum PriceAllAction {
+    /// Fetch the prices again
+    Refresh,
+    /// Remove a coin from the watchlist
+    Remove(String),
+}
+
+impl PriceAllAction {
+    /// Callback data of the button, e.g. `priceall:remove:BTC`
+    pub fn data(&self) -> String {
+        match self {
+            PriceAllAction::Refresh => format!("{}refresh", PRICE_ALL_CALLBACK_PREFIX),
+            PriceAllAction::Remove(symbol) => {
+                format!("{}remove:{}", PRICE_ALL_CALLBACK_PREFIX, symbol)
+            }
+        }
+    }
+
+    /// Action of a callback data, `None` if it is not a /priceall button
+    pub fn parse(data: &str) -> Option<PriceAllAction> {
+        let rest = data.strip_prefix(PRICE_ALL_CALLBACK_PREFIX)?;
+        match rest.split_once(':') {
+            None if rest == "refresh" => Some(PriceAllAction::Refresh),
+            Some(("remove", symbol)) if !symbol.is_empty() && !symbol.contains(':') => {
+                Some(PriceAllAction::Remove(symbol.to_uppercase()))
+            }
+            _ => None,
+        }
+    }
+}
+
+/// Button that opens, answers or closes a dialogue
+#[derive(Clone, Debug, PartialEq)]
+pub enum DialogueAction {
+    /// Close the open dialogue
+    Cancel,
+    /// Ask for the target price of an alert, the Set alert button of price cards
+    Alert(String),
+    /// Remove a coin from the watchlist
+    RemoveCurrency(String),
+    /// Remove an alert
+    RemoveAlert(String),
+}
+
+impl DialogueAction {
+    /// Callback data of the button, e.g. `removecurrency:BTC`
+    pub fn data(&self) -> String {
+        match self {
+            DialogueAction::Cancel => CANCEL_CALLBACK_DATA.to_string(),
+            DialogueAction::Alert(symbol) => PriceAction::Alert(symbol.clone()).data(),
+            DialogueAction::RemoveCurrency(symbol) => {
+                format!("{}{}", REMOVE_CALLBACK_PREFIX, symbol)
+            }
+            DialogueAction::RemoveAlert(id) => format!("{}{}", ALERTS_CALLBACK_PREFIX, id),
+        }
+    }
+
+    /// Action of a callback data, `None` if it is not a dialogue button
+    pub fn parse(data: &str) -> Option<DialogueAction> {
+        if data == CANCEL_CALLBACK_DATA {
+            return Some(DialogueAction::Cancel);
+        }
+        if let Some(PriceAction::Alert(symbol)) = PriceAction::parse(data) {
+            return Some(DialogueAction::Alert(symbol));
+        }
+        if let Some(symbol) = data.strip_prefix(REMOVE_CALLBACK_PREFIX) {
+            return (!symbol.is_empty() && !symbol.contains(':'))
+                .then(|| DialogueAction::RemoveCurrency(symbol.to_uppercase()));
+        }
+        data.strip_prefix(ALERTS_CALLBACK_PREFIX)
+            .filter(|id| !id.is_empty())
+            .map(|id| DialogueAction::RemoveAlert(id.to_string()))
+    }
+}
+
+fn button(text: String, action: &PriceAction) -> InlineKeyboardButton {
+    InlineKeyboardButton::callback(text, action.data())
+}
+
+/// Buttons under a price card
+///
+/// # Arguments
+///
+/// * `symbol` - Coin of the card
+/// * `watched` - Whether the coin is in the watchlist, picks Add or Remove
+/// * `lang` - Language of the buttons
+///
+/// # Returns
+///
+/// * `InlineKeyboardMarkup` - Refresh, the chart periods, Add or Remove and Set alert
+pub fn price_keyboard(symbol: &str, watched: bool, lang: Lang) -> InlineKeyboardMarkup {
+    let symbol = symbol.to_uppercase();
+    let watchlist = if watched {
+        button(
+            lang.tr("button-remove"),
+            &PriceAction::Remove(symbol.clone()),
+        )
+    } else {
+        button(lang.tr("button-add"), &PriceAction::Add(symbol.clone()))
+    };
+
+    InlineKeyboardMarkup::new(vec![
+        vec![button(
+            lang.tr("button-refresh"),
+            &PriceAction::Refresh(symbol.clone()),
+        )],
+        ChartPeriod::ALL
+            .into_iter()
+            .map(|period| {
+                button(
+                    period_label(period, lang),
+                    &PriceAction::Chart(symbol.clone(), period),
+                )
+            })
+            .collect(),
+        vec![
+            watchlist,
+            button(lang.tr("button-alert"), &PriceAction::Alert(symbol.clone())),
+        ],
+    ])
+}
+
+/// Period buttons under a chart, the shown period is marked and redraws it
+pub fn chart_keyboard(symbol: &str, current: ChartPeriod, lang: Lang) -> InlineKeyboardMarkup {
+    let symbol = symbol.to_uppercase();
+    InlineKeyboardMarkup::new(vec![ChartPeriod::ALL
+        .into_iter()
+        .map(|period| {
+            let label = period_label(period, lang);
+            let label = if period == current {
+                format!("• {} •", label)
+            } else {
+                label
+            };
+            button(label, &PriceAction::Chart(symbol.clone(), period))
+        })
+        .collect::<Vec<_>>()])
+}
+
+/// Buttons under /priceall, Refresh and a Remove button per coin
+pub fn price_all_keyboard(symbols: &[String], lang: Lang) -> InlineKeyboardMarkup {
+    let mut rows = vec![vec![InlineKeyboardButton::callback(
+        lang.tr("button-refresh"),
+        PriceAllAction::Refresh.data(),
+    )]];
+    let symbols: Vec<String> = symbols.iter().map(|symbol| symbol.to_uppercase()).collect();
+    rows.extend(symbols.chunks(REMOVE_BUTTONS_PER_ROW).map(|chunk| {
+        chunk
+            .iter()
+            .map(|symbol| {
+                InlineKeyboardButton::callback(
+                    format!("✖ {}", symbol),
+                    PriceAllAction::Remove(symbol.clone()).data(),
+                )
+            })
+            .collect()
+    }));
+    InlineKeyboardMarkup::new(rows)
+}
+
+/// Cancel button under a dialogue question
+pub fn cancel_keyboard(lang: Lang) -> InlineKeyboardMarkup {
+    InlineKeyboardMarkup::new(vec![vec![InlineKeyboardButton::callback(
+        lang.tr("button-cancel"),
+        DialogueAction::Cancel.data(),
+    )]])
+}
+
+/// Watchlist as Remove buttons, for /removecurrency without arguments
+pub fn remove_keyboard(symbols: &[String], lang: Lang) -> InlineKeyboardMarkup {
+    let symbols: Vec<String> = symbols.iter().map(|symbol| symbol.to_uppercase()).collect();
+    let mut rows: Vec<Vec<InlineKeyboardButton>> = symbols
+        .chunks(REMOVE_BUTTONS_PER_ROW)
+        .map(|chunk| {
+            chunk
+                .iter()
+                .map(|symbol| {
+                    InlineKeyboardButton::callback(
+                        format!("✖ {}", symbol),
+                        DialogueAction::RemoveCurrency(symbol.clone()).data(),
+                    )
+                })
+                .collect()
+        })
+        .collect();
+    rows.push(vec![InlineKeyboardButton::callback(
+        lang.tr("button-done"),
+        DialogueAction::Cancel.data(),
+    )]);
+    InlineKeyboardMarkup::new(rows)
+}
+
+fn period_label(period: ChartPeriod, lang: Lang) -> String {
+    lang.tr(&format!("button-chart-{}", period.code()))
+}
+
+/// Returns true if the coin is in the user's watchlist
+pub async fn is_watched(db: &Arc<dyn UserRepository>, user_id: i64, symbol: &str) -> bool {
+    db.get_user(user_id)
+        .await
+        .map(|user| {
+            user.currency
+                .iter()
+                .any(|currency| currency.eq_ignore_ascii_case(symbol))
+        })
+        .unwrap_or(false)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_price_action_data() {
+        let actions = vec![
+            PriceAction::Refresh("BTC".to_string()),
+            PriceAction::Chart("BTC".to_string(), ChartPeriod::Week),
+            PriceAction::Add("ETH".to_string()),
+            PriceAction::Remove("ETH".to_string()),
+            PriceAction::Alert("SOL".to_string()),
+        ];
+        for action in actions {
+            assert_eq!(PriceAction::parse(&action.data()), Some(action));
+        }
+        assert_eq!(
+            PriceAction::parse("price:chart:btc:30d"),
+            Some(PriceAction::Chart("BTC".to_string(), ChartPeriod::Month))
+        );
+        assert_eq!(PriceAction::parse("price:chart:BTC:1y"), None);
+        assert_eq!(PriceAction::parse("price:refresh:BTC:24h"), None);
+        assert_eq!(PriceAction::parse("price:refresh:"), None);
+        assert_eq!(PriceAction::parse("lang:en"), None);
+
+        assert_eq!(
+            PriceAllAction::parse(&PriceAllAction::Remove("BTC".to_string()).data()),
+            Some(PriceAllAction::Remove("BTC".to_string()))
+        );
+        assert_eq!(
+            PriceAllAction::parse("priceall:refresh"),
+            Some(PriceAllAction::Refresh)
+        );
+        assert_eq!(PriceAllAction::parse("priceall:remove:"), None);
+
+        let actions = vec![
+            DialogueAction::Cancel,
+            DialogueAction::Alert("BTC".to_string()),
+            DialogueAction::RemoveCurrency("ETH".to_string()),
+            DialogueAction::RemoveAlert("1-1714521600000".to_string()),
+        ];
+        for action in actions {
+            assert_eq!(DialogueAction::parse(&action.data()), Some(action));
+        }
+        assert_eq!(
+            DialogueAction::parse("removecurrency:sol"),
+            Some(DialogueAction::RemoveCurrency("SOL".to_string()))
+        );
+        assert_eq!(DialogueAction::parse("removecurrency:"), None);
+        assert_eq!(DialogueAction::parse("price:refresh:BTC"), None);
+    }
+
+    #[test]
+    fn test_keyboards() {
+        let keyboard = price_keyboard("btc", true, Lang::En);
+        assert_eq!(keyboard.inline_keyboard.len(), 3);
+        assert_eq!(keyboard.inline_keyboard[1].len(), 3);
+        assert_eq!(
+            keyboard.inline_keyboard[2][0].text,
+            Lang::En.tr("button-remove")
+        );
+
+        let keyboard = chart_keyboard("btc", ChartPeriod::Week, Lang::En);
+        let labels: Vec<&str> = keyboard.inline_keyboard[0]
+            .iter()
+            .map(|button| button.text.as_str())
+            .collect();
+        assert_eq!(labels, vec!["24h", "• 7d •", "30d"]);
+
+        let symbols: Vec<String> = ["btc", "eth", "sol", "ton"]
+            .iter()
+            .map(|symbol| symbol.to_string())
+            .collect();
+        let keyboard = price_all_keyboard(&symbols, Lang::En);
+        assert_eq!(keyboard.inline_keyboard.len(), 3);
+        assert_eq!(keyboard.inline_keyboard[2][0].text, "✖ TON");
+
+        let keyboard = remove_keyboard(&symbols, Lang::En);
+        assert_eq!(keyboard.inline_keyboard.len(), 3);
+        assert_eq!(keyboard.inline_keyboard[1][0].text, "✖ TON");
+        assert_eq!(
+            keyboard.inline_keyboard[2][0].text,
+            Lang::En.tr("button-done")
+        );
+    }
+}
@@ -0,0 +1,68 @@
+use crate::i18n::Lang;
+use crate::storage::UserRepository;
+use crate::tools::html;
+use log::warn;
+use std::sync::Arc;
+use teloxide::types::{InlineKeyboardButton, InlineKeyboardMarkup, User};
+
+/// Callback data prefix of the /language buttons
+pub const LANGUAGE_CALLBACK_PREFIX: &str = "lang:";
+
+/// Language of a Telegram user
+///
+/// The language stored with /start or /language, else the one of their
+/// Telegram client. Users without a stored language are not created.
+///
+/// # Arguments
+///
+/// * `db` - User storage
+/// * `user` - Sender of the message or callback
+///
+/// # Returns
+///
+/// * `Lang` - Reply language
+pub async fn user_language(db: &Arc<dyn UserRepository>, user: Option<&User>) -> Lang {
+    let user = match user {
+        Some(user) => user,
+        None => return Lang::default(),
+    };
+    match db.get_language(user.id.0 as i64).await {
+        Ok(Some(lang)) => lang,
+        Ok(None) => Lang::detect(user.language_code.as_deref()),
+        Err(err) => {
+            warn!("Error getting language: {}", err);
+            Lang::detect(user.language_code.as_deref())
+        }
+    }
+}
+
+/// One button per supported language
+pub fn language_keyboard() -> InlineKeyboardMarkup {
+    InlineKeyboardMarkup::new(vec![Lang::ALL
+        .into_iter()
+        .map(|lang| {
+            InlineKeyboardButton::callback(
+                lang.name(),
+                format!("{}{}", LANGUAGE_CALLBACK_PREFIX, lang.code()),
+            )
+        })
+        .collect::<Vec<_>>()])
+}
+
+/// Store the language picked with /language
+///
+/// # Arguments
+///
+/// * `user_id` - User id
+/// * `lang` - Picked language
+/// * `db` - User storage
+///
+/// # Returns
+///
+/// * `String` - Confirmation in the picked language
+pub async fn set_language_command(user_id: i64, lang: Lang, db: Arc<dyn UserRepository>) -> String {
+    match db.set_language(user_id, lang).await {
+        Ok(()) => lang.tr("language-set"),
+        Err(err) => html::escape(&err.to_string()),
+    }
+}
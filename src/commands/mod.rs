@@ -1,7 +1,14 @@
+pub mod alert;
+pub mod channel;
 pub mod chart;
 pub mod currency;
+pub mod group;
+pub mod inline;
+pub mod keyboards;
+pub mod language;
 pub mod notify;
 pub mod price;
 pub mod price_all;
 pub mod send_all;
+pub mod settings;
 pub mod start;
@@ -1,14 +1,44 @@
-use crate::db::DatabaseManager;
+use crate::i18n::Lang;
+use crate::models::preferences::Preference;
+use crate::storage::UserRepository;
+use crate::tools::html;
+use std::sync::Arc;
 
-pub async fn notify_command(user_id: i64, cfg: DatabaseManager) -> String {
-    let user_query = cfg.get_user(user_id).await;
-    let user = match user_query {
-        Some(user) => user,
-        None => return "Error getting user".to_string(),
+/// Turn the daily digest on or off
+///
+/// # Arguments
+///
+/// * `user_id` - User id
+/// * `state` - `on` or `off`, empty to show the current state
+/// * `lang` - Language of the reply
+/// * `cfg` - UserRepository
+///
+/// # Returns
+///
+/// * `String` - Result of the change, the current state or the usage
+pub async fn notify_command(
+    user_id: i64,
+    state: &str,
+    lang: Lang,
+    cfg: Arc<dyn UserRepository>,
+) -> String {
+    let on = match state.trim().to_lowercase().as_str() {
+        "" => {
+            return match cfg.get_preferences(user_id).await {
+                Ok(Some(preferences)) if preferences.digest => lang.tr("notify-state-on"),
+                Ok(Some(_)) => lang.tr("notify-state-off"),
+                Ok(None) => lang.tr("user-error"),
+                Err(err) => html::escape(&err.to_string()),
+            }
+        }
+        "on" => true,
+        "off" => false,
+        _ => return lang.tr("notify-usage"),
     };
-    let result = cfg.change_notify(user).await;
-    match result {
-        Ok(res) => res,
-        Err(err) => err.to_string(),
+
+    match cfg.set_preference(user_id, Preference::Digest(on)).await {
+        Ok(_) if on => lang.tr("notify-on"),
+        Ok(_) => lang.tr("notify-off"),
+        Err(err) => html::escape(&err.to_string()),
     }
 }
@@ -1,10 +1,79 @@
+use crate::config::Config;
+use crate::i18n::Lang;
+use crate::models::preferences::Fiat;
+use crate::tools::asset_registry::AssetRegistry;
+use crate::tools::html;
+use crate::tools::metrics::Upstream;
+use crate::tools::numbers;
+use log::debug;
 use reqwest::Url;
-use std::env;
-pub async fn price_command(currency: String) -> String {
-    let result = get_currency_price(currency).await;
+
+/// /price command handler
+///
+/// # Arguments
+///
+/// * `currency` - The currency to fetch
+/// * `lang` - Reply language
+/// * `fiat` - Currency the price is shown in
+/// * `registry` - AssetRegistry, used for the CoinMarketCap link
+/// * `config` - Bot configuration
+///
+/// # Returns
+///
+/// * `String` - Price card in Telegram HTML
+pub async fn price_command(
+    currency: String,
+    lang: Lang,
+    fiat: Fiat,
+    registry: &AssetRegistry,
+    config: &Config,
+) -> String {
+    let symbol = currency.to_uppercase();
+    let result = get_currency_price(currency, lang, fiat, registry, config).await;
     match result {
-        Ok(result) => result,
-        Err(err) => err.to_string(),
+        Ok(Some(card)) => card,
+        Ok(None) => lang.tr_with("price-not-found", &[("symbol", symbol.into())]),
+        Err(err) => {
+            debug!("price error {}", err);
+            lang.tr_with("price-error", &[("symbol", symbol.into())])
+        }
+    }
+}
+
+/// Price card of a coin, `None` if it is unknown or the request failed
+///
+/// Used by inline mode, which leaves out coins without a card instead of
+/// answering with an error.
+pub async fn price_card(
+    currency: String,
+    lang: Lang,
+    fiat: Fiat,
+    registry: &AssetRegistry,
+    config: &Config,
+) -> Option<String> {
+    match get_currency_price(currency, lang, fiat, registry, config).await {
+        Ok(card) => card,
+        Err(err) => {
+            debug!("price error {}", err);
+            None
+        }
+    }
+}
+
+/// Amount with the sign of the fiat currency, e.g. `€ 1,234.50`
+fn amount(value: f64, fiat: Fiat, lang: Lang) -> String {
+    format!("{} {}", fiat.sign(), numbers::price(value, lang))
+}
+
+/// Links to the CoinMarketCap and Binance pages of a coin, empty for unknown coins
+fn links(symbol: &str, registry: &AssetRegistry) -> String {
+    match registry.resolve(symbol).first() {
+        Some(asset) => format!(
+            "\n{} · {}",
+            html::link(&html::cmc_url(&asset.slug), "CoinMarketCap"),
+            html::link(&html::binance_url(symbol), "Binance")
+        ),
+        None => String::new(),
     }
 }
 
@@ -12,49 +81,53 @@ pub async fn price_command(currency: String) -> String {
 ///
 /// # Arguments
 ///
-/// * `value` - The amount of the currency to convert
-/// * `currency` - The currency to convert to
+/// * `currency` - The currency to fetch
+/// * `lang` - Reply language
+/// * `fiat` - Currency the price is shown in
+/// * `registry` - AssetRegistry, used for the CoinMarketCap link
+/// * `config` - Bot configuration
 ///
 /// # Returns
 ///
-/// * `Result<String, Box<dyn std::error::Error>>` - The price of the currency in USD
+/// * `Result<Option<String>, Box<dyn std::error::Error>>` - Price card of the
+///   currency in `fiat`, `None` if the currency is not found
 ///
 /// # Errors
 ///
 /// * `Error invalid value: 0.0` - If the value is 0.0
 /// * `Error fetching price for {}: Status code {}` - If the status code is not 200
-/// * `Error fetching price for {}: Currency not found` - If the currency is not found
 ///
 /// # Examples
 ///
 /// ```
-/// let result = get_currency_price(value, currency).await;
+/// let result = get_currency_price(currency, lang, fiat, &registry, &config).await;
 /// match result {
-/// Ok(result) => result,
+/// Ok(Some(card)) => card,
+/// Ok(None) => "not found".to_string(),
 /// Err(err) => err.to_string(),
 /// }
 ///
 /// ```
 ///
-
-async fn get_currency_price(currency: String) -> Result<String, Box<dyn std::error::Error>> {
-    let client = reqwest::Client::new();
-
+async fn get_currency_price(
+    currency: String,
+    lang: Lang,
+    fiat: Fiat,
+    registry: &AssetRegistry,
+    config: &Config,
+) -> Result<Option<String>, Box<dyn std::error::Error>> {
     let currency = currency.to_uppercase();
 
-    let token = env::var("CMC_TOKEN").expect("Fatality! CMC_PRO_API_KEY not set!");
-
     let url = Url::parse_with_params(
         "https://min-api.cryptocompare.com/data/pricemultifull",
-        &[("fsyms", &currency), ("tsyms", &"USD".to_string())],
+        &[("fsyms", currency.as_str()), ("tsyms", fiat.code())],
     )?;
 
-    let response = client
-        .get(url)
-        .header("X-CMC_PRO_API_KEY", token)
-        .header("Accept", "application/json")
-        .send()
-        .await?;
+    let http = config.http();
+    // The free CryptoCompare tier needs no key, the CoinMarketCap one stays
+    // with CoinMarketCap
+    let request = http.get(url).header("Accept", "application/json");
+    let response = http.send(Upstream::CryptoCompare, request).await?;
 
     if !response.status().is_success() {
         return Err(format!(
@@ -72,19 +145,39 @@ async fn get_currency_price(currency: String) -> Result<String, Box<dyn std::err
         Err(err) => return Err(err.into()),
     };
 
-    let price = response_json["RAW"][&currency]["USD"]["PRICE"].as_f64();
-    let max_price = response_json["RAW"][&currency]["USD"]["HIGH24HOUR"].as_f64();
-    let min_price = response_json["RAW"][&currency]["USD"]["LOW24HOUR"].as_f64();
-    let change = response_json["RAW"][&currency]["USD"]["CHANGEPCT24HOUR"].as_f64();
+    let raw = &response_json["RAW"][&currency][fiat.code()];
+    let price = raw["PRICE"].as_f64();
+    let max_price = raw["HIGH24HOUR"].as_f64();
+    let min_price = raw["LOW24HOUR"].as_f64();
+    let change = raw["CHANGEPCT24HOUR"].as_f64();
+    let market_cap = raw["MKTCAP"].as_f64();
 
     let result = match (price, max_price, min_price, change) {
         (Some(price), Some(max_price), Some(min_price), Some(change)) => {
-            format!(
-                "💰Coin: {}\n💵Price USD: $ {:.2}\n📊Change per 24 hour: {:.2}%\n📈High price(24 hour): $ {:.2}\n📉Low price(24 hour): $ {:.2}",
-                currency, price, change, max_price, min_price
-            )
+            let mut card = lang.tr_with(
+                "price-info",
+                &[
+                    ("symbol", currency.clone().into()),
+                    ("fiat", fiat.code().into()),
+                    ("price", amount(price, fiat, lang).into()),
+                    ("change", numbers::percent_change(change, lang).into()),
+                    ("high", amount(max_price, fiat, lang).into()),
+                    ("low", amount(min_price, fiat, lang).into()),
+                ],
+            );
+            if let Some(market_cap) = market_cap.filter(|cap| *cap > 0.0) {
+                card += "\n";
+                card += &lang.tr_with(
+                    "price-market-cap",
+                    &[(
+                        "cap",
+                        format!("{} {}", fiat.sign(), numbers::compact(market_cap, lang)).into(),
+                    )],
+                );
+            }
+            Some(card + &links(&currency, registry))
         }
-        _ => format!("Error fetching data for {}: Currency not found", currency),
+        _ => None,
     };
 
     Ok(result)
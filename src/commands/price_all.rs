@@ -1,45 +1,208 @@
+use crate::config::Config;
+use crate::i18n::Lang;
+use crate::models::preferences::Fiat;
 use crate::models::user::User;
-use log::{debug, info};
+use crate::tools::html;
+use crate::tools::metrics::Upstream;
+use crate::tools::numbers;
+use crate::tools::quota::{quotas, record_cmc_credits, QuotaLevel};
+use log::{debug, info, warn};
 use reqwest::Url;
-use std::env;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::sync::{Mutex, OnceLock};
 
 /// /priceall command handler
-/// send info about all user currency
-pub async fn price_all_command(user: User) -> String {
+/// send info about all user currency, in the user's fiat currency
+pub async fn price_all_command(user: User, lang: Lang, config: &Config) -> String {
     info!("price_all_command");
-    if user.currency.is_empty() {
-        return "You don't have any currency, type /addcurency curency-name".to_string();
+    watchlist_prices(
+        user.currency,
+        &user.cmc_ids,
+        user.preferences.fiat,
+        lang,
+        config,
+    )
+    .await
+}
+
+/// Price table of a watchlist, shared by users and group chats
+///
+/// # Arguments
+///
+/// * `currency` - Coins of the watchlist
+/// * `cmc_ids` - Resolved CoinMarketCap ids of the coins
+/// * `fiat` - Currency the prices are shown in
+/// * `lang` - Language of the table
+/// * `config` - Bot configuration
+///
+/// # Returns
+///
+/// * `String` - The table, or the empty list or error text
+pub async fn watchlist_prices(
+    currency: Vec<String>,
+    cmc_ids: &HashMap<String, i64>,
+    fiat: Fiat,
+    lang: Lang,
+    config: &Config,
+) -> String {
+    if currency.is_empty() {
+        return lang.tr("priceall-empty");
     }
-    let result = get_currency_price_multi(user.currency).await;
-    match result {
+    match get_currency_price_multi(currency, cmc_ids, fiat, lang, config).await {
         Ok(res) => res,
         Err(e) => {
             debug!("price all error {}", e);
-            "Error, maybe you don't have any valid currency".to_string()
+            lang.tr("priceall-error")
         }
     }
 }
 
 async fn get_currency_price_multi(
     currency: Vec<String>,
+    cmc_ids: &HashMap<String, i64>,
+    fiat: Fiat,
+    lang: Lang,
+    config: &Config,
 ) -> Result<String, Box<dyn std::error::Error>> {
-    let client = reqwest::Client::new();
+    let quotes = fetch_quotes(&currency, cmc_ids, fiat, config).await?;
+    if quotes.is_empty() {
+        return Err(format!("No prices for {}", currency.join(",")).into());
+    }
+    let header = lang.tr_with("priceall-header", &[("fiat", fiat.code().into())]);
+    Ok(prices_table(&quotes, &header, lang))
+}
+
+/// Price of one coin of a watchlist
+///
+/// # Fields
+///
+/// * `symbol` - Upper case symbol
+/// * `price` - Price in the requested fiat currency
+/// * `change_24h` - Change over 24 hours in percent
+/// * `slug` - CoinMarketCap slug, for the link
+///
+#[derive(Clone, Debug, PartialEq)]
+pub struct Quote {
+    pub symbol: String,
+    pub price: f64,
+    pub change_24h: Option<f64>,
+    pub slug: Option<String>,
+}
 
-    let currency_string = currency.clone().join(",").to_uppercase();
+/// Symbol, fiat and CoinMarketCap id of a cached quote
+type QuoteKey = (String, &'static str, Option<i64>);
 
-    let token = env::var("CMC_TOKEN").expect("Fatality! CMC_PRO_API_KEY not set!");
+/// Last quote of every coin fetched
+fn quote_cache() -> &'static Mutex<HashMap<QuoteKey, Quote>> {
+    static CACHE: OnceLock<Mutex<HashMap<QuoteKey, Quote>>> = OnceLock::new();
+    CACHE.get_or_init(Default::default)
+}
+
+/// Cached quotes of the coins, `None` unless every coin has one
+fn cached_quotes(
+    currency: &[String],
+    cmc_ids: &HashMap<String, i64>,
+    fiat: Fiat,
+) -> Option<Vec<Quote>> {
+    let cache = quote_cache().lock().unwrap();
+    currency
+        .iter()
+        .map(|item| {
+            let key = (item.to_uppercase(), fiat.code(), cmc_ids.get(item).copied());
+            cache.get(&key).cloned()
+        })
+        .collect()
+}
+
+/// Latest quotes of several coins, from the cache when CoinMarketCap can't
+/// be asked
+///
+/// Quotes are fetched while the CoinMarketCap budget in `quotas` is above its
+/// reserve. Past it, and when the request fails, the last quotes fetched are
+/// served if every coin has one.
+///
+/// # Arguments
+///
+/// * `currency` - Coins to look up
+/// * `cmc_ids` - Resolved CoinMarketCap ids of the coins
+/// * `fiat` - Currency of the prices
+/// * `config` - Bot configuration
+///
+/// # Returns
+///
+/// * `Result<Vec<Quote>, Box<dyn std::error::Error>>` - Quotes in the order of
+///   `currency`, coins without a price are left out
+pub async fn fetch_quotes(
+    currency: &[String],
+    cmc_ids: &HashMap<String, i64>,
+    fiat: Fiat,
+    config: &Config,
+) -> Result<Vec<Quote>, Box<dyn std::error::Error>> {
+    if quotas().level(Upstream::CoinMarketCap, &config.quotas) != QuotaLevel::Normal {
+        if let Some(quotes) = cached_quotes(currency, cmc_ids, fiat) {
+            debug!("CoinMarketCap quota low, cached quotes served");
+            return Ok(quotes);
+        }
+    }
+
+    match request_quotes(currency, cmc_ids, fiat, config).await {
+        Ok(quotes) => {
+            let mut cache = quote_cache().lock().unwrap();
+            for item in currency {
+                let symbol = item.to_uppercase();
+                if let Some(quote) = quotes.iter().find(|quote| quote.symbol == symbol) {
+                    let key = (symbol, fiat.code(), cmc_ids.get(item).copied());
+                    cache.insert(key, quote.clone());
+                }
+            }
+            Ok(quotes)
+        }
+        Err(e) => match cached_quotes(currency, cmc_ids, fiat) {
+            Some(quotes) => {
+                warn!("Cached quotes served, CoinMarketCap request failed: {}", e);
+                Ok(quotes)
+            }
+            None => Err(e),
+        },
+    }
+}
+
+/// Latest quotes of several coins with one CoinMarketCap request
+///
+/// # Arguments
+///
+/// * `currency` - Coins to look up
+/// * `cmc_ids` - Resolved CoinMarketCap ids of the coins
+/// * `fiat` - Currency of the prices
+/// * `config` - Bot configuration
+///
+/// # Returns
+///
+/// * `Result<Vec<Quote>, Box<dyn std::error::Error>>` - Quotes in the order of
+///   `currency`, coins without a price are left out
+async fn request_quotes(
+    currency: &[String],
+    cmc_ids: &HashMap<String, i64>,
+    fiat: Fiat,
+    config: &Config,
+) -> Result<Vec<Quote>, Box<dyn std::error::Error>> {
+    let currency_string = currency.join(",").to_uppercase();
 
     let url = Url::parse_with_params(
         "https://pro-api.coinmarketcap.com/v2/cryptocurrency/quotes/latest",
-        &[("symbol", &currency_string)],
+        &[
+            ("symbol", currency_string.as_str()),
+            ("convert", fiat.code()),
+        ],
     )?;
 
-    let response = client
+    let http = config.http();
+    let request = http
         .get(url)
-        .header("X-CMC_PRO_API_KEY", token)
-        .header("Accept", "application/json")
-        .send()
-        .await?;
+        .header("X-CMC_PRO_API_KEY", &config.cmc_token)
+        .header("Accept", "application/json");
+    let response = http.send(Upstream::CoinMarketCap, request).await?;
 
     if !response.status().is_success() {
         return Err(format!(
@@ -50,31 +213,76 @@ async fn get_currency_price_multi(
         .into());
     }
 
-    let response_json = response.json::<serde_json::Value>().await;
-
-    let response_json = match response_json {
-        Ok(response_json) => response_json,
-        Err(err) => return Err(err.into()),
-    };
-
-    let mut result_vec = Vec::new();
+    let response_json = response.json::<serde_json::Value>().await?;
+    record_cmc_credits(&response_json["status"]);
 
+    let mut quotes: Vec<Quote> = Vec::new();
     for item in currency {
-        let price =
-            response_json["data"][&item.to_uppercase()][0]["quote"]["USD"]["price"].as_f64();
-        let price_str = price.map(|price| {
-            format!(
-                "Coin📈: {}\nPrice USD💵: {:.2}$\n",
-                item.to_uppercase(),
-                price
-            )
-        });
-        if let Some(price_str) = price_str {
-            result_vec.push(price_str);
+        let symbol = item.to_uppercase();
+        let entry = select_entry(&response_json["data"][&symbol], cmc_ids.get(item).copied());
+        let quote = entry.map(|entry| &entry["quote"][fiat.code()]);
+        if let Some(price) = quote.and_then(|quote| quote["price"].as_f64()) {
+            quotes.push(Quote {
+                symbol,
+                price,
+                change_24h: quote.and_then(|quote| quote["percent_change_24h"].as_f64()),
+                slug: entry
+                    .and_then(|entry| entry["slug"].as_str())
+                    .map(str::to_string),
+            });
         }
     }
 
-    let result_string = result_vec.join("-----------\n");
+    Ok(quotes)
+}
+
+/// The /priceall table: header, aligned rows and CoinMarketCap links
+pub fn prices_table(quotes: &[Quote], header: &str, lang: Lang) -> String {
+    let rows: Vec<Vec<String>> = quotes
+        .iter()
+        .map(|quote| {
+            let change = quote
+                .change_24h
+                .map(|change| numbers::signed_percent(change, lang))
+                .unwrap_or_default();
+            vec![
+                quote.symbol.clone(),
+                numbers::price(quote.price, lang),
+                change,
+            ]
+        })
+        .collect();
+    let links: Vec<String> = quotes
+        .iter()
+        .filter_map(|quote| {
+            let slug = quote.slug.as_deref()?;
+            Some(html::link(&html::cmc_url(slug), &quote.symbol))
+        })
+        .collect();
+
+    let mut result_string = format!("{}\n{}", header, html::table(&rows));
+    if !links.is_empty() {
+        result_string += "\n";
+        result_string += &links.join(" · ");
+    }
+    result_string
+}
+
+/// CoinMarketCap returns every coin sharing a symbol, pick the one the user
+/// resolved through the asset registry or else the best ranked one
+pub(crate) fn select_entry(entries: &Value, cmc_id: Option<i64>) -> Option<&Value> {
+    let entries = entries.as_array()?;
+
+    if let Some(cmc_id) = cmc_id {
+        if let Some(entry) = entries
+            .iter()
+            .find(|entry| entry["id"].as_i64() == Some(cmc_id))
+        {
+            return Some(entry);
+        }
+    }
 
-    Ok(result_string)
+    entries
+        .iter()
+        .min_by_key(|entry| entry["cmc_rank"].as_i64().unwrap_or(i64::MAX))
 }
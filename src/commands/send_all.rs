@@ -1,19 +1,182 @@
-use crate::commands::price_all::price_all_command;
-use crate::db::DatabaseManager;
-use chrono::{Local, TimeZone};
-use log::{debug, error};
-use mongodb::bson::doc;
+use crate::commands::price_all::{price_all_command, watchlist_prices};
+use crate::config::Config;
+use crate::i18n::Lang;
+use crate::models::broadcast::Broadcast;
+use crate::models::preferences::Preferences;
+use crate::storage::{
+    BroadcastRepository, ChatRepository, DigestSlot, Storage, UserFilter, UserRepository,
+};
+use crate::tools::metrics::metrics;
+use crate::tools::shutdown::Shutdown;
+use crate::tools::supervisor::JobResult;
+use chrono::{DateTime, Local, Timelike, Utc};
+use chrono_tz::{Tz, TZ_VARIANTS};
+use log::{debug, error, info};
+use mongodb::bson;
+use std::collections::BTreeMap;
+use std::sync::Arc;
 use teloxide::prelude::*;
+use teloxide::types::ParseMode;
 use tokio::time::{self, Duration};
 
-async fn send_all_currency(cfg: DatabaseManager, bot: Bot, _text: String) {
-    let filter = Some(doc! {
-        "notification": true,
-        "currency.0": { "$exists": true }
-    });
-    let users_query = cfg.get_all_users(filter).await;
+/// Messages sent between two saves of the broadcast progress
+const PROGRESS_EVERY: usize = 20;
 
-    let users = match users_query {
+/// Sends the digest and /sendall messages
+///
+/// Group chats get the digest too, their negative ids share the pending list
+/// with the users. Broadcasts run as tracked tasks. When shutdown starts they stop after the
+/// current message and save who is still waiting, `resume` picks them up on
+/// the next start.
+#[derive(Clone)]
+pub struct Broadcaster {
+    bot: Bot,
+    config: Arc<Config>,
+    users: Arc<dyn UserRepository>,
+    chats: Arc<dyn ChatRepository>,
+    broadcasts: Arc<dyn BroadcastRepository>,
+    shutdown: Shutdown,
+}
+
+impl Broadcaster {
+    pub fn new(bot: Bot, config: Arc<Config>, storage: &Storage, shutdown: Shutdown) -> Self {
+        Self {
+            bot,
+            config,
+            users: storage.users.clone(),
+            chats: storage.chats.clone(),
+            broadcasts: storage.broadcasts.clone(),
+            shutdown,
+        }
+    }
+
+    /// Save the broadcast and start sending it in the background
+    pub async fn start(&self, broadcast: Broadcast) {
+        if let Err(err) = self.broadcasts.save_broadcast(&broadcast).await {
+            error!("Error saving broadcast {}: {}", broadcast.id, err);
+        }
+        let broadcaster = self.clone();
+        self.shutdown.spawn(broadcaster.run(broadcast));
+    }
+
+    /// Continue the broadcasts interrupted by the last shutdown
+    pub async fn resume(&self) {
+        let broadcasts = match self.broadcasts.get_broadcasts().await {
+            Ok(broadcasts) => broadcasts,
+            Err(err) => {
+                error!("Error loading broadcasts: {}", err);
+                return;
+            }
+        };
+
+        for broadcast in broadcasts {
+            info!(
+                "Resuming broadcast {}, {} users left",
+                broadcast.id,
+                broadcast.pending.len()
+            );
+            let broadcaster = self.clone();
+            self.shutdown.spawn(broadcaster.run(broadcast));
+        }
+    }
+
+    /// Send the broadcast to every pending user
+    async fn run(self, mut broadcast: Broadcast) {
+        let pending = std::mem::take(&mut broadcast.pending);
+
+        for (i, user_id) in pending.iter().enumerate() {
+            if self.shutdown.is_cancelled() {
+                broadcast.pending = pending[i..].to_vec();
+                self.save(&mut broadcast).await;
+                info!(
+                    "Broadcast {} paused, {} users left",
+                    broadcast.id,
+                    broadcast.pending.len()
+                );
+                return;
+            }
+
+            self.send(&broadcast, *user_id).await;
+
+            if (i + 1) % PROGRESS_EVERY == 0 {
+                broadcast.pending = pending[i + 1..].to_vec();
+                self.save(&mut broadcast).await;
+            }
+        }
+
+        if let Err(err) = self.broadcasts.delete_broadcast(&broadcast.id).await {
+            error!("Error deleting broadcast {}: {}", broadcast.id, err);
+        }
+        info!("Broadcast {} sent to {} users", broadcast.id, pending.len());
+    }
+
+    async fn save(&self, broadcast: &mut Broadcast) {
+        broadcast.updated_at = bson::DateTime::now();
+        if let Err(err) = self.broadcasts.save_broadcast(broadcast).await {
+            error!("Error saving broadcast {}: {}", broadcast.id, err);
+        }
+    }
+
+    /// Send one broadcast message, the digest is built from the user's or the
+    /// group's list in their language
+    async fn send(&self, broadcast: &Broadcast, user_id: i64) {
+        let chat = ChatId(user_id);
+        let (text, lang) = match &broadcast.text {
+            Some(text) => (text.clone(), Lang::default()),
+            None if user_id < 0 => match self.chats.get_chat(user_id).await {
+                Ok(Some(group)) => {
+                    let lang = group.preferences.language.unwrap_or_default();
+                    let text = watchlist_prices(
+                        group.currency,
+                        &group.cmc_ids,
+                        group.preferences.fiat,
+                        lang,
+                        &self.config,
+                    )
+                    .await;
+                    (text, lang)
+                }
+                _ => return,
+            },
+            None => match self.users.get_user(user_id).await {
+                Some(user) => {
+                    let lang = user.preferences.language.unwrap_or_default();
+                    (price_all_command(user, lang, &self.config).await, lang)
+                }
+                None => return,
+            },
+        };
+
+        // The digest is HTML, /sendall texts are sent as typed
+        let mut request = self.bot.send_message(chat, text);
+        if broadcast.text.is_none() {
+            request = request.parse_mode(ParseMode::Html);
+        }
+        let result = request.await;
+        metrics().broadcast(result.is_ok());
+        if let Err(err) = result {
+            debug!("Error sending broadcast to {}: {}", user_id, err);
+            return;
+        }
+
+        if broadcast.text.is_none() {
+            let mess = lang.tr("digest-unsubscribe");
+            if let Err(err) = self.bot.send_message(chat, mess).await {
+                debug!("Error sending the unsubscribe hint to {}: {}", user_id, err);
+            }
+        }
+    }
+}
+
+/// Send the digest to the subscribed users and groups with a watchlist whose
+/// digest time is `now`, only the users due are loaded
+async fn send_all_currency(broadcaster: &Broadcaster, now: DateTime<Utc>, default: (u32, u32)) {
+    let filter = UserFilter {
+        digest: Some(true),
+        with_currency: true,
+        digest_slots: Some(digest_slots(now, default)),
+    };
+    let users = match broadcaster.users.get_all_users(filter).await {
         Ok(users) => users,
         Err(err) => {
             debug!("Error getting users: {}", err);
@@ -21,73 +184,190 @@ async fn send_all_currency(cfg: DatabaseManager, bot: Bot, _text: String) {
         }
     };
 
-    tokio::spawn(async move {
-        for user in users {
-            let currency_text = price_all_command(user.clone()).await;
-
-            if let Err(err) = bot
-                .send_message(UserId(user.user_id as u64), currency_text)
-                .await
-            {
-                debug!("Error sending photo: {}", err);
-            }
-            let mess = "\n Для отключения уведомлений напишите /notify";
-            if let Err(err) = bot.send_message(UserId(user.user_id as u64), mess).await {
-                debug!("Error sending photo: {}", err);
-            }
+    let chats = match broadcaster.chats.get_digest_chats().await {
+        Ok(chats) => chats,
+        Err(err) => {
+            debug!("Error getting chats: {}", err);
+            vec![]
         }
-    });
+    };
+
+    let pending: Vec<i64> = users
+        .iter()
+        .map(|user| user.user_id)
+        .chain(
+            chats
+                .iter()
+                .filter(|chat| digest_due(&chat.preferences, now, default))
+                .map(|chat| chat.chat_id),
+        )
+        .collect();
+    if pending.is_empty() {
+        return;
+    }
+
+    let id = format!("digest-{}", now.format("%Y-%m-%dT%H:%M"));
+    broadcaster.start(Broadcast::new(id, None, pending)).await;
 }
+
 /// Send all users a message
-pub async fn send_all_command(cfg: DatabaseManager, bot: Bot, text: String) {
-    let users = cfg.get_all_users(None).await.unwrap();
-
-    for user in users {
-        if let Err(err) = bot
-            .send_message(UserId(user.user_id as u64), text.clone())
-            .await
-        {
-            error!("Error sending photo: {}", err);
+pub async fn send_all_command(broadcaster: &Broadcaster, text: String) {
+    let users = match broadcaster.users.get_all_users(UserFilter::default()).await {
+        Ok(users) => users,
+        Err(err) => {
+            error!("Error getting users: {}", err);
+            return;
         }
+    };
+
+    let id = format!("sendall-{}", bson::DateTime::now().timestamp_millis());
+    let pending = users.iter().map(|user| user.user_id).collect();
+    broadcaster
+        .start(Broadcast::new(id, Some(text), pending))
+        .await;
+}
+
+/// Returns true if `now` is the digest time of the user
+///
+/// The time is local to the user's time zone, or the server's without one.
+/// A time skipped by a DST change has no digest that day.
+pub fn digest_due(preferences: &Preferences, now: DateTime<Utc>, default: (u32, u32)) -> bool {
+    local_time_is(now, preferences.tz(), preferences.digest_at(default))
+}
+
+/// Slots of the users whose digest is due at `now`
+///
+/// Time zones are grouped by their local time at `now`, each group with the
+/// digest time it is there. The server's time zone joins the group of its
+/// local time, and the group at the `default` time also takes the users on
+/// the default time.
+pub fn digest_slots(now: DateTime<Utc>, default: (u32, u32)) -> Vec<DigestSlot> {
+    let mut groups: BTreeMap<(u32, u32), Vec<Option<String>>> = BTreeMap::new();
+    for tz in TZ_VARIANTS {
+        let local = now.with_timezone(&tz).time();
+        groups
+            .entry((local.hour(), local.minute()))
+            .or_default()
+            .push(Some(tz.name().to_string()));
     }
+    let local = now.with_timezone(&Local).time();
+    groups
+        .entry((local.hour(), local.minute()))
+        .or_default()
+        .push(None);
+
+    groups
+        .into_iter()
+        .map(|((hour, minute), timezones)| {
+            let mut times = vec![Some(format!("{:02}:{:02}", hour, minute))];
+            if (hour, minute) == default {
+                times.push(None);
+            }
+            DigestSlot { times, timezones }
+        })
+        .collect()
 }
 
-pub async fn send_message_at_specific_time(bot: Bot, cfg: DatabaseManager) {
-    tokio::spawn(async move {
-        // Установите время, в которое необходимо отправить сообщение (например, 00:39 AM)
-        let target_hour = 11;
-        let target_minute = 00;
-
-        loop {
-            let now = Local::now();
-
-            if let Some(naive_target_time) =
-                now.date_naive().and_hms_opt(target_hour, target_minute, 0)
-            {
-                let target_time = Local
-                    .from_local_datetime(&naive_target_time)
-                    .single()
-                    .unwrap();
-
-                // Вычислите продолжительность до наступления target_time
-                let duration_until_target = if now < target_time {
-                    target_time - now
-                } else {
-                    // Если target_time уже прошло, установите его на следующий день
-                    target_time + chrono::Duration::days(1) - now
-                };
+/// Returns true if `now` is `(hour, minute)` in `tz`, or in the server's time zone without one
+pub fn local_time_is(now: DateTime<Utc>, tz: Option<Tz>, at: (u32, u32)) -> bool {
+    let local = match tz {
+        Some(tz) => now.with_timezone(&tz).time(),
+        None => now.with_timezone(&Local).time(),
+    };
+    (local.hour(), local.minute()) == at
+}
+
+/// Time from `now` until the start of the next minute
+pub fn until_next_minute(now: DateTime<Utc>) -> Duration {
+    let elapsed = Duration::new(now.second() as u64, now.nanosecond() % 1_000_000_000);
+    Duration::from_secs(60).saturating_sub(elapsed)
+}
+
+/// Daily digest job, run by the supervisor
+///
+/// Wakes at the start of every minute and sends the digest to the users
+/// whose local digest time it is.
+pub async fn digest_job(broadcaster: Broadcaster) -> JobResult {
+    let default = broadcaster.config.digest_time()?;
 
-                // Ждите, пока не наступит нужное время
-                time::sleep(duration_until_target.to_std().unwrap()).await;
+    loop {
+        time::sleep(until_next_minute(Utc::now())).await;
+        send_all_currency(&broadcaster, Utc::now(), default).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::memory::MemoryStorage;
 
-                send_all_currency(cfg.clone(), bot.clone(), "Hello".to_string()).await;
+    #[tokio::test]
+    async fn test_broadcast_paused_on_shutdown() {
+        let storage = Storage::new(MemoryStorage::new());
+        let shutdown = Shutdown::new();
+        let broadcaster = Broadcaster::new(
+            Bot::new("123:abc"),
+            Arc::new(Config::default()),
+            &storage,
+            shutdown.clone(),
+        );
 
-                // Подождите некоторое время, чтобы избежать повторной отправки сообщения, если цикл выполняется слишком быстро
-                time::sleep(Duration::from_secs(60)).await;
-            } else {
-                log::error!("Некорректное время для отправки сообщений.");
-                break;
+        // Cancelled before the first message, nothing is sent and everyone stays pending
+        shutdown.cancel();
+        let broadcast = Broadcast::new("sendall-1".to_string(), Some("hi".to_string()), vec![1, 2]);
+        broadcaster.start(broadcast).await;
+        assert!(shutdown.drain(Duration::from_secs(5)).await);
+
+        let saved = storage.broadcasts.get_broadcasts().await.unwrap();
+        assert_eq!(saved.len(), 1);
+        assert_eq!(saved[0].pending, vec![1, 2]);
+    }
+
+    #[test]
+    fn test_digest_due() {
+        use chrono::TimeZone;
+
+        let now = Utc.with_ymd_and_hms(2024, 5, 7, 9, 0, 20).unwrap();
+        let mut preferences = Preferences {
+            timezone: Some("UTC".to_string()),
+            ..Preferences::default()
+        };
+        assert!(digest_due(&preferences, now, (9, 0)));
+        assert!(!digest_due(&preferences, now, (11, 0)));
+
+        // 09:00 UTC is 12:00 in Kyiv in summer
+        preferences.timezone = Some("Europe/Kiev".to_string());
+        preferences.digest_time = Some("12:00".to_string());
+        assert!(digest_due(&preferences, now, (9, 0)));
+        preferences.digest_time = Some("09:00".to_string());
+        assert!(!digest_due(&preferences, now, (9, 0)));
+
+        assert_eq!(until_next_minute(now), Duration::from_secs(40));
+    }
+
+    #[test]
+    fn test_digest_slots_match_digest_due() {
+        use chrono::TimeZone;
+
+        let now = Utc.with_ymd_and_hms(2024, 5, 7, 9, 0, 20).unwrap();
+        let slots = digest_slots(now, (12, 0));
+        let times = [None, Some("09:00"), Some("12:00"), Some("13:00")];
+        let timezones = [None, Some("UTC"), Some("Europe/Kiev"), Some("Asia/Kolkata")];
+        for time in times {
+            for timezone in timezones {
+                let preferences = Preferences {
+                    digest_time: time.map(str::to_string),
+                    timezone: timezone.map(str::to_string),
+                    ..Preferences::default()
+                };
+                assert_eq!(
+                    slots.iter().any(|slot| slot.contains(&preferences)),
+                    digest_due(&preferences, now, (12, 0)),
+                    "{:?} {:?}",
+                    time,
+                    timezone
+                );
             }
         }
-    });
+    }
 }
@@ -0,0 +1,815 @@
+use crate::commands::language::language_keyboard;
+use crate::config::Config;
+use crate::i18n::Lang;
+use crate::models::chat::{Chat, ChatSetting, COOLDOWNS};
+use crate::models::preferences::{
+    parse_time, ChartTheme, Expander, Fiat, Preference, Preferences, DIGEST_TIMES, TIMEZONES,
+};
+use crate::storage::{ChatRepository, UserRepository};
+use crate::tools::html;
+use chrono_tz::Tz;
+use log::warn;
+use mongodb::bson;
+use std::sync::Arc;
+use teloxide::types::{InlineKeyboardButton, InlineKeyboardButtonKind, InlineKeyboardMarkup, User};
+
+/// Callback data prefix of the /settings buttons
+pub const SETTINGS_CALLBACK_PREFIX: &str = "settings:";
+
+/// Digest time buttons per row
+const TIMES_PER_ROW: usize = 3;
+/// Time zone buttons per row
+const TIMEZONES_PER_ROW: usize = 2;
+
+/// Page of the /settings menu
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SettingsPage {
+    Main,
+    Time,
+    Timezone,
+    Fiat,
+    Expanders,
+}
+
+impl SettingsPage {
+    const ALL: [SettingsPage; 5] = [
+        SettingsPage::Main,
+        SettingsPage::Time,
+        SettingsPage::Timezone,
+        SettingsPage::Fiat,
+        SettingsPage::Expanders,
+    ];
+
+    /// Code used in callback data
+    fn code(self) -> &'static str {
+        match self {
+            SettingsPage::Main => "main",
+            SettingsPage::Time => "time",
+            SettingsPage::Timezone => "timezone",
+            SettingsPage::Fiat => "fiat",
+            SettingsPage::Expanders => "expanders",
+        }
+    }
+
+    fn from_code(code: &str) -> Option<SettingsPage> {
+        SettingsPage::ALL
+            .into_iter()
+            .find(|page| page.code() == code)
+    }
+}
+
+/// Button pressed in the /settings menu
+///
+/// Buttons carry the value they set, so a double tap or an old menu never
+/// flips a preference back.
+#[derive(Clone, Debug, PartialEq)]
+pub enum SettingsAction {
+    /// Show a page
+    Open(SettingsPage),
+    /// Change a preference and show the page it belongs to
+    Set(Preference),
+    /// Show the /language buttons
+    Language,
+    /// Change a group-only setting and show the main page
+    Chat(ChatSetting),
+}
+
+impl SettingsAction {
+    /// Callback data of the button, e.g. `settings:time:09:00`
+    pub fn data(&self) -> String {
+        let action = match self {
+            SettingsAction::Open(page) => format!("page:{}", page.code()),
+            SettingsAction::Language => "language".to_string(),
+            SettingsAction::Chat(ChatSetting::MentionOnly(on)) => {
+                format!("mention:{}", on_off(*on))
+            }
+            SettingsAction::Chat(ChatSetting::Cooldown(secs)) => match secs {
+                Some(secs) => format!("cooldown:{}", secs),
+                None => "cooldown:default".to_string(),
+            },
+            SettingsAction::Set(preference) => match preference {
+                Preference::Digest(on) => format!("digest:{}", on_off(*on)),
+                Preference::DigestTime(time) => {
+                    format!("time:{}", time.as_deref().unwrap_or("default"))
+                }
+                Preference::Timezone(timezone) => {
+                    format!("tz:{}", timezone.as_deref().unwrap_or("default"))
+                }
+                Preference::Fiat(fiat) => format!("fiat:{}", fiat.code()),
+                Preference::ChartTheme(theme) => format!("theme:{}", theme.code()),
+                Preference::Expander(expander, on) => {
+                    format!("expand:{}:{}", expander.code(), on_off(*on))
+                }
+            },
+        };
+        format!("{}{}", SETTINGS_CALLBACK_PREFIX, action)
+    }
+
+    /// Action of a callback data, `None` if it is not a valid /settings button
+    pub fn parse(data: &str) -> Option<SettingsAction> {
+        let rest = data.strip_prefix(SETTINGS_CALLBACK_PREFIX)?;
+        if rest == "language" {
+            return Some(SettingsAction::Language);
+        }
+        let (action, value) = rest.split_once(':')?;
+        let preference = match action {
+            "page" => return SettingsPage::from_code(value).map(SettingsAction::Open),
+            "mention" => {
+                let on = parse_on_off(value)?;
+                return Some(SettingsAction::Chat(ChatSetting::MentionOnly(on)));
+            }
+            "cooldown" if value == "default" => {
+                return Some(SettingsAction::Chat(ChatSetting::Cooldown(None)))
+            }
+            "cooldown" => {
+                let secs = value.parse().ok().filter(|secs| COOLDOWNS.contains(secs))?;
+                return Some(SettingsAction::Chat(ChatSetting::Cooldown(Some(secs))));
+            }
+            "digest" => Preference::Digest(parse_on_off(value)?),
+            "time" if value == "default" => Preference::DigestTime(None),
+            "time" => {
+                let (hour, minute) = parse_time(value)?;
+                Preference::DigestTime(Some(format!("{:02}:{:02}", hour, minute)))
+            }
+            "tz" if value == "default" => Preference::Timezone(None),
+            "tz" => Preference::Timezone(Some(value.parse::<Tz>().ok()?.name().to_string())),
+            "fiat" => Preference::Fiat(Fiat::from_code(value)?),
+            "theme" => Preference::ChartTheme(ChartTheme::from_code(value)?),
+            "expand" => {
+                let (expander, on) = value.split_once(':')?;
+                Preference::Expander(Expander::from_code(expander)?, parse_on_off(on)?)
+            }
+            _ => return None,
+        };
+        Some(SettingsAction::Set(preference))
+    }
+}
+
+fn on_off(on: bool) -> &'static str {
+    if on {
+        "on"
+    } else {
+        "off"
+    }
+}
+
+fn parse_on_off(value: &str) -> Option<bool> {
+    match value {
+        "on" => Some(true),
+        "off" => Some(false),
+        _ => None,
+    }
+}
+
+/// Page shown after a change, the expanders page stays open for the next toggle
+fn page_after(preference: &Preference) -> SettingsPage {
+    match preference {
+        Preference::Expander(..) => SettingsPage::Expanders,
+        _ => SettingsPage::Main,
+    }
+}
+
+/// Preferences of a Telegram user, the defaults for unknown users
+///
+/// # Arguments
+///
+/// * `db` - User storage
+/// * `user` - Sender of the message or callback
+///
+/// # Returns
+///
+/// * `Preferences` - Stored preferences, users are not created
+pub async fn user_preferences(db: &Arc<dyn UserRepository>, user: Option<&User>) -> Preferences {
+    match user {
+        Some(user) => stored_preferences(db, user.id.0 as i64).await,
+        None => Preferences::default(),
+    }
+}
+
+async fn stored_preferences(db: &Arc<dyn UserRepository>, user_id: i64) -> Preferences {
+    match db.get_preferences(user_id).await {
+        Ok(preferences) => preferences.unwrap_or_default(),
+        Err(err) => {
+            warn!("Error getting preferences: {}", err);
+            Preferences::default()
+        }
+    }
+}
+
+/// Text of a /settings page
+///
+/// # Arguments
+///
+/// * `page` - Shown page
+/// * `preferences` - Preferences of the user
+/// * `lang` - Language of the menu
+/// * `config` - Bot configuration, for the default digest time
+///
+/// # Returns
+///
+/// * `String` - Summary of every preference on the main page, a prompt on the others
+pub fn settings_text(
+    page: SettingsPage,
+    preferences: &Preferences,
+    lang: Lang,
+    config: &Config,
+) -> String {
+    match page {
+        SettingsPage::Main => {}
+        SettingsPage::Time => return lang.tr("settings-choose-time"),
+        SettingsPage::Timezone => return lang.tr("settings-choose-timezone"),
+        SettingsPage::Fiat => return lang.tr("settings-choose-fiat"),
+        SettingsPage::Expanders => return lang.tr("settings-choose-expanders"),
+    }
+
+    let [digest, fiat, theme, expanders] = summary(preferences, lang, config);
+    [
+        lang.tr("settings-title"),
+        digest,
+        fiat,
+        lang.tr_with("settings-language", &[("language", lang.name().into())]),
+        theme,
+        expanders,
+    ]
+    .join("\n")
+}
+
+/// Digest, fiat, chart theme and expanders lines of the main page
+fn summary(preferences: &Preferences, lang: Lang, config: &Config) -> [String; 4] {
+    let digest = if preferences.digest {
+        let time = preferences
+            .digest_time
+            .clone()
+            .unwrap_or_else(|| config.schedule.digest_time.clone());
+        let timezone = preferences
+            .timezone
+            .clone()
+            .unwrap_or_else(|| lang.tr("settings-server-time"));
+        lang.tr_with(
+            "settings-digest-on",
+            &[("time", time.into()), ("timezone", timezone.into())],
+        )
+    } else {
+        lang.tr("settings-digest-off")
+    };
+    let expanders: Vec<String> = Expander::ALL
+        .into_iter()
+        .filter(|expander| preferences.expanders.get(*expander))
+        .map(|expander| lang.tr(&format!("expander-{}", expander.code())))
+        .collect();
+    let expanders = if expanders.is_empty() {
+        lang.tr("settings-none")
+    } else {
+        expanders.join(", ")
+    };
+
+    [
+        digest,
+        lang.tr_with("settings-fiat", &[("fiat", preferences.fiat.code().into())]),
+        lang.tr_with(
+            "settings-theme",
+            &[(
+                "theme",
+                lang.tr(&format!("theme-{}", preferences.chart_theme.code()))
+                    .into(),
+            )],
+        ),
+        lang.tr_with("settings-expanders", &[("expanders", expanders.into())]),
+    ]
+}
+
+fn button(text: String, action: SettingsAction) -> InlineKeyboardButton {
+    InlineKeyboardButton::callback(text, action.data())
+}
+
+fn set(text: String, preference: Preference) -> InlineKeyboardButton {
+    button(text, SettingsAction::Set(preference))
+}
+
+/// Marks the current option of a sub-page
+fn mark(label: &str, current: bool) -> String {
+    if current {
+        format!("✅ {}", label)
+    } else {
+        label.to_string()
+    }
+}
+
+fn back(lang: Lang) -> Vec<InlineKeyboardButton> {
+    vec![button(
+        lang.tr("settings-button-back"),
+        SettingsAction::Open(SettingsPage::Main),
+    )]
+}
+
+/// Buttons of a /settings page
+///
+/// # Arguments
+///
+/// * `page` - Shown page
+/// * `preferences` - Preferences of the user, the current options are marked
+/// * `lang` - Language of the buttons
+///
+/// # Returns
+///
+/// * `InlineKeyboardMarkup` - Buttons setting explicit values, sub-pages end
+///   with Back
+pub fn settings_keyboard(
+    page: SettingsPage,
+    preferences: &Preferences,
+    lang: Lang,
+) -> InlineKeyboardMarkup {
+    let mut rows: Vec<Vec<InlineKeyboardButton>> = vec![];
+    match page {
+        SettingsPage::Main => {
+            let digest = if preferences.digest {
+                set(
+                    lang.tr("settings-button-digest-off"),
+                    Preference::Digest(false),
+                )
+            } else {
+                set(
+                    lang.tr("settings-button-digest-on"),
+                    Preference::Digest(true),
+                )
+            };
+            let theme = match preferences.chart_theme {
+                ChartTheme::Light => set(
+                    lang.tr("settings-button-theme-dark"),
+                    Preference::ChartTheme(ChartTheme::Dark),
+                ),
+                ChartTheme::Dark => set(
+                    lang.tr("settings-button-theme-light"),
+                    Preference::ChartTheme(ChartTheme::Light),
+                ),
+            };
+            rows.push(vec![digest]);
+            rows.push(vec![
+                button(
+                    lang.tr("settings-button-time"),
+                    SettingsAction::Open(SettingsPage::Time),
+                ),
+                button(
+                    lang.tr("settings-button-timezone"),
+                    SettingsAction::Open(SettingsPage::Timezone),
+                ),
+            ]);
+            rows.push(vec![
+                button(
+                    lang.tr("settings-button-fiat"),
+                    SettingsAction::Open(SettingsPage::Fiat),
+                ),
+                button(
+                    lang.tr("settings-button-language"),
+                    SettingsAction::Language,
+                ),
+            ]);
+            rows.push(vec![theme]);
+            rows.push(vec![button(
+                lang.tr("settings-button-expanders"),
+                SettingsAction::Open(SettingsPage::Expanders),
+            )]);
+        }
+        SettingsPage::Time => {
+            let current = preferences.digest_time.as_deref();
+            rows.extend(DIGEST_TIMES.chunks(TIMES_PER_ROW).map(|chunk| {
+                chunk
+                    .iter()
+                    .map(|time| {
+                        set(
+                            mark(time, current == Some(*time)),
+                            Preference::DigestTime(Some(time.to_string())),
+                        )
+                    })
+                    .collect()
+            }));
+            rows.push(vec![set(
+                mark(&lang.tr("settings-default"), current.is_none()),
+                Preference::DigestTime(None),
+            )]);
+            rows.push(back(lang));
+        }
+        SettingsPage::Timezone => {
+            let current = preferences.timezone.as_deref();
+            rows.extend(TIMEZONES.chunks(TIMEZONES_PER_ROW).map(|chunk| {
+                chunk
+                    .iter()
+                    .map(|timezone| {
+                        set(
+                            mark(timezone, current == Some(*timezone)),
+                            Preference::Timezone(Some(timezone.to_string())),
+                        )
+                    })
+                    .collect()
+            }));
+            rows.push(vec![set(
+                mark(&lang.tr("settings-server-time"), current.is_none()),
+                Preference::Timezone(None),
+            )]);
+            rows.push(back(lang));
+        }
+        SettingsPage::Fiat => {
+            rows.push(
+                Fiat::ALL
+                    .into_iter()
+                    .map(|fiat| {
+                        let label = format!("{} {}", fiat.sign(), fiat.code());
+                        set(
+                            mark(&label, preferences.fiat == fiat),
+                            Preference::Fiat(fiat),
+                        )
+                    })
+                    .collect(),
+            );
+            rows.push(back(lang));
+        }
+        SettingsPage::Expanders => {
+            rows.extend(Expander::ALL.into_iter().map(|expander| {
+                let on = preferences.expanders.get(expander);
+                let label = lang.tr(&format!("expander-{}", expander.code()));
+                let label = if on {
+                    format!("✅ {}", label)
+                } else {
+                    format!("▫️ {}", label)
+                };
+                vec![set(label, Preference::Expander(expander, !on))]
+            }));
+            rows.push(back(lang));
+        }
+    }
+    InlineKeyboardMarkup::new(rows)
+}
+
+/// /settings command handler
+///
+/// # Arguments
+///
+/// * `user` - Sender of the command
+/// * `lang` - Language of the menu
+/// * `db` - User storage
+/// * `config` - Bot configuration
+///
+/// # Returns
+///
+/// * `(String, InlineKeyboardMarkup)` - Main page of the menu
+pub async fn settings_command(
+    user: Option<&User>,
+    lang: Lang,
+    db: &Arc<dyn UserRepository>,
+    config: &Config,
+) -> (String, InlineKeyboardMarkup) {
+    let preferences = user_preferences(db, user).await;
+    (
+        settings_text(SettingsPage::Main, &preferences, lang, config),
+        settings_keyboard(SettingsPage::Main, &preferences, lang),
+    )
+}
+
+/// /settings button handler, the menu message is edited to the returned page
+///
+/// # Arguments
+///
+/// * `user_id` - User id
+/// * `action` - Pressed button
+/// * `lang` - Language of the menu
+/// * `db` - User storage
+/// * `config` - Bot configuration
+///
+/// # Returns
+///
+/// * `(String, InlineKeyboardMarkup)` - Page to show, the error on the main
+///   page if the change was not saved
+pub async fn settings_callback(
+    user_id: i64,
+    action: SettingsAction,
+    lang: Lang,
+    db: &Arc<dyn UserRepository>,
+    config: &Config,
+) -> (String, InlineKeyboardMarkup) {
+    let preference = match action {
+        SettingsAction::Language => return (lang.tr("language-choose"), language_keyboard()),
+        SettingsAction::Open(page) => {
+            let preferences = stored_preferences(db, user_id).await;
+            return (
+                settings_text(page, &preferences, lang, config),
+                settings_keyboard(page, &preferences, lang),
+            );
+        }
+        SettingsAction::Set(preference) => preference,
+        // Group buttons only come with group menus
+        SettingsAction::Chat(_) => {
+            let preferences = stored_preferences(db, user_id).await;
+            return (
+                settings_text(SettingsPage::Main, &preferences, lang, config),
+                settings_keyboard(SettingsPage::Main, &preferences, lang),
+            );
+        }
+    };
+
+    let mut preferences = stored_preferences(db, user_id).await;
+    if let Err(err) = db.set_preference(user_id, preference.clone()).await {
+        return (
+            html::escape(&err.to_string()),
+            settings_keyboard(SettingsPage::Main, &preferences, lang),
+        );
+    }
+    preferences.apply(&preference);
+    let page = page_after(&preference);
+    (
+        settings_text(page, &preferences, lang, config),
+        settings_keyboard(page, &preferences, lang),
+    )
+}
+
+/// Text of a group /settings page, the main page adds the group-only settings
+///
+/// # Arguments
+///
+/// * `page` - Shown page
+/// * `chat` - Settings of the group
+/// * `lang` - Language of the menu
+/// * `config` - Bot configuration, for the default digest time and cooldown
+///
+/// # Returns
+///
+/// * `String` - Summary of every setting on the main page, a prompt on the others
+pub fn group_settings_text(page: SettingsPage, chat: &Chat, lang: Lang, config: &Config) -> String {
+    if page != SettingsPage::Main {
+        return settings_text(page, &chat.preferences, lang, config);
+    }
+
+    let [digest, fiat, theme, expanders] = summary(&chat.preferences, lang, config);
+    let mention = if chat.mention_only {
+        lang.tr("settings-mention-on")
+    } else {
+        lang.tr("settings-mention-off")
+    };
+    let cooldown = cooldown_label(chat.cooldown(config.group_cooldown()).as_secs(), lang);
+    [
+        lang.tr_with(
+            "settings-group-title",
+            &[("title", chat.title.clone().into())],
+        ),
+        digest,
+        fiat,
+        theme,
+        expanders,
+        mention,
+        lang.tr_with("settings-cooldown", &[("cooldown", cooldown.into())]),
+    ]
+    .join("\n")
+}
+
+/// Cooldown as shown in the group menu
+fn cooldown_label(secs: u64, lang: Lang) -> String {
+    if secs == 0 {
+        lang.tr("settings-cooldown-off")
+    } else {
+        lang.tr_with("settings-cooldown-secs", &[("secs", secs.into())])
+    }
+}
+
+/// Buttons of a group /settings page
+///
+/// The main page has no Language button, group answers are in the language
+/// of the sender, and ends with the mention-only toggle and the cooldowns.
+///
+/// # Arguments
+///
+/// * `page` - Shown page
+/// * `chat` - Settings of the group, the current options are marked
+/// * `lang` - Language of the buttons
+///
+/// # Returns
+///
+/// * `InlineKeyboardMarkup` - Buttons setting explicit values
+pub fn group_settings_keyboard(
+    page: SettingsPage,
+    chat: &Chat,
+    lang: Lang,
+) -> InlineKeyboardMarkup {
+    let keyboard = settings_keyboard(page, &chat.preferences, lang);
+    if page != SettingsPage::Main {
+        return keyboard;
+    }
+
+    let language = SettingsAction::Language.data();
+    let mut rows: Vec<Vec<InlineKeyboardButton>> = keyboard
+        .inline_keyboard
+        .into_iter()
+        .map(|row| {
+            row.into_iter()
+                .filter(|button| {
+                    !matches!(&button.kind, InlineKeyboardButtonKind::CallbackData(data) if *data == language)
+                })
+                .collect()
+        })
+        .collect();
+    let mention = if chat.mention_only {
+        lang.tr("settings-button-mention-off")
+    } else {
+        lang.tr("settings-button-mention-on")
+    };
+    rows.push(vec![button(
+        mention,
+        SettingsAction::Chat(ChatSetting::MentionOnly(!chat.mention_only)),
+    )]);
+    let mut cooldowns: Vec<InlineKeyboardButton> = COOLDOWNS
+        .into_iter()
+        .map(|secs| {
+            button(
+                mark(
+                    &cooldown_label(secs, lang),
+                    chat.cooldown_secs == Some(secs),
+                ),
+                SettingsAction::Chat(ChatSetting::Cooldown(Some(secs))),
+            )
+        })
+        .collect();
+    cooldowns.push(button(
+        mark(&lang.tr("settings-default"), chat.cooldown_secs.is_none()),
+        SettingsAction::Chat(ChatSetting::Cooldown(None)),
+    ));
+    rows.push(cooldowns);
+    InlineKeyboardMarkup::new(rows)
+}
+
+/// Group /settings button handler, the caller checks that the sender is an admin
+///
+/// # Arguments
+///
+/// * `chat` - Settings of the group, changed in place
+/// * `action` - Pressed button
+/// * `lang` - Language of the menu
+/// * `chats` - Chat storage
+/// * `config` - Bot configuration
+///
+/// # Returns
+///
+/// * `(String, InlineKeyboardMarkup)` - Page to show, the error on the main
+///   page if the change was not saved
+pub async fn group_settings_callback(
+    chat: &mut Chat,
+    action: SettingsAction,
+    lang: Lang,
+    chats: &Arc<dyn ChatRepository>,
+    config: &Config,
+) -> (String, InlineKeyboardMarkup) {
+    let page = match action {
+        SettingsAction::Open(page) => {
+            return (
+                group_settings_text(page, chat, lang, config),
+                group_settings_keyboard(page, chat, lang),
+            )
+        }
+        SettingsAction::Language => SettingsPage::Main,
+        SettingsAction::Set(preference) => {
+            chat.preferences.apply(&preference);
+            page_after(&preference)
+        }
+        SettingsAction::Chat(setting) => {
+            chat.apply(&setting);
+            SettingsPage::Main
+        }
+    };
+
+    chat.updated_at = bson::DateTime::now();
+    if let Err(err) = chats.save_chat(chat).await {
+        return (
+            html::escape(&err.to_string()),
+            group_settings_keyboard(SettingsPage::Main, chat, lang),
+        );
+    }
+    (
+        group_settings_text(page, chat, lang, config),
+        group_settings_keyboard(page, chat, lang),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_settings_action_data() {
+        let actions = vec![
+            SettingsAction::Open(SettingsPage::Timezone),
+            SettingsAction::Language,
+            SettingsAction::Set(Preference::Digest(false)),
+            SettingsAction::Set(Preference::DigestTime(Some("09:00".to_string()))),
+            SettingsAction::Set(Preference::DigestTime(None)),
+            SettingsAction::Set(Preference::Timezone(Some("Europe/Kiev".to_string()))),
+            SettingsAction::Set(Preference::Timezone(None)),
+            SettingsAction::Set(Preference::Fiat(Fiat::Eur)),
+            SettingsAction::Set(Preference::ChartTheme(ChartTheme::Dark)),
+            SettingsAction::Set(Preference::Expander(Expander::Twitter, false)),
+            SettingsAction::Chat(ChatSetting::MentionOnly(true)),
+            SettingsAction::Chat(ChatSetting::Cooldown(Some(60))),
+            SettingsAction::Chat(ChatSetting::Cooldown(None)),
+        ];
+        for action in actions {
+            let data = action.data();
+            assert!(data.len() <= 64, "{} is too long for Telegram", data);
+            assert_eq!(SettingsAction::parse(&data), Some(action));
+        }
+
+        assert_eq!(
+            SettingsAction::parse("settings:time:9:00"),
+            Some(SettingsAction::Set(Preference::DigestTime(Some(
+                "09:00".to_string()
+            ))))
+        );
+        assert_eq!(SettingsAction::parse("settings:time:25:00"), None);
+        assert_eq!(SettingsAction::parse("settings:tz:Mars/Olympus"), None);
+        assert_eq!(SettingsAction::parse("settings:digest:toggle"), None);
+        assert_eq!(SettingsAction::parse("settings:expand:eden"), None);
+        assert_eq!(SettingsAction::parse("settings:cooldown:45"), None);
+        assert_eq!(SettingsAction::parse("lang:en"), None);
+    }
+
+    #[test]
+    fn test_settings_keyboard() {
+        let mut preferences = Preferences::default();
+        let keyboard = settings_keyboard(SettingsPage::Main, &preferences, Lang::En);
+        assert_eq!(
+            keyboard.inline_keyboard[0][0].text,
+            Lang::En.tr("settings-button-digest-on")
+        );
+
+        preferences.digest_time = Some("18:00".to_string());
+        let keyboard = settings_keyboard(SettingsPage::Time, &preferences, Lang::En);
+        let marked: Vec<&str> = keyboard
+            .inline_keyboard
+            .iter()
+            .flatten()
+            .map(|button| button.text.as_str())
+            .filter(|text| text.starts_with('✅'))
+            .collect();
+        assert_eq!(marked, vec!["✅ 18:00"]);
+
+        preferences.expanders.set(Expander::Eden, false);
+        let text = settings_text(
+            SettingsPage::Main,
+            &preferences,
+            Lang::En,
+            &Config::default(),
+        );
+        assert!(text.ends_with("coin amounts, Twitter links"));
+        let keyboard = settings_keyboard(SettingsPage::Expanders, &preferences, Lang::En);
+        let action = match &keyboard.inline_keyboard[2][0].kind {
+            InlineKeyboardButtonKind::CallbackData(data) => SettingsAction::parse(data),
+            _ => None,
+        };
+        assert_eq!(
+            action,
+            Some(SettingsAction::Set(Preference::Expander(
+                Expander::Eden,
+                true
+            )))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_group_settings() {
+        use crate::storage::memory::MemoryStorage;
+        use crate::storage::Storage;
+
+        let storage = Storage::new(MemoryStorage::new());
+        let config = Config::default();
+        let mut chat = Chat::new(-1001, "Traders".to_string());
+
+        let keyboard = group_settings_keyboard(SettingsPage::Main, &chat, Lang::En);
+        let actions: Vec<SettingsAction> = keyboard
+            .inline_keyboard
+            .iter()
+            .flatten()
+            .filter_map(|button| match &button.kind {
+                InlineKeyboardButtonKind::CallbackData(data) => SettingsAction::parse(data),
+                _ => None,
+            })
+            .collect();
+        assert!(!actions.contains(&SettingsAction::Language));
+        assert!(actions.contains(&SettingsAction::Chat(ChatSetting::MentionOnly(true))));
+
+        let (text, keyboard) = group_settings_callback(
+            &mut chat,
+            SettingsAction::Chat(ChatSetting::Cooldown(Some(0))),
+            Lang::En,
+            &storage.chats,
+            &config,
+        )
+        .await;
+        assert!(text.contains("Pause between answers: off"));
+        let marked: Vec<&str> = keyboard
+            .inline_keyboard
+            .iter()
+            .flatten()
+            .map(|button| button.text.as_str())
+            .filter(|text| text.starts_with('✅'))
+            .collect();
+        assert_eq!(marked, vec!["✅ off"]);
+
+        let stored = storage.chats.get_chat(-1001).await.unwrap().unwrap();
+        assert_eq!(stored.cooldown_secs, Some(0));
+    }
+}
@@ -1,31 +1,62 @@
-use crate::db::DatabaseManager;
+use crate::i18n::Lang;
 use crate::models::user::User;
-use log::debug;
-use teloxide::prelude::Message;
+use crate::storage::UserRepository;
+use log::{debug, error};
+use std::sync::Arc;
 
 /// Start command
 ///
+/// New users get the language of their Telegram client, existing users keep
+/// the one they picked with /language.
+///
 /// # Arguments
 ///
-/// * `msg` - Message
+/// * `user_id` - User id
+/// * `username` - User username
+/// * `language_code` - Telegram `language_code` of the user
+/// * `cfg` - User storage
 ///
 /// # Returns
 ///
 /// * `String` - Response message
-pub async fn start_command(msg: Message, cfg: DatabaseManager) -> String {
-    let res = User::new(
-        msg.from().unwrap().id.0 as i64,
-        msg.from()
-            .unwrap()
-            .username
-            .clone()
-            .unwrap_or("".to_string()),
-        vec![],
-    );
-    let save = cfg.insert_user(res).await;
-    match save {
-        Ok(_) => (),
-        Err(err) => debug!("user already exists: {}", err),
+pub async fn start_command(
+    user_id: i64,
+    username: String,
+    language_code: Option<&str>,
+    cfg: Arc<dyn UserRepository>,
+) -> String {
+    let detected = Lang::detect(language_code);
+    let mut user = User::new(user_id, username, vec![]);
+    user.preferences.language = Some(detected);
+
+    let save = cfg.insert_user(user).await;
+    let lang = match save {
+        Ok(true) => detected,
+        Ok(false) => {
+            debug!("user already exists");
+            stored_language(user_id, detected, cfg).await
+        }
+        Err(err) => {
+            error!("Error saving user: {}", err);
+            detected
+        }
+    };
+    lang.tr("start-greeting")
+}
+
+// language of an existing user, users from before /language get the detected one
+async fn stored_language(user_id: i64, detected: Lang, cfg: Arc<dyn UserRepository>) -> Lang {
+    match cfg.get_language(user_id).await {
+        Ok(Some(lang)) => lang,
+        Ok(None) => {
+            if let Err(err) = cfg.set_language(user_id, detected).await {
+                error!("Error saving language: {}", err);
+            }
+            detected
+        }
+        Err(err) => {
+            error!("Error getting language: {}", err);
+            detected
+        }
     }
-    "Hello with start".to_string()
 }
@@ -1,13 +1,37 @@
+use crate::i18n::Lang;
+use crate::models::alert::Alert;
+use crate::models::asset::Asset;
+use crate::models::broadcast::Broadcast;
+use crate::models::channel::ChannelPost;
+use crate::models::chat::Chat;
+use crate::models::cmc_ids;
+use crate::models::dialogue::StoredDialogue;
+use crate::models::preferences::{Preference, Preferences, USER_SCHEMA_VERSION};
+use crate::models::quota::QuotaUsage;
 use crate::models::user::User;
+use crate::storage::{
+    AlertRepository, AssetRepository, BroadcastRepository, ChannelRepository, ChatRepository,
+    DialogueRepository, QuotaRepository, StorageResult, UserFilter, UserRepository,
+};
+use async_trait::async_trait;
 use futures::stream::StreamExt;
-use log::debug;
-use mongodb::bson::Document;
+use log::{debug, warn};
+use mongodb::bson::{Document, Regex};
+use mongodb::error::{ErrorKind, WriteFailure};
+use mongodb::options::{
+    FindOneAndUpdateOptions, FindOptions, IndexOptions, ReplaceOptions, ReturnDocument,
+    UpdateOptions,
+};
 use mongodb::{
     bson::{doc, Bson},
     options::ClientOptions,
-    Client, Collection, Database,
+    Client, Collection, Database, IndexModel,
 };
 use std::error::Error;
+use std::time::Duration;
+
+/// Upper bound of the delay between connection attempts
+const MAX_CONNECT_BACKOFF: Duration = Duration::from_secs(30);
 
 /// Database manager
 ///
@@ -18,14 +42,32 @@ use std::error::Error;
 /// # Methods
 ///
 /// * `new` - Create new database manager
-/// * `insert_user` - Insert user to database
+/// * `create_indexes` - Create indexes, called once at startup
+/// * `migrate_users` - Bring user documents to `USER_SCHEMA_VERSION`, called once at startup
+/// * `migrate_chats` - Turn the chat `cmc_ids` into lists, called once at startup
 ///
-
+/// Implements the storage traits on top of MongoDB.
+/// Watchlist and notification changes are single field-level updates, so
+/// concurrent commands from the same user never overwrite each other.
 #[derive(Clone)]
 pub struct DatabaseManager {
     db: Database,
 }
 
+/// MongoDB error code of a unique index violation
+const DUPLICATE_KEY_CODE: i32 = 11000;
+
+/// Returns true if the error is a unique index violation
+fn is_duplicate_key(err: &mongodb::error::Error) -> bool {
+    match err.kind.as_ref() {
+        ErrorKind::Write(WriteFailure::WriteError(write_error)) => {
+            write_error.code == DUPLICATE_KEY_CODE
+        }
+        ErrorKind::Command(command_error) => command_error.code == DUPLICATE_KEY_CODE,
+        _ => false,
+    }
+}
+
 impl DatabaseManager {
     pub async fn new(uri: &str, db_name: &str) -> Result<Self, Box<dyn Error>> {
         let client_options = ClientOptions::parse(uri).await?;
@@ -35,41 +77,327 @@ impl DatabaseManager {
         Ok(Self { db })
     }
 
-    pub async fn insert_user(&self, user: User) -> Result<(), Box<dyn Error>> {
-        let collection = self.db.collection("user");
+    /// Connect and ping the server, retrying with exponential backoff
+    ///
+    /// The driver connects lazily, so without the ping a wrong password or an
+    /// unreachable host only shows up on the first query.
+    ///
+    /// # Arguments
+    ///
+    /// * `uri` - MongoDB connection string
+    /// * `db_name` - Database name
+    /// * `retries` - Attempts after the first one before giving up
+    pub async fn connect(uri: &str, db_name: &str, retries: u32) -> Result<Self, Box<dyn Error>> {
+        let mut delay = Duration::from_secs(1);
+        let mut attempt = 0;
+        loop {
+            let result = match Self::new(uri, db_name).await {
+                Ok(manager) => match manager.ping().await {
+                    Ok(()) => Ok(manager),
+                    Err(err) => Err(err as Box<dyn Error>),
+                },
+                Err(err) => Err(err),
+            };
+            match result {
+                Ok(manager) => return Ok(manager),
+                Err(err) if attempt < retries => {
+                    attempt += 1;
+                    warn!(
+                        "MongoDB connection failed ({}), retry {}/{} in {:?}",
+                        err, attempt, retries, delay
+                    );
+                    tokio::time::sleep(delay).await;
+                    delay = (delay * 2).min(MAX_CONNECT_BACKOFF);
+                }
+                Err(err) => return Err(err),
+            }
+        }
+    }
+
+    /// Create the unique indexes on `user_id`, the dialogue and chat `chat_id`, the alert
+    /// and channel post `id` and the quota usage `provider` and `day`
+    pub async fn create_indexes(&self) -> Result<(), Box<dyn Error>> {
+        let unique = |keys: Document| {
+            IndexModel::builder()
+                .keys(keys)
+                .options(IndexOptions::builder().unique(true).build())
+                .build()
+        };
+
+        let collection: Collection<User> = self.db.collection("user");
+        collection
+            .create_index(unique(doc! {"user_id": 1}), None)
+            .await?;
+        let collection: Collection<StoredDialogue> = self.db.collection("dialogue");
+        collection
+            .create_index(unique(doc! {"chat_id": 1}), None)
+            .await?;
+        let collection: Collection<Alert> = self.db.collection("alert");
+        collection
+            .create_index(unique(doc! {"id": 1}), None)
+            .await?;
+        let collection: Collection<Chat> = self.db.collection("chat");
+        collection
+            .create_index(unique(doc! {"chat_id": 1}), None)
+            .await?;
+        let collection: Collection<ChannelPost> = self.db.collection("channel_post");
+        collection
+            .create_index(unique(doc! {"id": 1}), None)
+            .await?;
+        let collection: Collection<QuotaUsage> = self.db.collection("quota_usage");
+        collection
+            .create_index(unique(doc! {"provider": 1, "day": 1}), None)
+            .await?;
 
-        let user_doc = self.update_user_doc(user).await;
+        Ok(())
+    }
+
+    /// Bring user documents to `USER_SCHEMA_VERSION`
+    ///
+    /// Version 2 moves the flat `notification` and `language` fields into the
+    /// `preferences` sub-document, version 3 turns the `cmc_ids` sub-document
+    /// into a list. A step only matches documents of older versions, so an
+    /// interrupted migration carries on at the next start.
+    ///
+    /// # Returns
+    ///
+    /// * `Result<u64, Box<dyn Error>>` - Number of migrated documents
+    pub async fn migrate_users(&self) -> Result<u64, Box<dyn Error>> {
+        let collection: Collection<Document> = self.db.collection("user");
+        let to_v2 = vec![
+            doc! {"$set": {
+                "preferences.digest": {"$ifNull": ["$notification", false]},
+                "preferences.language": "$language",
+                "schema_version": 2,
+            }},
+            doc! {"$unset": ["notification", "language"]},
+        ];
+        let result = collection
+            .update_many(doc! {"schema_version": {"$not": {"$gte": 2}}}, to_v2, None)
+            .await?;
+        let mut migrated = result.modified_count;
+
+        let to_v3 = vec![doc! {"$set": {
+            "cmc_ids": cmc_ids_list(),
+            "schema_version": 3,
+        }}];
+        let result = collection
+            .update_many(doc! {"schema_version": {"$not": {"$gte": 3}}}, to_v3, None)
+            .await?;
+        migrated += result.modified_count;
+
+        Ok(migrated)
+    }
 
-        let res = collection.insert_one(user_doc, None).await;
-        // info!("Inserted user: {:?}", res.inserted_id);
+    /// Turn the `cmc_ids` sub-documents of the chats into lists, like version 3
+    /// of the users
+    ///
+    /// # Returns
+    ///
+    /// * `Result<u64, Box<dyn Error>>` - Number of migrated documents
+    pub async fn migrate_chats(&self) -> Result<u64, Box<dyn Error>> {
+        let collection: Collection<Document> = self.db.collection("chat");
+        let query = doc! {"$expr": {"$eq": [{"$type": "$cmc_ids"}, "object"]}};
+        let update = vec![doc! {"$set": {"cmc_ids": cmc_ids_list()}}];
+        let result = collection.update_many(query, update, None).await?;
+        Ok(result.modified_count)
+    }
 
-        match res {
-            Ok(_) => (),
-            Err(err) => debug!("user already exists: {}", err),
+    // fields of a freshly created user, used with `$setOnInsert`;
+    // `currency`, `updated_at` and `preferences` are left to the update
+    // operators, missing preferences read as the defaults
+    fn new_user_fields(&self) -> Document {
+        doc! {
+            "username": "",
+            "created_at": mongodb::bson::DateTime::now(),
+            "schema_version": USER_SCHEMA_VERSION,
         }
+    }
 
-        Ok(())
+    // update user document
+    async fn update_user_doc(&self, user: User) -> StorageResult<Document> {
+        Ok(doc! {
+            "user_id": user.user_id,
+            "username": user.username,
+            "currency": Bson::Array(user.currency.into_iter().map(Bson::String).collect()),
+            "cmc_ids": cmc_ids::to_bson(&user.cmc_ids),
+            "created_at": user.created_at,
+            "updated_at": mongodb::bson::DateTime::now(),
+            "preferences": mongodb::bson::to_bson(&user.preferences)?,
+            "schema_version": user.schema_version,
+        })
     }
+}
 
-    pub async fn get_user(&self, user_id: i64) -> Option<User> {
-        let collection: Collection<User> = self.db.collection("user");
-        let user_query = collection.find_one(doc! {"user_id": user_id}, None).await;
+/// Aggregation expression turning a `{symbol: id}` sub-document into the list
+/// of `models::cmc_ids`, dotted symbols were stored as nested documents and
+/// are dropped
+fn cmc_ids_list() -> Document {
+    doc! {"$map": {
+        "input": {"$filter": {
+            "input": {"$objectToArray": {"$ifNull": ["$cmc_ids", {}]}},
+            "cond": {"$isNumber": "$$this.v"},
+        }},
+        "in": {"symbol": "$$this.k", "cmc_id": {"$toLong": "$$this.v"}},
+    }}
+}
 
-        match user_query {
-            Ok(Some(user)) => {
-                // debug!("user Ok: {:?}", user);
-                Some(user)
-            }
-            Ok(None) => {
-                let usr = User::new(user_id, "".to_string(), vec![]);
-                match usr.save(self.clone()).await {
-                    Ok(_) => Some(usr),
-                    Err(err) => {
-                        debug!("user save error: {:?}", err);
-                        None
-                    }
+/// Query of a watchlist owner that still takes `currency`, a full list only
+/// takes the coins it already has
+fn capped(mut query: Document, currency: &str, max: usize) -> Document {
+    query.insert(
+        "$or",
+        vec![
+            doc! {"currency": currency},
+            doc! {"$expr": {"$lt": [{"$size": {"$ifNull": ["$currency", []]}}, max as i64]}},
+        ],
+    );
+    query
+}
+
+/// Update pipeline adding `currency` to a watchlist, one id per symbol: a new
+/// pick replaces the old one in the same update
+fn watchlist_add(currency: &str, cmc_id: Option<i64>) -> Vec<Document> {
+    // Symbols are user input, `$literal` keeps a leading `$` from reading as a field path
+    let symbol = doc! {"$literal": currency};
+    let list = doc! {"$ifNull": ["$currency", []]};
+    let mut set = doc! {
+        "currency": {"$cond": [
+            {"$in": [symbol.clone(), list.clone()]},
+            list.clone(),
+            {"$concatArrays": [list, [symbol.clone()]]},
+        ]},
+        "updated_at": mongodb::bson::DateTime::now(),
+    };
+    if let Some(cmc_id) = cmc_id {
+        set.insert(
+            "cmc_ids",
+            doc! {"$concatArrays": [
+                {"$filter": {
+                    "input": {"$ifNull": ["$cmc_ids", []]},
+                    "cond": {"$ne": ["$$this.symbol", symbol]},
+                }},
+                [{"$literal": cmc_ids::entry(currency, cmc_id)}],
+            ]},
+        );
+    }
+    vec![doc! {"$set": set}]
+}
+
+/// `$set` and `$setOnInsert` documents of a chat upsert
+///
+/// # Arguments
+///
+/// * `chat` - Chat to store
+/// * `set` - Fields always written, the others only on insert
+/// * `skip` - Fields changed by other operators of the update
+///
+fn chat_update(chat: &Chat, set: &[&str], skip: &[&str]) -> StorageResult<(Document, Document)> {
+    let mut on_insert = mongodb::bson::to_document(chat)?;
+    on_insert.remove("chat_id");
+    let mut fields = Document::new();
+    for key in set {
+        if let Some(value) = on_insert.remove(*key) {
+            fields.insert(*key, value);
+        }
+    }
+    for key in skip {
+        on_insert.remove(*key);
+    }
+    Ok((fields, on_insert))
+}
+
+/// Query of the users matching the filter
+fn user_query(filter: &UserFilter) -> Document {
+    let mut query = doc! {};
+    if let Some(digest) = filter.digest {
+        query.insert("preferences.digest", digest);
+    }
+    if filter.with_currency {
+        query.insert("currency.0", doc! {"$exists": true});
+    }
+    if let Some(slots) = &filter.digest_slots {
+        // `$in` with `null` also matches the users without the field
+        let slots: Vec<Document> = slots
+            .iter()
+            .map(|slot| {
+                doc! {
+                    "preferences.digest_time": {"$in": slot.times.clone()},
+                    "preferences.timezone": {"$in": slot.timezones.clone()},
                 }
+            })
+            .collect();
+        if slots.is_empty() {
+            // An empty `$or` is an error, match nobody instead
+            query.insert("$expr", false);
+        } else {
+            query.insert("$or", slots);
+        }
+    }
+    query
+}
+
+/// Document path and value of a preference change
+fn preference_field(preference: &Preference) -> (String, Bson) {
+    let optional = |value: &Option<String>| match value {
+        Some(value) => Bson::String(value.clone()),
+        None => Bson::Null,
+    };
+    match preference {
+        Preference::Digest(on) => ("preferences.digest".to_string(), Bson::Boolean(*on)),
+        Preference::DigestTime(time) => ("preferences.digest_time".to_string(), optional(time)),
+        Preference::Timezone(timezone) => ("preferences.timezone".to_string(), optional(timezone)),
+        Preference::Fiat(fiat) => ("preferences.fiat".to_string(), fiat.code().into()),
+        Preference::ChartTheme(theme) => {
+            ("preferences.chart_theme".to_string(), theme.code().into())
+        }
+        Preference::Expander(expander, on) => (
+            format!("preferences.expanders.{}", expander.code()),
+            Bson::Boolean(*on),
+        ),
+    }
+}
+
+#[async_trait]
+impl UserRepository for DatabaseManager {
+    /// Insert user to database
+    ///
+    /// Returns `Ok(false)` if the user already exists, any other failure is an error.
+    async fn insert_user(&self, user: User) -> StorageResult<bool> {
+        let collection = self.db.collection("user");
+
+        let user_doc = self.update_user_doc(user).await?;
+
+        match collection.insert_one(user_doc, None).await {
+            Ok(_) => Ok(true),
+            Err(err) if is_duplicate_key(&err) => {
+                debug!("user already exists: {}", err);
+                Ok(false)
             }
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    /// Get user, creating an empty one on first access
+    async fn get_user(&self, user_id: i64) -> Option<User> {
+        let collection: Collection<User> = self.db.collection("user");
+        let options = FindOneAndUpdateOptions::builder()
+            .upsert(true)
+            .return_document(ReturnDocument::After)
+            .build();
+        let mut fields = self.new_user_fields();
+        fields.insert("currency", Bson::Array(vec![]));
+        fields.insert("updated_at", mongodb::bson::DateTime::now());
+        let user_query = collection
+            .find_one_and_update(
+                doc! {"user_id": user_id},
+                doc! {"$setOnInsert": fields},
+                options,
+            )
+            .await;
+
+        match user_query {
+            Ok(user) => user,
             Err(err) => {
                 debug!("get user error: {:?}", err);
                 None
@@ -77,78 +405,71 @@ impl DatabaseManager {
         }
     }
 
-    // change user currency
-    pub async fn change_user_currency(
+    // change user currency, `cmc_id` is the id resolved through the asset registry
+    async fn change_user_currency(
         &self,
         user_id: i64,
         currency: String,
-    ) -> Result<(), Box<dyn Error>> {
+        cmc_id: Option<i64>,
+        max: usize,
+    ) -> StorageResult<bool> {
         let collection: Collection<User> = self.db.collection("user");
-        let user_query = self.get_user(user_id).await;
-        let mut user = match user_query {
-            Some(user) => user,
-            None => return Err("Problem with user saving".into()),
-        };
-        user.currency.push(currency);
-        let mut unique_currency: Vec<String> = Vec::new();
-        for c in user.currency.into_iter() {
-            if !unique_currency.contains(&c) {
-                unique_currency.push(c);
-            }
-        }
-        user.currency = unique_currency;
-        let user_doc = self.update_user_doc(user).await;
+        // Insert the user first, the capped update below can't upsert: a full
+        // list would not match and get a second document
+        let mut fields = self.new_user_fields();
+        fields.insert("currency", Bson::Array(vec![]));
+        fields.insert("updated_at", mongodb::bson::DateTime::now());
         collection
-            .update_one(doc! {"user_id": user_id}, doc! {"$set": user_doc}, None)
+            .update_one(
+                doc! {"user_id": user_id},
+                doc! {"$setOnInsert": fields},
+                UpdateOptions::builder().upsert(true).build(),
+            )
             .await?;
-        //debug!("Updated user: {:?}", res.modified_count);
-        Ok(())
+
+        let update = watchlist_add(&currency, cmc_id);
+
+        let result = collection
+            .update_one(
+                capped(doc! {"user_id": user_id}, &currency, max),
+                update,
+                None,
+            )
+            .await?;
+
+        Ok(result.matched_count > 0)
     }
 
-    pub async fn remove_user_currency(
+    async fn remove_user_currency(
         &self,
         user_id: i64,
         currency_to_remove: String,
-    ) -> Result<(), Box<dyn Error>> {
+    ) -> StorageResult<()> {
         let collection: Collection<User> = self.db.collection("user");
-        let user_query = self.get_user(user_id).await;
 
-        let mut user = match user_query {
-            Some(user) => user,
-            None => return Err("Problem with user saving".into()),
+        // Older documents stored symbols as typed, so match them case-insensitively
+        let pattern = Regex {
+            pattern: format!("^{}$", regex::escape(&currency_to_remove)),
+            options: "i".to_string(),
+        };
+        let update = doc! {
+            "$pull": {"currency": pattern.clone(), "cmc_ids": {"symbol": pattern}},
+            "$set": {"updated_at": mongodb::bson::DateTime::now()},
         };
 
-        // Отфильтровать список валют, удаляя указанную валюту
-        user.currency
-            .retain(|currency| currency != &currency_to_remove);
-
-        // Обновить документ с новым списком валют
-        let user_doc = self.update_user_doc(user).await;
-
-        collection
-            .update_one(doc! {"user_id": user_id}, doc! {"$set": user_doc}, None)
+        let result = collection
+            .update_one(doc! {"user_id": user_id}, update, None)
             .await?;
+        if result.matched_count == 0 {
+            return Err("user not found".into());
+        }
 
         Ok(())
     }
-    // update user document
-    async fn update_user_doc(&self, user: User) -> Document {
-        doc! {
-            "user_id": user.user_id,
-            "username": user.username,
-            "currency": Bson::Array(user.currency.into_iter().map(Bson::String).collect()),
-            "created_at": user.created_at,
-            "updated_at": mongodb::bson::DateTime::now(),
-            "notification": user.notification,
-        }
-    }
 
-    pub async fn get_all_users(
-        &self,
-        filter: Option<Document>,
-    ) -> Result<Vec<User>, Box<dyn Error>> {
+    async fn get_all_users(&self, filter: UserFilter) -> StorageResult<Vec<User>> {
         let collection: Collection<User> = self.db.collection("user");
-        let mut cursor = collection.find(filter, None).await?;
+        let mut cursor = collection.find(user_query(&filter), None).await?;
         let mut users_vec: Vec<User> = Vec::new();
         while let Some(result) = cursor.next().await {
             let user = result?;
@@ -158,27 +479,380 @@ impl DatabaseManager {
         Ok(users_vec)
     }
 
-    pub async fn change_notify(&self, mut user: User) -> Result<String, Box<dyn Error>> {
+    async fn count_users(&self, filter: UserFilter) -> StorageResult<u64> {
+        let collection: Collection<User> = self.db.collection("user");
+        Ok(collection
+            .count_documents(user_query(&filter), None)
+            .await?)
+    }
+
+    async fn get_preferences(&self, user_id: i64) -> StorageResult<Option<Preferences>> {
         let collection: Collection<User> = self.db.collection("user");
-        user.notification = match user.notification {
-            true => false,
-            false => true,
+        let user = collection.find_one(doc! {"user_id": user_id}, None).await?;
+        Ok(user.map(|user| user.preferences))
+    }
+
+    /// Set one preference field in place
+    async fn set_preference(&self, user_id: i64, preference: Preference) -> StorageResult<()> {
+        let collection: Collection<User> = self.db.collection("user");
+        let (path, value) = preference_field(&preference);
+        let mut fields = self.new_user_fields();
+        fields.insert("currency", Bson::Array(vec![]));
+        let update = doc! {
+            "$set": {path: value, "updated_at": mongodb::bson::DateTime::now()},
+            "$setOnInsert": fields,
         };
-        let user_id = user.user_id;
-        let user_doc = self.update_user_doc(user.clone()).await;
-        let update_result = collection
-            .update_one(doc! {"user_id": user_id}, doc! {"$set": user_doc}, None)
+
+        collection
+            .update_one(
+                doc! {"user_id": user_id},
+                update,
+                UpdateOptions::builder().upsert(true).build(),
+            )
             .await?;
 
-        if update_result.modified_count > 0 {
-            let message = if user.notification {
-                "successfully turned on"
-            } else {
-                "successfully turned off"
-            };
-            Ok(message.to_string())
-        } else {
-            Err("user not found".into())
+        Ok(())
+    }
+
+    async fn get_language(&self, user_id: i64) -> StorageResult<Option<Lang>> {
+        let collection: Collection<User> = self.db.collection("user");
+        let user = collection.find_one(doc! {"user_id": user_id}, None).await?;
+        Ok(user.and_then(|user| user.preferences.language))
+    }
+
+    async fn set_language(&self, user_id: i64, language: Lang) -> StorageResult<()> {
+        let collection: Collection<User> = self.db.collection("user");
+        let mut fields = self.new_user_fields();
+        fields.insert("currency", Bson::Array(vec![]));
+        let update = doc! {
+            "$set": {
+                "preferences.language": language.code(),
+                "updated_at": mongodb::bson::DateTime::now(),
+            },
+            "$setOnInsert": fields,
+        };
+
+        collection
+            .update_one(
+                doc! {"user_id": user_id},
+                update,
+                UpdateOptions::builder().upsert(true).build(),
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    /// Check that the server is reachable and the credentials are accepted
+    async fn ping(&self) -> StorageResult<()> {
+        self.db.run_command(doc! {"ping": 1}, None).await?;
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl AssetRepository for DatabaseManager {
+    /// Replace the cached asset registry with a fresh CoinMarketCap map
+    async fn replace_assets(&self, assets: Vec<Asset>) -> StorageResult<()> {
+        let collection: Collection<Asset> = self.db.collection("asset");
+        collection.delete_many(doc! {}, None).await?;
+        if !assets.is_empty() {
+            collection.insert_many(assets, None).await?;
+        }
+        Ok(())
+    }
+
+    /// Get all cached assets
+    async fn get_all_assets(&self) -> StorageResult<Vec<Asset>> {
+        let collection: Collection<Asset> = self.db.collection("asset");
+        let mut cursor = collection.find(None, None).await?;
+        let mut assets_vec: Vec<Asset> = Vec::new();
+        while let Some(result) = cursor.next().await {
+            assets_vec.push(result?);
+        }
+
+        Ok(assets_vec)
+    }
+}
+
+#[async_trait]
+impl BroadcastRepository for DatabaseManager {
+    /// Upsert the broadcast by id
+    async fn save_broadcast(&self, broadcast: &Broadcast) -> StorageResult<()> {
+        let collection: Collection<Broadcast> = self.db.collection("broadcast");
+        let options = ReplaceOptions::builder().upsert(true).build();
+        collection
+            .replace_one(doc! {"id": &broadcast.id}, broadcast, options)
+            .await?;
+        Ok(())
+    }
+
+    /// Get all unfinished broadcasts
+    async fn get_broadcasts(&self) -> StorageResult<Vec<Broadcast>> {
+        let collection: Collection<Broadcast> = self.db.collection("broadcast");
+        let mut cursor = collection.find(None, None).await?;
+        let mut broadcasts: Vec<Broadcast> = Vec::new();
+        while let Some(result) = cursor.next().await {
+            broadcasts.push(result?);
+        }
+
+        Ok(broadcasts)
+    }
+
+    /// Remove a finished broadcast
+    async fn delete_broadcast(&self, id: &str) -> StorageResult<()> {
+        let collection: Collection<Broadcast> = self.db.collection("broadcast");
+        collection.delete_one(doc! {"id": id}, None).await?;
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl DialogueRepository for DatabaseManager {
+    /// Get the dialogue of a chat
+    async fn get_dialogue(&self, chat_id: i64) -> StorageResult<Option<StoredDialogue>> {
+        let collection: Collection<StoredDialogue> = self.db.collection("dialogue");
+        Ok(collection.find_one(doc! {"chat_id": chat_id}, None).await?)
+    }
+
+    /// Upsert the dialogue by chat id
+    async fn save_dialogue(&self, dialogue: &StoredDialogue) -> StorageResult<()> {
+        let collection: Collection<StoredDialogue> = self.db.collection("dialogue");
+        let options = ReplaceOptions::builder().upsert(true).build();
+        collection
+            .replace_one(doc! {"chat_id": dialogue.chat_id}, dialogue, options)
+            .await?;
+        Ok(())
+    }
+
+    /// Remove the dialogue of a chat
+    async fn delete_dialogue(&self, chat_id: i64) -> StorageResult<()> {
+        let collection: Collection<StoredDialogue> = self.db.collection("dialogue");
+        collection
+            .delete_one(doc! {"chat_id": chat_id}, None)
+            .await?;
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl AlertRepository for DatabaseManager {
+    /// Insert an alert
+    async fn add_alert(&self, alert: &Alert) -> StorageResult<()> {
+        let collection: Collection<Alert> = self.db.collection("alert");
+        collection.insert_one(alert, None).await?;
+        Ok(())
+    }
+
+    /// Get every alert
+    async fn get_alerts(&self) -> StorageResult<Vec<Alert>> {
+        let collection: Collection<Alert> = self.db.collection("alert");
+        let mut cursor = collection.find(None, None).await?;
+        let mut alerts: Vec<Alert> = Vec::new();
+        while let Some(result) = cursor.next().await {
+            alerts.push(result?);
+        }
+
+        Ok(alerts)
+    }
+
+    /// Get the alerts of a user, oldest first
+    async fn get_user_alerts(&self, user_id: i64) -> StorageResult<Vec<Alert>> {
+        let collection: Collection<Alert> = self.db.collection("alert");
+        let options = FindOptions::builder().sort(doc! {"created_at": 1}).build();
+        let mut cursor = collection.find(doc! {"user_id": user_id}, options).await?;
+        let mut alerts: Vec<Alert> = Vec::new();
+        while let Some(result) = cursor.next().await {
+            alerts.push(result?);
+        }
+
+        Ok(alerts)
+    }
+
+    /// Remove an alert
+    async fn delete_alert(&self, id: &str) -> StorageResult<bool> {
+        let collection: Collection<Alert> = self.db.collection("alert");
+        let result = collection.delete_one(doc! {"id": id}, None).await?;
+        Ok(result.deleted_count > 0)
+    }
+}
+
+#[async_trait]
+impl ChatRepository for DatabaseManager {
+    /// Get a group chat
+    async fn get_chat(&self, chat_id: i64) -> StorageResult<Option<Chat>> {
+        let collection: Collection<Chat> = self.db.collection("chat");
+        Ok(collection.find_one(doc! {"chat_id": chat_id}, None).await?)
+    }
+
+    /// Upsert the title and settings by chat id, the watchlist is only
+    /// written on insert
+    async fn save_chat(&self, chat: &Chat) -> StorageResult<()> {
+        let collection: Collection<Chat> = self.db.collection("chat");
+        let (set, on_insert) = chat_update(
+            chat,
+            &[
+                "title",
+                "preferences",
+                "mention_only",
+                "cooldown_secs",
+                "updated_at",
+            ],
+            &[],
+        )?;
+        collection
+            .update_one(
+                doc! {"chat_id": chat.chat_id},
+                doc! {"$set": set, "$setOnInsert": on_insert},
+                UpdateOptions::builder().upsert(true).build(),
+            )
+            .await?;
+        Ok(())
+    }
+
+    /// Add currency to the watchlist with one pipeline update, the same way as
+    /// `change_user_currency`
+    async fn add_chat_currency(
+        &self,
+        chat: &Chat,
+        currency: String,
+        cmc_id: Option<i64>,
+        max: usize,
+    ) -> StorageResult<bool> {
+        let collection: Collection<Chat> = self.db.collection("chat");
+        // Insert the chat first, as for `change_user_currency`
+        let (mut set, mut on_insert) =
+            chat_update(chat, &["title"], &["currency", "cmc_ids", "updated_at"])?;
+        set.insert("updated_at", mongodb::bson::DateTime::now());
+        on_insert.insert("currency", Bson::Array(vec![]));
+        on_insert.insert("cmc_ids", Bson::Array(vec![]));
+        collection
+            .update_one(
+                doc! {"chat_id": chat.chat_id},
+                doc! {"$set": set, "$setOnInsert": on_insert},
+                UpdateOptions::builder().upsert(true).build(),
+            )
+            .await?;
+
+        let update = watchlist_add(&currency, cmc_id);
+        let result = collection
+            .update_one(
+                capped(doc! {"chat_id": chat.chat_id}, &currency, max),
+                update,
+                None,
+            )
+            .await?;
+        Ok(result.matched_count > 0)
+    }
+
+    /// Remove currency from the watchlist with `$pull`
+    async fn remove_chat_currency(&self, chat_id: i64, currency: String) -> StorageResult<()> {
+        let collection: Collection<Chat> = self.db.collection("chat");
+        let pattern = Regex {
+            pattern: format!("^{}$", regex::escape(&currency)),
+            options: "i".to_string(),
+        };
+        let update = doc! {
+            "$pull": {"currency": pattern.clone(), "cmc_ids": {"symbol": pattern}},
+            "$set": {"updated_at": mongodb::bson::DateTime::now()},
+        };
+        collection
+            .update_one(doc! {"chat_id": chat_id}, update, None)
+            .await?;
+        Ok(())
+    }
+
+    /// Get the chats with the digest on and at least one currency
+    async fn get_digest_chats(&self) -> StorageResult<Vec<Chat>> {
+        let collection: Collection<Chat> = self.db.collection("chat");
+        let query = doc! {"preferences.digest": true, "currency.0": {"$exists": true}};
+        let mut cursor = collection.find(query, None).await?;
+        let mut chats: Vec<Chat> = Vec::new();
+        while let Some(result) = cursor.next().await {
+            chats.push(result?);
+        }
+
+        Ok(chats)
+    }
+}
+
+#[async_trait]
+impl ChannelRepository for DatabaseManager {
+    /// Insert a schedule
+    async fn add_channel_post(&self, post: &ChannelPost) -> StorageResult<()> {
+        let collection: Collection<ChannelPost> = self.db.collection("channel_post");
+        collection.insert_one(post, None).await?;
+        Ok(())
+    }
+
+    /// Get every schedule, oldest first
+    async fn get_channel_posts(&self) -> StorageResult<Vec<ChannelPost>> {
+        let collection: Collection<ChannelPost> = self.db.collection("channel_post");
+        let options = FindOptions::builder().sort(doc! {"created_at": 1}).build();
+        let mut cursor = collection.find(None, options).await?;
+        let mut posts: Vec<ChannelPost> = Vec::new();
+        while let Some(result) = cursor.next().await {
+            posts.push(result?);
         }
+
+        Ok(posts)
+    }
+
+    /// Remove a schedule
+    async fn delete_channel_post(&self, id: &str) -> StorageResult<bool> {
+        let collection: Collection<ChannelPost> = self.db.collection("channel_post");
+        let result = collection.delete_one(doc! {"id": id}, None).await?;
+        Ok(result.deleted_count > 0)
+    }
+
+    /// Set `last_message_id` of a schedule
+    async fn set_last_message(&self, id: &str, message_id: i32) -> StorageResult<()> {
+        let collection: Collection<ChannelPost> = self.db.collection("channel_post");
+        collection
+            .update_one(
+                doc! {"id": id},
+                doc! {"$set": {"last_message_id": message_id}},
+                None,
+            )
+            .await?;
+        Ok(())
+    }
+
+    /// Set `last_posted_at` of a schedule
+    async fn set_last_posted(&self, id: &str, at: mongodb::bson::DateTime) -> StorageResult<()> {
+        let collection: Collection<ChannelPost> = self.db.collection("channel_post");
+        collection
+            .update_one(doc! {"id": id}, doc! {"$set": {"last_posted_at": at}}, None)
+            .await?;
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl QuotaRepository for DatabaseManager {
+    /// Increment the usage document of the provider and day, creating it if needed
+    async fn add_usage(&self, usage: &QuotaUsage) -> StorageResult<()> {
+        let collection: Collection<QuotaUsage> = self.db.collection("quota_usage");
+        let options = UpdateOptions::builder().upsert(true).build();
+        collection
+            .update_one(
+                doc! {"provider": &usage.provider, "day": &usage.day},
+                doc! {"$inc": {"calls": usage.calls, "credits": usage.credits}},
+                options,
+            )
+            .await?;
+        Ok(())
+    }
+
+    /// Get the usage documents since a day
+    async fn get_usage(&self, since: &str) -> StorageResult<Vec<QuotaUsage>> {
+        let collection: Collection<QuotaUsage> = self.db.collection("quota_usage");
+        let mut cursor = collection.find(doc! {"day": {"$gte": since}}, None).await?;
+        let mut usage: Vec<QuotaUsage> = Vec::new();
+        while let Some(result) = cursor.next().await {
+            usage.push(result?);
+        }
+
+        Ok(usage)
     }
 }
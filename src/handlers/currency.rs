@@ -1,18 +1,122 @@
 use crate::commands::notify::notify_command;
 use crate::commands::{
-    chart::chart_command,
-    currency::{add_currency_command, remove_currency_command},
+    alert::{
+        alert_command, alert_job, alert_price_step, alert_symbol_step, alerts_command,
+        remove_alert_command, AlertReply,
+    },
+    channel::{
+        add_channel_command, channel_job, channels_command, post_channel_command,
+        remove_channel_command,
+    },
+    chart::{chart_button_command, chart_command, ChartRequest},
+    currency::{
+        add_currency_command, remove_currency_command, select_asset_command, AddCurrencyReply,
+        ASSET_CALLBACK_PREFIX,
+    },
+    group::{is_group, Cooldowns},
+    inline::{inline_command, InlineCache},
+    keyboards::{
+        cancel_keyboard, is_watched, price_all_keyboard, price_keyboard, remove_keyboard,
+        DialogueAction, PriceAction, PriceAllAction,
+    },
+    language::{language_keyboard, set_language_command, user_language, LANGUAGE_CALLBACK_PREFIX},
     price::price_command,
     price_all::price_all_command,
-    send_all::{send_all_command, send_message_at_specific_time},
+    send_all::{digest_job, send_all_command, Broadcaster},
+    settings::{
+        settings_callback, settings_command, user_preferences, SettingsAction,
+        SETTINGS_CALLBACK_PREFIX,
+    },
     start::start_command,
 };
-use crate::db::DatabaseManager;
+use crate::config::Config;
+use crate::handlers::group::{
+    addressed_group_chat, group_button, group_commands_handler, group_messages_handler,
+    is_group_button, private_only_handler,
+};
+use crate::handlers::rate_limit::{
+    limit_button, limit_inline, limit_message, refuse_inline, slow_down_button, slow_down_message,
+    unban_command,
+};
+use crate::handlers::webhook::webhook_listener;
+use crate::i18n::Lang;
+use crate::models::dialogue::DialogueState;
+use crate::storage::dialogue::{BotDialogue, DialogueStorage};
+use crate::storage::{AlertRepository, ChannelRepository, Storage, UserRepository};
+use crate::tools::asset_registry::{refresh_assets_job, AssetRegistry};
+use crate::tools::html;
+use crate::tools::metrics::metrics;
 use crate::tools::parse_text::parse_text;
-use teloxide::{prelude::*, types::Update, utils::command::BotCommands};
+use crate::tools::quota::{quota_job, quotas};
+use crate::tools::rate_limit::RateLimiter;
+use crate::tools::shutdown::{wait_for_signal, Shutdown};
+use crate::tools::supervisor::Supervisor;
+use log::{info, warn};
+use std::sync::Arc;
+use std::time::Duration;
+use teloxide::{
+    prelude::*,
+    types::{BotCommand, BotCommandScope, Me, ParseMode, Recipient, Update},
+    utils::command::BotCommands,
+    ApiError, RequestError,
+};
+
+pub async fn register_currency_handlers(
+    bot: Bot,
+    storage: Storage,
+    registry: AssetRegistry,
+    config: Arc<Config>,
+    shutdown: Shutdown,
+) {
+    let db = storage.users.clone();
+    let alerts = storage.alerts.clone();
+    let chats = storage.chats.clone();
+    let channels = storage.channels.clone();
+    let quota_usage = storage.quotas.clone();
+    let dialogues = DialogueStorage::new(storage.dialogues.clone(), config.dialogue_timeout());
+    let broadcaster = Broadcaster::new(bot.clone(), config.clone(), &storage, shutdown.clone());
 
-pub async fn register_currency_handlers(bot: Bot, db: DatabaseManager) {
-    let handler = Update::filter_message()
+    let admin_handler = dptree::entry()
+        .filter_command::<AdminCommand>()
+        // Admin commands are only parsed for users listed in `admin_ids`
+        .filter(|msg: Message, config: Arc<Config>| {
+            msg.from()
+                .map(|user| config.is_admin(user.id.0))
+                .unwrap_or(false)
+        })
+        .branch(
+            dptree::filter(|cmd: AdminCommand| cmd.is_channel()).endpoint(channel_commands_handler),
+        )
+        .branch(
+            dptree::filter(|cmd: AdminCommand| matches!(cmd, AdminCommand::Unban(_)))
+                .endpoint(unban_handler),
+        )
+        .endpoint(admin_commands_handler);
+
+    // Groups have their own watchlist and settings and no dialogues, every
+    // member would answer the question of a dialogue kept per chat
+    let group_handler = dptree::filter(|msg: Message| is_group(&msg.chat))
+        .branch(
+            dptree::entry()
+                .filter_command::<SimpleCommand>()
+                .branch(
+                    dptree::filter(|cmd: SimpleCommand| cmd.is_group())
+                        .endpoint(group_commands_handler),
+                )
+                .branch(
+                    dptree::filter(|cmd: SimpleCommand| {
+                        cmd.is_dialogue() || matches!(cmd, SimpleCommand::Cancel)
+                    })
+                    .endpoint(private_only_handler),
+                )
+                .endpoint(simple_commands_handler),
+        )
+        .branch(admin_handler.clone())
+        .branch(dptree::filter_map_async(addressed_group_chat).endpoint(group_messages_handler));
+
+    let dialogue_handler = dptree::entry()
+        // The handlers below receive the `BotDialogue` of the chat and its `DialogueState`
+        .enter_dialogue::<Message, DialogueStorage, DialogueState>()
         // You can use branching to define multiple ways in which an update will be handled. If the
         // first branch fails, an update will be passed to the second branch, and so on.
         .branch(
@@ -20,26 +124,130 @@ pub async fn register_currency_handlers(bot: Bot, db: DatabaseManager) {
                 // Filter commands: the next handlers will receive a parsed `SimpleCommand`.
                 .filter_command::<SimpleCommand>()
                 // If a command parsing fails, this handler will not be executed.
+                .branch(
+                    dptree::filter(|cmd: SimpleCommand| matches!(cmd, SimpleCommand::Cancel))
+                        .endpoint(cancel_handler),
+                )
+                // Any other command leaves the open dialogue
+                .inspect_async(|dialogue: BotDialogue, state: DialogueState| async move {
+                    if state.is_active() {
+                        update_dialogue(&dialogue, DialogueState::Idle).await;
+                    }
+                })
+                .branch(
+                    dptree::filter(|cmd: SimpleCommand| cmd.is_dialogue())
+                        .endpoint(dialogue_commands_handler),
+                )
                 .endpoint(simple_commands_handler),
         )
+        .branch(admin_handler)
+        // Answers to the question of the open dialogue
+        .branch(dptree::case![DialogueState::AddCurrency].endpoint(add_currency_answer))
+        .branch(dptree::case![DialogueState::AlertSymbol].endpoint(alert_symbol_answer))
+        .branch(dptree::case![DialogueState::AlertPrice { symbol }].endpoint(alert_price_answer))
+        .branch(dptree::entry().endpoint(messages_handler));
+
+    // Updates over the rate limit stop here, with one polite answer
+    let message_handler = Update::filter_message()
+        .branch(dptree::filter_map(limit_message).endpoint(slow_down_message))
+        .branch(group_handler)
+        .branch(dialogue_handler);
+
+    let callback_query_handler = Update::filter_callback_query()
+        .branch(dptree::filter_map(limit_button).endpoint(slow_down_button))
+        .branch(dptree::filter(|q: CallbackQuery| is_group_button(&q)).endpoint(group_button))
         .branch(
-            dptree::entry()
-                .filter_command::<AdminCommand>()
-                .endpoint(admin_commands_handler),
+            dptree::filter_map(|q: CallbackQuery| {
+                q.data.as_deref().and_then(DialogueAction::parse)
+            })
+            .endpoint(dialogue_button),
         )
-        .branch(dptree::entry().endpoint(messages_handler));
+        .endpoint(callback_handler);
+
+    let handler = dptree::entry()
+        .branch(message_handler)
+        .branch(callback_query_handler)
+        .branch(
+            Update::filter_inline_query()
+                .filter(|config: Arc<Config>| config.features.inline_mode)
+                .endpoint(inline_query_handler),
+        );
 
-    bot.set_my_commands(SimpleCommand::bot_commands())
+    // The default menu is English, Telegram picks the others by the client language
+    bot.set_my_commands(commands_for(Lang::default()))
         .await
         .expect("failed setting commands");
+    for lang in Lang::ALL {
+        bot.set_my_commands(commands_for(lang))
+            .language_code(lang.code())
+            .await
+            .expect("failed setting commands");
+    }
+
+    broadcaster.resume().await;
+
+    // Budgets carry over restarts
+    if let Err(err) = quotas().load(quota_usage.as_ref()).await {
+        warn!("Error loading the quota usage: {}", err);
+    }
+
+    let supervisor = Supervisor::new(bot.clone(), config.clone(), shutdown.clone());
+    if config.features.daily_digest {
+        let digest = broadcaster.clone();
+        supervisor.spawn("daily_digest", move || digest_job(digest.clone()));
+    }
+    let (assets, assets_config) = (registry.clone(), config.clone());
+    supervisor.spawn("asset_refresh", move || {
+        refresh_assets_job(assets.clone(), assets_config.clone())
+    });
+    let (alert_bot, alert_repo, alert_users, alert_config) =
+        (bot.clone(), alerts.clone(), db.clone(), config.clone());
+    supervisor.spawn("price_alerts", move || {
+        alert_job(
+            alert_bot.clone(),
+            alert_repo.clone(),
+            alert_users.clone(),
+            alert_config.clone(),
+        )
+    });
+
+    let quota_repo = quota_usage.clone();
+    supervisor.spawn("quota_usage", move || quota_job(quota_repo.clone()));
+
+    if config.features.channel_posts {
+        let (channel_bot, channel_repo, channel_config) =
+            (bot.clone(), channels.clone(), config.clone());
+        supervisor.spawn("channel_posts", move || {
+            channel_job(
+                channel_bot.clone(),
+                channel_repo.clone(),
+                channel_config.clone(),
+            )
+        });
+    }
 
-    send_message_at_specific_time(bot.clone(), db.clone()).await;
+    let webhook = config.webhook.clone();
+    let inline_cache = InlineCache::new(Duration::from_secs(config.inline.cache_secs));
 
-    Dispatcher::builder(bot, handler)
+    let mut dispatcher = Dispatcher::builder(bot.clone(), handler)
         // Here you specify initial dependencies that all handlers will receive; they can be
         // database connections, configurations, and other auxiliary arguments. It is similar to
         // `actix_web::Extensions`.
-        .dependencies(dptree::deps![db])
+        .dependencies(dptree::deps![
+            db,
+            alerts,
+            chats,
+            channels,
+            dialogues,
+            registry,
+            config.clone(),
+            broadcaster,
+            supervisor,
+            inline_cache,
+            Cooldowns::new(),
+            RateLimiter::new(),
+            shutdown.clone()
+        ])
         // If no handler succeeded to handle an update, this closure will be called.
         .default_handler(|upd| async move {
             log::warn!("Unhandled update: {:?}", upd);
@@ -48,15 +256,47 @@ pub async fn register_currency_handlers(bot: Bot, db: DatabaseManager) {
         .error_handler(LoggingErrorHandler::with_custom_text(
             "An error has occurred in the dispatcher",
         ))
-        .enable_ctrlc_handler()
-        .build()
-        .dispatch()
-        .await;
+        .build();
+
+    // Ctrl-C or SIGTERM stops new updates, running charts and broadcasts are drained below
+    let dispatcher_token = dispatcher.shutdown_token();
+    let signal_shutdown = shutdown.clone();
+    tokio::spawn(async move {
+        wait_for_signal().await;
+        info!("Shutting down...");
+        signal_shutdown.cancel();
+        match dispatcher_token.shutdown() {
+            Ok(stopped) => stopped.await,
+            Err(err) => warn!("Error stopping the dispatcher: {}", err),
+        }
+    });
+
+    // Long polling unless a public webhook URL is configured
+    if webhook.enabled() {
+        let listener = webhook_listener(bot, &webhook)
+            .await
+            .expect("failed setting up the webhook");
+        dispatcher
+            .dispatch_with_listener(
+                listener,
+                LoggingErrorHandler::with_custom_text("An error from the webhook listener"),
+            )
+            .await;
+    } else {
+        dispatcher.dispatch().await;
+    }
+
+    shutdown.cancel();
+    shutdown.drain(config.shutdown_timeout()).await;
+    if let Err(err) = quotas().flush(quota_usage.as_ref()).await {
+        warn!("Error saving the quota usage: {}", err);
+    }
+    info!("Bye");
 }
 
 #[derive(BotCommands, Clone)]
 #[command(rename_rule = "lowercase", description = "Simple commands")]
-enum SimpleCommand {
+pub(super) enum SimpleCommand {
     #[command(description = "shows this message.")]
     Help,
     #[command(description = "register user")]
@@ -65,86 +305,441 @@ enum SimpleCommand {
     Chart(String),
     #[command(description = "handle a price")]
     Price(String),
-    #[command(description = "add currency")]
+    #[command(description = "add currencies, e.g. /addcurrency btc eth sol")]
     AddCurrency(String),
     #[command(description = "remove currency")]
     RemoveCurrency(String),
     #[command(description = "print all user currencies")]
     PriceAll,
-    #[command(description = "enable/disable notify about currencies")]
-    Notify,
+    #[command(description = "turn the daily digest on or off, e.g. /notify off")]
+    Notify(String),
+    #[command(description = "choose the bot language")]
+    Language(String),
+    #[command(description = "digest, time zone, currency and other settings")]
+    Settings,
+    #[command(description = "notify me when a coin reaches a price, e.g. /alert btc 70000")]
+    Alert(String),
+    #[command(description = "list and remove price alerts")]
+    Alerts,
+    #[command(description = "cancel the current question")]
+    Cancel,
+}
+
+impl SimpleCommand {
+    /// Command name used as metrics label
+    pub(super) fn name(&self) -> &'static str {
+        match self {
+            SimpleCommand::Help => "help",
+            SimpleCommand::Start => "start",
+            SimpleCommand::Chart(_) => "chart",
+            SimpleCommand::Price(_) => "price",
+            SimpleCommand::AddCurrency(_) => "addcurrency",
+            SimpleCommand::RemoveCurrency(_) => "removecurrency",
+            SimpleCommand::PriceAll => "priceall",
+            SimpleCommand::Notify(_) => "notify",
+            SimpleCommand::Language(_) => "language",
+            SimpleCommand::Settings => "settings",
+            SimpleCommand::Alert(_) => "alert",
+            SimpleCommand::Alerts => "alerts",
+            SimpleCommand::Cancel => "cancel",
+        }
+    }
+
+    /// Commands answered by `dialogue_commands_handler`, they ask a question
+    /// or need the alert storage
+    fn is_dialogue(&self) -> bool {
+        match self {
+            SimpleCommand::AddCurrency(args) => args.trim().is_empty(),
+            SimpleCommand::Alert(_) | SimpleCommand::Alerts => true,
+            _ => false,
+        }
+    }
+
+    /// Commands answered by `group_commands_handler` in groups, they work
+    /// with the group watchlist and settings
+    fn is_group(&self) -> bool {
+        matches!(
+            self,
+            SimpleCommand::AddCurrency(_)
+                | SimpleCommand::RemoveCurrency(_)
+                | SimpleCommand::PriceAll
+                | SimpleCommand::Notify(_)
+                | SimpleCommand::Settings
+        )
+    }
+}
+
+/// Simple commands with their descriptions in `lang`
+fn commands_for(lang: Lang) -> Vec<BotCommand> {
+    SimpleCommand::bot_commands()
+        .into_iter()
+        .map(|command| {
+            let key = format!("cmd-{}", command.command.trim_start_matches('/'));
+            let description = lang.tr(&key);
+            BotCommand::new(command.command, description)
+        })
+        .collect()
+}
+
+/// /help text in `lang`, laid out like `BotCommands::descriptions`
+fn help_text(lang: Lang) -> String {
+    let mut text = lang.tr("help-header") + "\n";
+    for command in commands_for(lang) {
+        text += &format!("\n{} — {}", command.command, command.description);
+    }
+    text
+}
+
+/// Show the menu of the picked language in this chat
+async fn set_chat_commands(bot: &Bot, chat_id: ChatId, lang: Lang) {
+    let result = bot
+        .set_my_commands(commands_for(lang))
+        .scope(BotCommandScope::Chat {
+            chat_id: Recipient::Id(chat_id),
+        })
+        .await;
+    if let Err(err) = result {
+        warn!("Error setting commands for chat {}: {}", chat_id, err);
+    }
 }
 
 async fn simple_commands_handler(
-    cfg: DatabaseManager,
+    cfg: Arc<dyn UserRepository>,
+    registry: AssetRegistry,
+    config: Arc<Config>,
+    shutdown: Shutdown,
     bot: Bot,
     // me: teloxide::types::Me,
     msg: Message,
     cmd: SimpleCommand,
 ) -> Result<(), teloxide::RequestError> {
+    metrics().command(cmd.name());
+    let lang = user_language(&cfg, msg.from()).await;
     match cmd {
         SimpleCommand::Help => {
-            bot.send_message(msg.chat.id, SimpleCommand::descriptions().to_string())
+            bot.send_message(msg.chat.id, help_text(lang))
+                .parse_mode(ParseMode::Html)
                 .await?;
         }
         SimpleCommand::Chart(currency) => {
-            chart_command(bot.clone(), msg.clone(), currency).await;
+            let preferences = user_preferences(&cfg, msg.from()).await;
+            chart_command(
+                bot.clone(),
+                msg.clone(),
+                currency,
+                lang,
+                preferences.chart_theme,
+                config.clone(),
+                shutdown,
+            )
+            .await;
         }
         SimpleCommand::Price(currency) => {
-            let result = price_command(currency).await;
-            bot.send_message(msg.chat.id, result).await?;
+            let symbol = currency.trim().to_uppercase();
+            let fiat = user_preferences(&cfg, msg.from()).await.fiat;
+            let result = price_command(currency, lang, fiat, &registry, &config).await;
+            let mut reply = bot
+                .send_message(msg.chat.id, result)
+                .parse_mode(ParseMode::Html);
+            if !symbol.is_empty() && symbol.chars().all(|c| c.is_ascii_alphanumeric()) {
+                let watched = is_watched(&cfg, msg.from().unwrap().id.0 as i64, &symbol).await;
+                reply = reply.reply_markup(price_keyboard(&symbol, watched, lang));
+            }
+            reply.await?;
         }
         SimpleCommand::Start => {
-            let result = start_command(msg.clone(), cfg.clone()).await;
-            bot.send_message(msg.chat.id, result).await?;
+            let user = msg.from().unwrap();
+            let result = start_command(
+                user.id.0 as i64,
+                user.username.clone().unwrap_or_default(),
+                user.language_code.as_deref(),
+                cfg.clone(),
+            )
+            .await;
+            bot.send_message(msg.chat.id, result)
+                .parse_mode(ParseMode::Html)
+                .await?;
         }
         SimpleCommand::AddCurrency(currency) => {
-            // return if currency is empty
-            if currency.is_empty() {
-                bot.send_message(
-                    msg.chat.id,
-                    "Type /addcurrency [currency_name]\nExample: /addcurrency btc",
-                )
-                .reply_to_message_id(msg.id)
-                .await?;
-                return Ok(());
-            }
-            let result =
-                add_currency_command(msg.from().unwrap().id.0 as i64, currency, cfg.clone()).await;
-            bot.send_message(msg.chat.id, result).await?;
+            let result = add_currency_command(
+                msg.from().unwrap().id.0 as i64,
+                currency,
+                lang,
+                cfg.clone(),
+                registry.clone(),
+                &config,
+            )
+            .await;
+            send_add_currency_reply(&bot, msg.chat.id, result).await?;
         }
         SimpleCommand::RemoveCurrency(currency) => {
-            if currency.is_empty() {
-                bot.send_message(
-                    msg.chat.id,
-                    "Type /removecurrency [currency_name]\nExample: /removecurrency btc",
-                )
-                .reply_to_message_id(msg.id)
-                .await?;
+            // The watchlist as buttons
+            if currency.trim().is_empty() {
+                let symbols = cfg
+                    .get_user(msg.from().unwrap().id.0 as i64)
+                    .await
+                    .map(|user| user.currency)
+                    .unwrap_or_default();
+                if symbols.is_empty() {
+                    bot.send_message(msg.chat.id, lang.tr("removecurrency-empty"))
+                        .parse_mode(ParseMode::Html)
+                        .await?;
+                } else {
+                    bot.send_message(msg.chat.id, lang.tr("removecurrency-choose"))
+                        .parse_mode(ParseMode::Html)
+                        .reply_markup(remove_keyboard(&symbols, lang))
+                        .await?;
+                }
                 return Ok(());
             }
-            let res =
-                remove_currency_command(msg.from().unwrap().id.0 as i64, currency, cfg.clone())
-                    .await;
-            bot.send_message(msg.chat.id, res).await?;
+            let res = remove_currency_command(
+                msg.from().unwrap().id.0 as i64,
+                currency,
+                lang,
+                cfg.clone(),
+            )
+            .await;
+            bot.send_message(msg.chat.id, res)
+                .parse_mode(ParseMode::Html)
+                .await?;
         }
         SimpleCommand::PriceAll => {
             let user = cfg
                 .get_user(msg.from().unwrap().id.0 as i64)
                 .await
                 .expect("Error get user");
-            let result = price_all_command(user).await;
-            bot.send_message(msg.chat.id, result).await?;
+            let symbols = user.currency.clone();
+            let result = price_all_command(user, lang, &config).await;
+            let mut reply = bot
+                .send_message(msg.chat.id, result)
+                .parse_mode(ParseMode::Html);
+            if !symbols.is_empty() {
+                reply = reply.reply_markup(price_all_keyboard(&symbols, lang));
+            }
+            reply.await?;
         }
-        SimpleCommand::Notify => {
-            let result = notify_command(msg.from().unwrap().id.0 as i64, cfg.clone()).await;
-            bot.send_message(msg.chat.id, result).await?;
+        SimpleCommand::Notify(state) => {
+            let result =
+                notify_command(msg.from().unwrap().id.0 as i64, &state, lang, cfg.clone()).await;
+            bot.send_message(msg.chat.id, result)
+                .parse_mode(ParseMode::Html)
+                .await?;
         }
+        SimpleCommand::Language(code) => match Lang::from_code(code.trim()) {
+            Some(picked) => {
+                let result =
+                    set_language_command(msg.from().unwrap().id.0 as i64, picked, cfg.clone())
+                        .await;
+                set_chat_commands(&bot, msg.chat.id, picked).await;
+                bot.send_message(msg.chat.id, result)
+                    .parse_mode(ParseMode::Html)
+                    .await?;
+            }
+            None => {
+                bot.send_message(msg.chat.id, lang.tr("language-choose"))
+                    .parse_mode(ParseMode::Html)
+                    .reply_markup(language_keyboard())
+                    .await?;
+            }
+        },
+        SimpleCommand::Settings => {
+            let (text, keyboard) = settings_command(msg.from(), lang, &cfg, &config).await;
+            bot.send_message(msg.chat.id, text)
+                .parse_mode(ParseMode::Html)
+                .reply_markup(keyboard)
+                .await?;
+        }
+        // Answered by `cancel_handler` and `dialogue_commands_handler`
+        SimpleCommand::Alert(_) | SimpleCommand::Alerts | SimpleCommand::Cancel => {}
     };
 
     Ok(())
 }
 
+/// /cancel, leaves the open dialogue
+async fn cancel_handler(
+    cfg: Arc<dyn UserRepository>,
+    bot: Bot,
+    msg: Message,
+    dialogue: BotDialogue,
+    state: DialogueState,
+) -> Result<(), teloxide::RequestError> {
+    metrics().command(SimpleCommand::Cancel.name());
+    let lang = user_language(&cfg, msg.from()).await;
+    let text = if state.is_active() {
+        update_dialogue(&dialogue, DialogueState::Idle).await;
+        lang.tr("dialogue-cancelled")
+    } else {
+        lang.tr("dialogue-nothing")
+    };
+    bot.send_message(msg.chat.id, text)
+        .parse_mode(ParseMode::Html)
+        .await?;
+    Ok(())
+}
+
+/// Commands that ask a question or work with alerts, see `SimpleCommand::is_dialogue`
+async fn dialogue_commands_handler(
+    cfg: Arc<dyn UserRepository>,
+    alerts: Arc<dyn AlertRepository>,
+    config: Arc<Config>,
+    bot: Bot,
+    msg: Message,
+    dialogue: BotDialogue,
+    cmd: SimpleCommand,
+) -> Result<(), teloxide::RequestError> {
+    metrics().command(cmd.name());
+    let lang = user_language(&cfg, msg.from()).await;
+    let user_id = msg.from().unwrap().id.0 as i64;
+    match cmd {
+        SimpleCommand::AddCurrency(_) => {
+            bot.send_message(msg.chat.id, lang.tr("addcurrency-ask"))
+                .parse_mode(ParseMode::Html)
+                .reply_markup(cancel_keyboard(lang))
+                .await?;
+            update_dialogue(&dialogue, DialogueState::AddCurrency).await;
+        }
+        SimpleCommand::Alert(args) => {
+            let fiat = user_preferences(&cfg, msg.from()).await.fiat;
+            let reply = alert_command(user_id, &args, lang, fiat, &alerts, &config).await;
+            send_alert_reply(&bot, &dialogue, reply, lang).await?;
+        }
+        SimpleCommand::Alerts => {
+            let (text, keyboard) = alerts_command(user_id, lang, &alerts).await;
+            let mut reply = bot
+                .send_message(msg.chat.id, text)
+                .parse_mode(ParseMode::Html);
+            if let Some(keyboard) = keyboard {
+                reply = reply.reply_markup(keyboard);
+            }
+            reply.await?;
+        }
+        _ => {}
+    }
+    Ok(())
+}
+
+/// Symbols asked by argument-less /addcurrency
+async fn add_currency_answer(
+    cfg: Arc<dyn UserRepository>,
+    registry: AssetRegistry,
+    config: Arc<Config>,
+    bot: Bot,
+    msg: Message,
+    dialogue: BotDialogue,
+) -> Result<(), teloxide::RequestError> {
+    let lang = user_language(&cfg, msg.from()).await;
+    let text = match msg.text() {
+        Some(text) => text.to_string(),
+        None => {
+            bot.send_message(msg.chat.id, lang.tr("addcurrency-ask"))
+                .parse_mode(ParseMode::Html)
+                .reply_markup(cancel_keyboard(lang))
+                .await?;
+            return Ok(());
+        }
+    };
+    update_dialogue(&dialogue, DialogueState::Idle).await;
+    let result = add_currency_command(
+        msg.from().unwrap().id.0 as i64,
+        text,
+        lang,
+        cfg,
+        registry,
+        &config,
+    )
+    .await;
+    send_add_currency_reply(&bot, msg.chat.id, result).await
+}
+
+/// Coin asked by /alert
+async fn alert_symbol_answer(
+    cfg: Arc<dyn UserRepository>,
+    alerts: Arc<dyn AlertRepository>,
+    config: Arc<Config>,
+    bot: Bot,
+    msg: Message,
+    dialogue: BotDialogue,
+) -> Result<(), teloxide::RequestError> {
+    let lang = user_language(&cfg, msg.from()).await;
+    let fiat = user_preferences(&cfg, msg.from()).await.fiat;
+    let user_id = msg.from().unwrap().id.0 as i64;
+    let text = msg.text().unwrap_or_default();
+    let reply = alert_symbol_step(user_id, text, lang, fiat, &alerts, &config).await;
+    send_alert_reply(&bot, &dialogue, reply, lang).await
+}
+
+/// Target price asked by /alert
+async fn alert_price_answer(
+    cfg: Arc<dyn UserRepository>,
+    alerts: Arc<dyn AlertRepository>,
+    config: Arc<Config>,
+    bot: Bot,
+    msg: Message,
+    dialogue: BotDialogue,
+    symbol: String,
+) -> Result<(), teloxide::RequestError> {
+    let lang = user_language(&cfg, msg.from()).await;
+    let fiat = user_preferences(&cfg, msg.from()).await.fiat;
+    let user_id = msg.from().unwrap().id.0 as i64;
+    let text = msg.text().unwrap_or_default();
+    let reply = alert_price_step(user_id, &symbol, text, lang, fiat, &alerts, &config).await;
+    send_alert_reply(&bot, &dialogue, reply, lang).await
+}
+
+/// Send a step of the alert dialogue, questions get a Cancel button
+async fn send_alert_reply(
+    bot: &Bot,
+    dialogue: &BotDialogue,
+    reply: AlertReply,
+    lang: Lang,
+) -> Result<(), teloxide::RequestError> {
+    let mut message = bot
+        .send_message(dialogue.chat_id(), reply.text)
+        .parse_mode(ParseMode::Html);
+    if reply.next.is_active() {
+        message = message.reply_markup(cancel_keyboard(lang));
+    }
+    message.await?;
+    update_dialogue(dialogue, reply.next).await;
+    Ok(())
+}
+
+/// Send the result of /addcurrency, ambiguous symbols come with buttons
+pub(super) async fn send_add_currency_reply(
+    bot: &Bot,
+    chat_id: ChatId,
+    reply: AddCurrencyReply,
+) -> Result<(), teloxide::RequestError> {
+    match reply {
+        AddCurrencyReply::Text(text) => {
+            bot.send_message(chat_id, text)
+                .parse_mode(ParseMode::Html)
+                .await?;
+        }
+        AddCurrencyReply::Choose(text, keyboard) => {
+            bot.send_message(chat_id, text)
+                .parse_mode(ParseMode::Html)
+                .reply_markup(keyboard)
+                .await?;
+        }
+    }
+    Ok(())
+}
+
+/// Move the dialogue to `state`, `Idle` closes it
+///
+/// The reply is sent already, so a storage error is only logged.
+async fn update_dialogue(dialogue: &BotDialogue, state: DialogueState) {
+    if let Err(err) = dialogue.update(state).await {
+        warn!(
+            "Error saving the dialogue of chat {}: {}",
+            dialogue.chat_id(),
+            err
+        );
+    }
+}
+
 #[derive(BotCommands, Clone)]
 #[command(rename_rule = "lowercase", description = "Admin commands")]
 enum AdminCommand {
@@ -154,18 +749,65 @@ enum AdminCommand {
     Me,
     #[command(description = "shows your ID.")]
     MyId,
+    #[command(description = "shows background jobs.")]
+    Jobs,
+    #[command(description = "shows upstream API usage.")]
+    Quota,
+    #[command(description = "lift the rate limit ban of a user.")]
+    Unban(String),
+    #[command(description = "schedule a channel summary.")]
+    AddChannel(String),
+    #[command(description = "remove a channel summary.")]
+    RemoveChannel(String),
+    #[command(description = "lists the channel summaries.")]
+    Channels,
+    #[command(description = "publish a channel summary now.")]
+    PostChannel(String),
+}
+
+impl AdminCommand {
+    /// Command name used as metrics label
+    fn name(&self) -> &'static str {
+        match self {
+            AdminCommand::Sendall(_) => "sendall",
+            AdminCommand::Me => "me",
+            AdminCommand::MyId => "myid",
+            AdminCommand::Jobs => "jobs",
+            AdminCommand::Quota => "quota",
+            AdminCommand::Unban(_) => "unban",
+            AdminCommand::AddChannel(_) => "addchannel",
+            AdminCommand::RemoveChannel(_) => "removechannel",
+            AdminCommand::Channels => "channels",
+            AdminCommand::PostChannel(_) => "postchannel",
+        }
+    }
+
+    /// Commands answered by `channel_commands_handler`
+    fn is_channel(&self) -> bool {
+        matches!(
+            self,
+            AdminCommand::AddChannel(_)
+                | AdminCommand::RemoveChannel(_)
+                | AdminCommand::Channels
+                | AdminCommand::PostChannel(_)
+        )
+    }
 }
 
 async fn admin_commands_handler(
-    cfg: DatabaseManager,
+    cfg: Arc<dyn UserRepository>,
+    config: Arc<Config>,
+    broadcaster: Broadcaster,
+    supervisor: Supervisor,
     bot: Bot,
     // me: teloxide::types::Me,
     msg: Message,
     cmd: AdminCommand,
 ) -> Result<(), teloxide::RequestError> {
+    metrics().command(cmd.name());
     match cmd {
         AdminCommand::Sendall(text) => {
-            send_all_command(cfg, bot.clone(), text).await;
+            send_all_command(&broadcaster, text).await;
         }
         AdminCommand::Me => {
             let result = cfg
@@ -175,26 +817,452 @@ async fn admin_commands_handler(
             bot.send_message(msg.chat.id, format!("{:?}", result))
                 .await?;
         }
+        AdminCommand::Jobs => {
+            bot.send_message(msg.chat.id, supervisor.report()).await?;
+        }
+        AdminCommand::Quota => {
+            bot.send_message(msg.chat.id, quotas().report(&config.quotas))
+                .await?;
+        }
         AdminCommand::MyId => {
             bot.send_message(msg.chat.id, format!("{}", msg.from().unwrap().id))
                 .await?;
         }
+        // Answered by `channel_commands_handler`
+        AdminCommand::AddChannel(_)
+        | AdminCommand::RemoveChannel(_)
+        | AdminCommand::Channels
+        | AdminCommand::PostChannel(_) => {}
+        // Answered by `unban_handler`
+        AdminCommand::Unban(_) => {}
+    }
+    Ok(())
+}
+
+/// /unban, lifts the ban and resets the rate limit of a user
+async fn unban_handler(
+    limiter: RateLimiter,
+    bot: Bot,
+    msg: Message,
+    cmd: AdminCommand,
+) -> Result<(), teloxide::RequestError> {
+    metrics().command(cmd.name());
+    if let AdminCommand::Unban(user_id) = cmd {
+        bot.send_message(msg.chat.id, unban_command(&limiter, &user_id))
+            .await?;
     }
     Ok(())
 }
 
+/// Channel summary admin commands, see `AdminCommand::is_channel`
+async fn channel_commands_handler(
+    cfg: Arc<dyn UserRepository>,
+    channels: Arc<dyn ChannelRepository>,
+    config: Arc<Config>,
+    bot: Bot,
+    me: Me,
+    msg: Message,
+    cmd: AdminCommand,
+) -> Result<(), teloxide::RequestError> {
+    metrics().command(cmd.name());
+    let lang = user_language(&cfg, msg.from()).await;
+    let result = match cmd {
+        AdminCommand::AddChannel(args) => {
+            add_channel_command(&bot, &me, &args, lang, &channels, &config).await
+        }
+        AdminCommand::RemoveChannel(id) => remove_channel_command(&id, lang, &channels).await,
+        AdminCommand::Channels => channels_command(lang, &channels).await,
+        AdminCommand::PostChannel(id) => {
+            post_channel_command(&bot, &id, lang, &channels, &config).await
+        }
+        _ => return Ok(()),
+    };
+    bot.send_message(msg.chat.id, result)
+        .parse_mode(ParseMode::Html)
+        .await?;
+    Ok(())
+}
+
 async fn messages_handler(
-    // cfg: DatabaseManager,
+    cfg: Arc<dyn UserRepository>,
+    registry: AssetRegistry,
+    config: Arc<Config>,
     bot: Bot,
     // me: teloxide::types::Me,
     msg: Message,
 ) -> Result<(), teloxide::RequestError> {
     if let Some(text) = msg.text() {
-        let res = parse_text(text).await;
+        let lang = user_language(&cfg, msg.from()).await;
+        let preferences = user_preferences(&cfg, msg.from()).await;
+        let res = parse_text(text, lang, &preferences, &registry, &config).await;
         if res.len() <= 1 {
             return Ok(());
         }
-        bot.send_message(msg.chat.id, res).await?;
+        bot.send_message(msg.chat.id, res)
+            .parse_mode(ParseMode::Html)
+            .await?;
+    }
+    Ok(())
+}
+
+async fn callback_handler(
+    cfg: Arc<dyn UserRepository>,
+    registry: AssetRegistry,
+    config: Arc<Config>,
+    shutdown: Shutdown,
+    bot: Bot,
+    q: CallbackQuery,
+) -> Result<(), teloxide::RequestError> {
+    if let Some(action) = q.data.as_deref().and_then(PriceAction::parse) {
+        return price_callback(cfg, registry, config, shutdown, bot, q, action).await;
+    }
+    if let Some(action) = q.data.as_deref().and_then(PriceAllAction::parse) {
+        return price_all_callback(cfg, config, bot, q, action).await;
+    }
+    if q.data
+        .as_deref()
+        .is_some_and(|data| data.starts_with(SETTINGS_CALLBACK_PREFIX))
+    {
+        return settings_button(cfg, config, bot, q).await;
+    }
+
+    bot.answer_callback_query(q.id.clone()).await?;
+
+    let picked = q
+        .data
+        .as_deref()
+        .and_then(|data| data.strip_prefix(LANGUAGE_CALLBACK_PREFIX))
+        .and_then(Lang::from_code);
+
+    if let Some(picked) = picked {
+        let result = set_language_command(q.from.id.0 as i64, picked, cfg).await;
+        match q.message {
+            Some(message) => {
+                set_chat_commands(&bot, message.chat.id, picked).await;
+                bot.edit_message_text(message.chat.id, message.id, result)
+                    .parse_mode(ParseMode::Html)
+                    .await?;
+            }
+            None => {
+                bot.send_message(q.from.id, result)
+                    .parse_mode(ParseMode::Html)
+                    .await?;
+            }
+        }
+        return Ok(());
+    }
+
+    let cmc_id = q
+        .data
+        .as_deref()
+        .and_then(|data| data.strip_prefix(ASSET_CALLBACK_PREFIX))
+        .and_then(|id| id.parse::<i64>().ok());
+
+    if let Some(cmc_id) = cmc_id {
+        let lang = user_language(&cfg, Some(&q.from)).await;
+        let result =
+            select_asset_command(q.from.id.0 as i64, cmc_id, lang, cfg, registry, &config).await;
+        match q.message {
+            Some(message) => {
+                bot.edit_message_text(message.chat.id, message.id, result)
+                    .parse_mode(ParseMode::Html)
+                    .await?;
+            }
+            None => {
+                bot.send_message(q.from.id, result)
+                    .parse_mode(ParseMode::Html)
+                    .await?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+async fn inline_query_handler(
+    cfg: Arc<dyn UserRepository>,
+    registry: AssetRegistry,
+    config: Arc<Config>,
+    cache: InlineCache,
+    limiter: RateLimiter,
+    bot: Bot,
+    q: InlineQuery,
+) -> Result<(), teloxide::RequestError> {
+    // Telegram sends a query per keystroke, answer once the user stops typing
+    let user_id = q.from.id.0;
+    cache.track(user_id, &q.id);
+    tokio::time::sleep(Duration::from_millis(config.inline.debounce_ms)).await;
+    if !cache.is_latest(user_id, &q.id) {
+        return Ok(());
+    }
+    if limit_inline(&limiter, &config, &q).is_some() {
+        cache.finish(user_id, &q.id);
+        return refuse_inline(bot, q).await;
+    }
+
+    metrics().command("inline");
+    let lang = user_language(&cfg, Some(&q.from)).await;
+    let results = inline_command(&bot, &q.query, lang, &cache, &registry, &config).await;
+    cache.finish(user_id, &q.id);
+
+    bot.answer_inline_query(q.id, results)
+        .cache_time(config.inline.cache_secs as u32)
+        // Answers are in the language of the user
+        .is_personal(true)
+        .await?;
+    Ok(())
+}
+
+/// Buttons under price cards and charts, the message is edited in place
+async fn price_callback(
+    cfg: Arc<dyn UserRepository>,
+    registry: AssetRegistry,
+    config: Arc<Config>,
+    shutdown: Shutdown,
+    bot: Bot,
+    q: CallbackQuery,
+    action: PriceAction,
+) -> Result<(), teloxide::RequestError> {
+    let message = match q.message {
+        Some(message) => message,
+        None => {
+            bot.answer_callback_query(q.id).await?;
+            return Ok(());
+        }
+    };
+    let lang = user_language(&cfg, Some(&q.from)).await;
+    let preferences = user_preferences(&cfg, Some(&q.from)).await;
+    let user_id = q.from.id.0 as i64;
+    let mut notice: Option<String> = None;
+
+    match action {
+        PriceAction::Refresh(symbol) => {
+            let card =
+                price_command(symbol.clone(), lang, preferences.fiat, &registry, &config).await;
+            let watched = is_watched(&cfg, user_id, &symbol).await;
+            let edited = bot
+                .edit_message_text(message.chat.id, message.id, card)
+                .parse_mode(ParseMode::Html)
+                .reply_markup(price_keyboard(&symbol, watched, lang))
+                .await;
+            ignore_not_modified(edited)?;
+        }
+        PriceAction::Chart(symbol, period) => {
+            let request = ChartRequest {
+                currency: symbol,
+                period,
+                theme: preferences.chart_theme,
+            };
+            chart_button_command(bot.clone(), message, request, lang, config, shutdown).await;
+        }
+        PriceAction::Add(symbol) => {
+            let reply = add_currency_command(
+                user_id,
+                symbol.clone(),
+                lang,
+                cfg.clone(),
+                registry,
+                &config,
+            )
+            .await;
+            match reply {
+                AddCurrencyReply::Text(text) => notice = Some(text),
+                AddCurrencyReply::Choose(text, keyboard) => {
+                    bot.send_message(message.chat.id, text)
+                        .parse_mode(ParseMode::Html)
+                        .reply_markup(keyboard)
+                        .await?;
+                }
+            }
+            let watched = is_watched(&cfg, user_id, &symbol).await;
+            let edited = bot
+                .edit_message_reply_markup(message.chat.id, message.id)
+                .reply_markup(price_keyboard(&symbol, watched, lang))
+                .await;
+            ignore_not_modified(edited)?;
+        }
+        PriceAction::Remove(symbol) => {
+            notice =
+                Some(remove_currency_command(user_id, symbol.clone(), lang, cfg.clone()).await);
+            let watched = is_watched(&cfg, user_id, &symbol).await;
+            let edited = bot
+                .edit_message_reply_markup(message.chat.id, message.id)
+                .reply_markup(price_keyboard(&symbol, watched, lang))
+                .await;
+            ignore_not_modified(edited)?;
+        }
+        // Starts a dialogue, see `dialogue_button`
+        PriceAction::Alert(_) => {}
+    }
+
+    answer_with_notice(&bot, q.id, notice).await
+}
+
+/// Buttons under /priceall, the table is rebuilt in place
+async fn price_all_callback(
+    cfg: Arc<dyn UserRepository>,
+    config: Arc<Config>,
+    bot: Bot,
+    q: CallbackQuery,
+    action: PriceAllAction,
+) -> Result<(), teloxide::RequestError> {
+    let lang = user_language(&cfg, Some(&q.from)).await;
+    let user_id = q.from.id.0 as i64;
+    let mut notice: Option<String> = None;
+
+    if let PriceAllAction::Remove(symbol) = action {
+        notice = Some(remove_currency_command(user_id, symbol, lang, cfg.clone()).await);
+    }
+
+    if let Some(message) = q.message {
+        match cfg.get_user(user_id).await {
+            Some(user) => {
+                let symbols = user.currency.clone();
+                let result = price_all_command(user, lang, &config).await;
+                let edited = bot
+                    .edit_message_text(message.chat.id, message.id, result)
+                    .parse_mode(ParseMode::Html)
+                    .reply_markup(price_all_keyboard(&symbols, lang))
+                    .await;
+                ignore_not_modified(edited)?;
+            }
+            None => notice = Some(lang.tr("user-error")),
+        }
     }
+
+    answer_with_notice(&bot, q.id, notice).await
+}
+
+/// /settings buttons, the menu is edited in place
+async fn settings_button(
+    cfg: Arc<dyn UserRepository>,
+    config: Arc<Config>,
+    bot: Bot,
+    q: CallbackQuery,
+) -> Result<(), teloxide::RequestError> {
+    let action = match q.data.as_deref().and_then(SettingsAction::parse) {
+        Some(action) => action,
+        // A button of an older menu
+        None => {
+            bot.answer_callback_query(q.id).await?;
+            return Ok(());
+        }
+    };
+    let lang = user_language(&cfg, Some(&q.from)).await;
+    let (text, keyboard) = settings_callback(q.from.id.0 as i64, action, lang, &cfg, &config).await;
+
+    bot.answer_callback_query(q.id).await?;
+    if let Some(message) = q.message {
+        let edited = bot
+            .edit_message_text(message.chat.id, message.id, text)
+            .parse_mode(ParseMode::Html)
+            .reply_markup(keyboard)
+            .await;
+        ignore_not_modified(edited)?;
+    }
+    Ok(())
+}
+
+/// Buttons that open, answer or close a dialogue
+async fn dialogue_button(
+    cfg: Arc<dyn UserRepository>,
+    alerts: Arc<dyn AlertRepository>,
+    dialogues: Arc<DialogueStorage>,
+    config: Arc<Config>,
+    bot: Bot,
+    q: CallbackQuery,
+    action: DialogueAction,
+) -> Result<(), teloxide::RequestError> {
+    let lang = user_language(&cfg, Some(&q.from)).await;
+    let user_id = q.from.id.0 as i64;
+    // Buttons of inline messages have no chat, the bot asks in private then
+    let chat_id = q
+        .message
+        .as_ref()
+        .map(|message| message.chat.id)
+        .unwrap_or(ChatId(user_id));
+    let dialogue = BotDialogue::new(dialogues, chat_id);
+    let mut notice: Option<String> = None;
+
+    match action {
+        DialogueAction::Cancel => {
+            if dialogue.get().await.ok().flatten().is_some() {
+                update_dialogue(&dialogue, DialogueState::Idle).await;
+                notice = Some(lang.tr("dialogue-cancelled"));
+            }
+            if let Some(message) = q.message {
+                let edited = bot
+                    .edit_message_reply_markup(message.chat.id, message.id)
+                    .await;
+                ignore_not_modified(edited)?;
+            }
+        }
+        DialogueAction::Alert(symbol) => {
+            let fiat = user_preferences(&cfg, Some(&q.from)).await.fiat;
+            let reply = alert_command(user_id, &symbol, lang, fiat, &alerts, &config).await;
+            send_alert_reply(&bot, &dialogue, reply, lang).await?;
+        }
+        DialogueAction::RemoveCurrency(symbol) => {
+            notice = Some(remove_currency_command(user_id, symbol, lang, cfg.clone()).await);
+            if let Some(message) = q.message {
+                let symbols = cfg
+                    .get_user(user_id)
+                    .await
+                    .map(|user| user.currency)
+                    .unwrap_or_default();
+                let edited = if symbols.is_empty() {
+                    bot.edit_message_text(
+                        message.chat.id,
+                        message.id,
+                        lang.tr("removecurrency-empty"),
+                    )
+                    .parse_mode(ParseMode::Html)
+                    .await
+                } else {
+                    bot.edit_message_reply_markup(message.chat.id, message.id)
+                        .reply_markup(remove_keyboard(&symbols, lang))
+                        .await
+                };
+                ignore_not_modified(edited)?;
+            }
+        }
+        DialogueAction::RemoveAlert(id) => {
+            let (text, keyboard) = remove_alert_command(user_id, &id, lang, &alerts).await;
+            if let Some(message) = q.message {
+                let mut edit = bot
+                    .edit_message_text(message.chat.id, message.id, text)
+                    .parse_mode(ParseMode::Html);
+                if let Some(keyboard) = keyboard {
+                    edit = edit.reply_markup(keyboard);
+                }
+                ignore_not_modified(edit.await)?;
+            }
+        }
+    }
+
+    answer_with_notice(&bot, q.id, notice).await
+}
+
+/// Answer a callback query, showing a reply as a short notice
+pub(super) async fn answer_with_notice(
+    bot: &Bot,
+    query_id: String,
+    notice: Option<String>,
+) -> Result<(), teloxide::RequestError> {
+    let mut answer = bot.answer_callback_query(query_id);
+    if let Some(notice) = notice {
+        // Notices are plain text
+        answer = answer.text(html::strip_tags(&notice));
+    }
+    answer.await?;
     Ok(())
 }
+
+/// Editing a message to what it already shows fails, e.g. a refresh without a
+/// price change, which is not an error for us
+pub(super) fn ignore_not_modified<T>(result: Result<T, RequestError>) -> Result<(), RequestError> {
+    match result {
+        Ok(_) | Err(RequestError::Api(ApiError::MessageNotModified)) => Ok(()),
+        Err(err) => Err(err),
+    }
+}
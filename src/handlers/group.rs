@@ -0,0 +1,338 @@
+use crate::commands::{
+    currency::{add_currency, remove_currency, select_asset, ASSET_CALLBACK_PREFIX},
+    group::{
+        addressed_to_bot, group_chat, group_notify_command, is_chat_admin, is_group, Cooldowns,
+    },
+    keyboards::{
+        price_all_keyboard, remove_keyboard, DialogueAction, PriceAllAction,
+        PRICE_ALL_CALLBACK_PREFIX, REMOVE_CALLBACK_PREFIX,
+    },
+    language::user_language,
+    price_all::watchlist_prices,
+    settings::{
+        group_settings_callback, group_settings_keyboard, group_settings_text, SettingsAction,
+        SettingsPage, SETTINGS_CALLBACK_PREFIX,
+    },
+};
+use crate::config::Config;
+use crate::handlers::currency::{
+    answer_with_notice, ignore_not_modified, send_add_currency_reply, SimpleCommand,
+};
+use crate::models::chat::Chat;
+use crate::storage::watchlist::ChatWatchlist;
+use crate::storage::{ChatRepository, UserRepository};
+use crate::tools::asset_registry::AssetRegistry;
+use crate::tools::metrics::metrics;
+use crate::tools::parse_text::parse_text;
+use std::sync::Arc;
+use teloxide::{prelude::*, types::Me, types::ParseMode};
+
+/// Callback data prefixes of the buttons acting on the group watchlist or settings
+const GROUP_CALLBACK_PREFIXES: [&str; 4] = [
+    SETTINGS_CALLBACK_PREFIX,
+    REMOVE_CALLBACK_PREFIX,
+    PRICE_ALL_CALLBACK_PREFIX,
+    ASSET_CALLBACK_PREFIX,
+];
+
+/// Returns true for the buttons of group watchlist and settings messages
+pub(super) fn is_group_button(q: &CallbackQuery) -> bool {
+    let in_group = q
+        .message
+        .as_ref()
+        .is_some_and(|message| is_group(&message.chat));
+    in_group
+        && q.data.as_deref().is_some_and(|data| {
+            GROUP_CALLBACK_PREFIXES
+                .iter()
+                .any(|prefix| data.starts_with(prefix))
+        })
+}
+
+/// Returns true if the sender may change the group, anonymous admins write as the chat
+async fn sender_is_admin(bot: &Bot, msg: &Message) -> bool {
+    if msg
+        .sender_chat()
+        .is_some_and(|sender| sender.id == msg.chat.id)
+    {
+        return true;
+    }
+    match msg.from() {
+        Some(user) => is_chat_admin(bot, msg.chat.id, user.id).await,
+        None => false,
+    }
+}
+
+/// Watchlist and settings commands sent in a group, see `SimpleCommand::is_group`
+pub(super) async fn group_commands_handler(
+    cfg: Arc<dyn UserRepository>,
+    chats: Arc<dyn ChatRepository>,
+    registry: AssetRegistry,
+    config: Arc<Config>,
+    bot: Bot,
+    msg: Message,
+    cmd: SimpleCommand,
+) -> Result<(), teloxide::RequestError> {
+    metrics().command(cmd.name());
+    let lang = user_language(&cfg, msg.from()).await;
+    let mut chat = match group_chat(&chats, &msg.chat, lang).await {
+        Ok(chat) => chat,
+        Err(_) => {
+            bot.send_message(msg.chat.id, lang.tr("chat-error"))
+                .parse_mode(ParseMode::Html)
+                .await?;
+            return Ok(());
+        }
+    };
+
+    if let SimpleCommand::PriceAll = cmd {
+        let symbols = chat.currency.clone();
+        let result = watchlist_prices(
+            chat.currency,
+            &chat.cmc_ids,
+            chat.preferences.fiat,
+            lang,
+            &config,
+        )
+        .await;
+        let mut reply = bot
+            .send_message(msg.chat.id, result)
+            .parse_mode(ParseMode::Html);
+        if !symbols.is_empty() {
+            reply = reply.reply_markup(price_all_keyboard(&symbols, lang));
+        }
+        reply.await?;
+        return Ok(());
+    }
+
+    if !sender_is_admin(&bot, &msg).await {
+        bot.send_message(msg.chat.id, lang.tr("group-admin-only"))
+            .parse_mode(ParseMode::Html)
+            .await?;
+        return Ok(());
+    }
+
+    match cmd {
+        SimpleCommand::AddCurrency(currencies) => {
+            // Dialogues are private, anyone in the group could answer the question
+            if currencies.trim().is_empty() {
+                bot.send_message(msg.chat.id, lang.tr("addcurrency-usage"))
+                    .parse_mode(ParseMode::Html)
+                    .await?;
+                return Ok(());
+            }
+            let watchlist = ChatWatchlist { chats, chat };
+            let reply = add_currency(&watchlist, currencies, lang, &registry, &config).await;
+            send_add_currency_reply(&bot, msg.chat.id, reply).await?;
+        }
+        SimpleCommand::RemoveCurrency(currency) if currency.trim().is_empty() => {
+            if chat.currency.is_empty() {
+                bot.send_message(msg.chat.id, lang.tr("removecurrency-empty"))
+                    .parse_mode(ParseMode::Html)
+                    .await?;
+            } else {
+                bot.send_message(msg.chat.id, lang.tr("removecurrency-choose"))
+                    .parse_mode(ParseMode::Html)
+                    .reply_markup(remove_keyboard(&chat.currency, lang))
+                    .await?;
+            }
+        }
+        SimpleCommand::RemoveCurrency(currency) => {
+            let watchlist = ChatWatchlist { chats, chat };
+            let result = remove_currency(&watchlist, currency, lang).await;
+            bot.send_message(msg.chat.id, result)
+                .parse_mode(ParseMode::Html)
+                .await?;
+        }
+        SimpleCommand::Notify(state) => {
+            let result = group_notify_command(&mut chat, &state, lang, &chats).await;
+            bot.send_message(msg.chat.id, result)
+                .parse_mode(ParseMode::Html)
+                .await?;
+        }
+        SimpleCommand::Settings => {
+            bot.send_message(
+                msg.chat.id,
+                group_settings_text(SettingsPage::Main, &chat, lang, &config),
+            )
+            .parse_mode(ParseMode::Html)
+            .reply_markup(group_settings_keyboard(SettingsPage::Main, &chat, lang))
+            .await?;
+        }
+        _ => {}
+    }
+    Ok(())
+}
+
+/// Dialogue and alert commands sent in a group, see `SimpleCommand::is_dialogue`
+///
+/// Dialogues are kept per chat, in a group the next message of any member
+/// would answer the question, so these commands point to the private chat.
+pub(super) async fn private_only_handler(
+    cfg: Arc<dyn UserRepository>,
+    bot: Bot,
+    me: Me,
+    msg: Message,
+    cmd: SimpleCommand,
+) -> Result<(), teloxide::RequestError> {
+    metrics().command(cmd.name());
+    let lang = user_language(&cfg, msg.from()).await;
+    bot.send_message(
+        msg.chat.id,
+        lang.tr_with("group-private-only", &[("bot", me.username().into())]),
+    )
+    .parse_mode(ParseMode::Html)
+    .reply_to_message_id(msg.id)
+    .await?;
+    Ok(())
+}
+
+/// Buttons under group watchlist and settings messages, see `is_group_button`
+pub(super) async fn group_button(
+    cfg: Arc<dyn UserRepository>,
+    chats: Arc<dyn ChatRepository>,
+    registry: AssetRegistry,
+    config: Arc<Config>,
+    bot: Bot,
+    q: CallbackQuery,
+) -> Result<(), teloxide::RequestError> {
+    let (message, data) = match (q.message, q.data) {
+        (Some(message), Some(data)) => (message, data),
+        _ => return Ok(()),
+    };
+    let lang = user_language(&cfg, Some(&q.from)).await;
+
+    // Everyone can refresh the prices, the rest changes the group
+    let refresh = PriceAllAction::parse(&data) == Some(PriceAllAction::Refresh);
+    if !refresh && !is_chat_admin(&bot, message.chat.id, q.from.id).await {
+        return answer_with_notice(&bot, q.id, Some(lang.tr("group-admin-only"))).await;
+    }
+
+    let mut chat = match group_chat(&chats, &message.chat, lang).await {
+        Ok(chat) => chat,
+        Err(_) => return answer_with_notice(&bot, q.id, Some(lang.tr("chat-error"))).await,
+    };
+    let mut notice: Option<String> = None;
+
+    if let Some(action) = SettingsAction::parse(&data) {
+        let (text, keyboard) =
+            group_settings_callback(&mut chat, action, lang, &chats, &config).await;
+        let edited = bot
+            .edit_message_text(message.chat.id, message.id, text)
+            .parse_mode(ParseMode::Html)
+            .reply_markup(keyboard)
+            .await;
+        ignore_not_modified(edited)?;
+    } else if let Some(DialogueAction::RemoveCurrency(symbol)) = DialogueAction::parse(&data) {
+        let watchlist = ChatWatchlist {
+            chats: chats.clone(),
+            chat,
+        };
+        notice = Some(remove_currency(&watchlist, symbol, lang).await);
+        let symbols = match group_chat(&chats, &message.chat, lang).await {
+            Ok(chat) => chat.currency,
+            Err(_) => return answer_with_notice(&bot, q.id, Some(lang.tr("chat-error"))).await,
+        };
+        let edited = if symbols.is_empty() {
+            bot.edit_message_text(message.chat.id, message.id, lang.tr("removecurrency-empty"))
+                .parse_mode(ParseMode::Html)
+                .await
+        } else {
+            bot.edit_message_reply_markup(message.chat.id, message.id)
+                .reply_markup(remove_keyboard(&symbols, lang))
+                .await
+        };
+        ignore_not_modified(edited)?;
+    } else if let Some(action) = PriceAllAction::parse(&data) {
+        if let PriceAllAction::Remove(symbol) = action {
+            let watchlist = ChatWatchlist {
+                chats: chats.clone(),
+                chat: chat.clone(),
+            };
+            notice = Some(remove_currency(&watchlist, symbol, lang).await);
+            chat = match group_chat(&chats, &message.chat, lang).await {
+                Ok(chat) => chat,
+                Err(_) => return answer_with_notice(&bot, q.id, Some(lang.tr("chat-error"))).await,
+            };
+        }
+        let symbols = chat.currency.clone();
+        let result = watchlist_prices(
+            chat.currency,
+            &chat.cmc_ids,
+            chat.preferences.fiat,
+            lang,
+            &config,
+        )
+        .await;
+        let edited = bot
+            .edit_message_text(message.chat.id, message.id, result)
+            .parse_mode(ParseMode::Html)
+            .reply_markup(price_all_keyboard(&symbols, lang))
+            .await;
+        ignore_not_modified(edited)?;
+    } else if let Some(cmc_id) = data
+        .strip_prefix(ASSET_CALLBACK_PREFIX)
+        .and_then(|id| id.parse::<i64>().ok())
+    {
+        let watchlist = ChatWatchlist { chats, chat };
+        let result = select_asset(&watchlist, cmc_id, lang, &registry, &config).await;
+        bot.edit_message_text(message.chat.id, message.id, result)
+            .parse_mode(ParseMode::Html)
+            .await?;
+    }
+
+    answer_with_notice(&bot, q.id, notice).await
+}
+
+/// Settings of the group a plain message is meant for, `None` keeps the bot quiet
+///
+/// Groups in mention mode only get answers to messages mentioning or
+/// replying to the bot.
+pub(super) async fn addressed_group_chat(
+    cfg: Arc<dyn UserRepository>,
+    chats: Arc<dyn ChatRepository>,
+    me: Me,
+    msg: Message,
+) -> Option<Chat> {
+    let text = msg.text()?;
+    let lang = user_language(&cfg, msg.from()).await;
+    // Without the settings the mention mode is unknown, stay quiet
+    let chat = group_chat(&chats, &msg.chat, lang).await.ok()?;
+
+    if chat.mention_only {
+        let reply_to_bot = msg
+            .reply_to_message()
+            .and_then(|reply| reply.from())
+            .is_some_and(|user| user.id == me.id);
+        if !addressed_to_bot(text, me.username(), reply_to_bot) {
+            return None;
+        }
+    }
+    Some(chat)
+}
+
+/// Plain messages in a group, answered with the group's expanders and cooldown
+pub(super) async fn group_messages_handler(
+    cfg: Arc<dyn UserRepository>,
+    cooldowns: Cooldowns,
+    registry: AssetRegistry,
+    config: Arc<Config>,
+    bot: Bot,
+    msg: Message,
+    chat: Chat,
+) -> Result<(), teloxide::RequestError> {
+    let text = match msg.text() {
+        Some(text) => text,
+        None => return Ok(()),
+    };
+    let lang = user_language(&cfg, msg.from()).await;
+    let res = parse_text(text, lang, &chat.preferences, &registry, &config).await;
+    if res.len() <= 1 || !cooldowns.try_start(chat.chat_id, chat.cooldown(config.group_cooldown()))
+    {
+        return Ok(());
+    }
+    bot.send_message(msg.chat.id, res)
+        .parse_mode(ParseMode::Html)
+        .await?;
+    Ok(())
+}
@@ -1,2 +1,6 @@
 //pub mod common;
 pub mod currency;
+pub mod group;
+pub mod rate_limit;
+pub mod status;
+pub mod webhook;
@@ -0,0 +1,229 @@
+use crate::commands::{group::is_group, language::user_language};
+use crate::config::Config;
+use crate::handlers::currency::answer_with_notice;
+use crate::i18n::Lang;
+use crate::storage::UserRepository;
+use crate::tools::metrics::metrics;
+use crate::tools::rate_limit::{RateLimiter, Refusal};
+use std::sync::Arc;
+use teloxide::{prelude::*, types::Me};
+
+/// What a message costs, `None` for the free ones
+///
+/// Commands are charged by name, commands of other bots are free. Plain
+/// messages cost `text` in private chats, in groups the group cooldown
+/// limits them and chatter not meant for the bot is never refused.
+///
+/// # Arguments
+///
+/// * `text` - Text of the message
+/// * `group` - Whether the message was sent to a group
+/// * `username` - Username of the bot, without `@`
+///
+fn message_action(text: &str, group: bool, username: &str) -> Option<String> {
+    let command = match text.strip_prefix('/') {
+        Some(command) => command.split_whitespace().next().unwrap_or_default(),
+        None if group => return None,
+        None => return Some("text".to_string()),
+    };
+    let (name, bot) = command.split_once('@').unwrap_or((command, username));
+    if !bot.eq_ignore_ascii_case(username) {
+        return None;
+    }
+    Some(name.to_lowercase())
+}
+
+/// Middleware of the messages, `Some` when the message is over the limit
+pub(super) fn limit_message(
+    limiter: RateLimiter,
+    config: Arc<Config>,
+    me: Me,
+    msg: Message,
+) -> Option<Refusal> {
+    let user = msg.from()?;
+    let group = is_group(&msg.chat);
+    let action = message_action(msg.text()?, group, me.username())?;
+    let chat_id = group.then_some(msg.chat.id.0);
+    limit(&limiter, &config, user.id.0, chat_id, &action)
+}
+
+/// Middleware of the buttons, `Some` when the press is over the limit
+pub(super) fn limit_button(
+    limiter: RateLimiter,
+    config: Arc<Config>,
+    q: CallbackQuery,
+) -> Option<Refusal> {
+    let chat_id = q
+        .message
+        .as_ref()
+        .filter(|message| is_group(&message.chat))
+        .map(|message| message.chat.id.0);
+    limit(&limiter, &config, q.from.id.0, chat_id, "button")
+}
+
+/// Limit of the inline queries, `Some` when the query is over the limit
+///
+/// Checked once the typing settles, so only the answered query costs
+/// `inline` and the keystrokes before it are free, empty queries as well.
+pub(super) fn limit_inline(
+    limiter: &RateLimiter,
+    config: &Config,
+    q: &InlineQuery,
+) -> Option<Refusal> {
+    if q.query.trim().is_empty() {
+        return None;
+    }
+    limit(limiter, config, q.from.id.0, None, "inline")
+}
+
+/// Take the tokens of an action, `Some` when it is refused
+fn limit(
+    limiter: &RateLimiter,
+    config: &Config,
+    user_id: u64,
+    chat_id: Option<i64>,
+    action: &str,
+) -> Option<Refusal> {
+    if !config.rate_limit.enabled || config.is_rate_exempt(user_id) {
+        return None;
+    }
+    let cost = config.rate_limit.cost(action);
+    let refusal = limiter
+        .check(user_id as i64, chat_id, cost, &config.rate_limit)
+        .err()?;
+    metrics().rate_limited(refusal);
+    Some(refusal)
+}
+
+/// Answer to a refused message, only the first refusal and bans are answered
+pub(super) async fn slow_down_message(
+    cfg: Arc<dyn UserRepository>,
+    bot: Bot,
+    msg: Message,
+    refusal: Refusal,
+) -> Result<(), teloxide::RequestError> {
+    let lang = user_language(&cfg, msg.from()).await;
+    if let Some(text) = refusal_text(refusal, lang) {
+        bot.send_message(msg.chat.id, text)
+            .reply_to_message_id(msg.id)
+            .await?;
+    }
+    Ok(())
+}
+
+/// Answer to a refused button, the spinner always stops
+pub(super) async fn slow_down_button(
+    cfg: Arc<dyn UserRepository>,
+    bot: Bot,
+    q: CallbackQuery,
+    refusal: Refusal,
+) -> Result<(), teloxide::RequestError> {
+    let lang = user_language(&cfg, Some(&q.from)).await;
+    answer_with_notice(&bot, q.id, refusal_text(refusal, lang)).await
+}
+
+/// Answer to a refused inline query, no results and nothing cached
+pub(super) async fn refuse_inline(bot: Bot, q: InlineQuery) -> Result<(), teloxide::RequestError> {
+    bot.answer_inline_query(q.id, vec![])
+        .cache_time(0)
+        .is_personal(true)
+        .await?;
+    Ok(())
+}
+
+/// Answer to /unban
+///
+/// # Arguments
+///
+/// * `limiter` - Rate limiter of the bot
+/// * `args` - Telegram id of the user
+///
+pub(super) fn unban_command(limiter: &RateLimiter, args: &str) -> String {
+    match args.trim().parse::<i64>() {
+        Ok(user_id) if limiter.unban(user_id) => format!("User {} unbanned", user_id),
+        Ok(user_id) => format!("User {} is not banned, its rate limit is reset", user_id),
+        Err(_) => "Type /unban user_id".to_string(),
+    }
+}
+
+/// Text of a refusal, `None` for the silent ones
+fn refusal_text(refusal: Refusal, lang: Lang) -> Option<String> {
+    match refusal {
+        Refusal::SlowDown(wait) => {
+            Some(lang.tr_with("rate-limited", &[("secs", wait.as_secs().max(1).into())]))
+        }
+        Refusal::Banned(ban) => Some(lang.tr_with(
+            "rate-banned",
+            &[("minutes", ban.as_secs().div_ceil(60).into())],
+        )),
+        Refusal::Silent => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn test_message_action() {
+        assert_eq!(
+            message_action("/chart btc", false, "coinbot").as_deref(),
+            Some("chart")
+        );
+        assert_eq!(
+            message_action("/Chart@CoinBot btc", true, "coinbot").as_deref(),
+            Some("chart")
+        );
+        // Commands of other bots and group chatter are free
+        assert_eq!(message_action("/start@otherbot", true, "coinbot"), None);
+        assert_eq!(message_action("btc eth", true, "coinbot"), None);
+        assert_eq!(
+            message_action("btc eth", false, "coinbot").as_deref(),
+            Some("text")
+        );
+    }
+
+    #[test]
+    fn test_limit_inline() {
+        let query = |user_id: u64, query: &str| -> InlineQuery {
+            serde_json::from_value(serde_json::json!({
+                "id": "1",
+                "from": {"id": user_id, "is_bot": false, "first_name": "Test"},
+                "query": query,
+                "offset": "",
+            }))
+            .unwrap()
+        };
+        let mut config = Config::default();
+        config.admin_ids = vec![1];
+        config.rate_limit.user_burst = 2;
+        config.rate_limit.costs.insert("inline".to_string(), 1);
+        let limiter = RateLimiter::new();
+        let limit = |q| limit_inline(&limiter, &config, &q);
+
+        assert_eq!(limit(query(2, "btc")), None);
+        assert_eq!(limit(query(2, "btc eth")), None);
+        assert!(matches!(
+            limit(query(2, "btc eth sol")),
+            Some(Refusal::SlowDown(_))
+        ));
+        // Empty queries and admins are never limited
+        assert_eq!(limit(query(2, " ")), None);
+        for _ in 0..5 {
+            assert_eq!(limit(query(1, "btc")), None);
+        }
+    }
+
+    #[test]
+    fn test_refusal_text() {
+        let text = refusal_text(Refusal::SlowDown(Duration::from_secs(12)), Lang::En);
+        assert_eq!(
+            text.as_deref(),
+            Some("⏳ Slow down, please. Try again in 12 s")
+        );
+        let text = refusal_text(Refusal::Banned(Duration::from_secs(600)), Lang::En);
+        assert!(text.unwrap().contains("10 min"));
+        assert_eq!(refusal_text(Refusal::Silent, Lang::En), None);
+    }
+}
@@ -0,0 +1,112 @@
+use crate::config::Config;
+use crate::handlers::webhook::serve;
+use crate::storage::UserRepository;
+use crate::tools::metrics::metrics;
+use axum::extract::State;
+use axum::http::{header, StatusCode};
+use axum::response::IntoResponse;
+use axum::routing::get;
+use axum::Router;
+use log::info;
+use std::error::Error;
+use std::net::TcpListener;
+use std::sync::Arc;
+use teloxide::prelude::*;
+
+#[derive(Clone)]
+struct StatusState {
+    bot: Bot,
+    db: Arc<dyn UserRepository>,
+}
+
+/// Routes of the status server
+///
+/// * `/healthz` - The process is up
+/// * `/readyz` - Storage is reachable and the Telegram token is accepted
+/// * `/metrics` - Prometheus metrics
+pub fn status_router(bot: Bot, db: Arc<dyn UserRepository>) -> Router {
+    Router::new()
+        .route("/healthz", get(healthz))
+        .route("/readyz", get(readyz))
+        .route("/metrics", get(metrics_handler))
+        .with_state(StatusState { bot, db })
+}
+
+/// Start the status server in the background if it is enabled
+pub fn start_status_server(
+    bot: Bot,
+    db: Arc<dyn UserRepository>,
+    config: &Config,
+) -> Result<(), Box<dyn Error>> {
+    if !config.status.enabled {
+        return Ok(());
+    }
+
+    let address = config.status_address()?;
+    let socket = TcpListener::bind(address)?;
+    serve(socket, status_router(bot, db), std::future::pending())?;
+    info!("Status server listening on {}", address);
+
+    Ok(())
+}
+
+async fn healthz() -> &'static str {
+    "ok"
+}
+
+async fn readyz(State(state): State<StatusState>) -> (StatusCode, String) {
+    let mut problems: Vec<String> = Vec::new();
+    if let Err(err) = state.db.ping().await {
+        problems.push(format!("storage: {}", err));
+    }
+    if let Err(err) = state.bot.get_me().await {
+        problems.push(format!("telegram: {}", err));
+    }
+
+    if problems.is_empty() {
+        (StatusCode::OK, "ok".to_string())
+    } else {
+        (StatusCode::SERVICE_UNAVAILABLE, problems.join("\n"))
+    }
+}
+
+async fn metrics_handler(State(state): State<StatusState>) -> impl IntoResponse {
+    metrics().update_users(state.db.as_ref()).await;
+    (
+        [(header::CONTENT_TYPE, "text/plain; version=0.0.4")],
+        metrics().render(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::memory::MemoryStorage;
+
+    #[tokio::test]
+    async fn test_status_endpoints() {
+        // Nothing listens on the API URL, so the token check has to fail
+        let closed = TcpListener::bind("127.0.0.1:0").unwrap();
+        let api_url = format!("http://{}", closed.local_addr().unwrap());
+        drop(closed);
+        let bot = Bot::new("123:abc").set_api_url(api_url.parse().unwrap());
+
+        let socket = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = socket.local_addr().unwrap();
+        let router = status_router(bot, Arc::new(MemoryStorage::new()));
+        serve(socket, router, std::future::pending()).unwrap();
+
+        let get = |path: &str| reqwest::get(format!("http://{}{}", address, path));
+
+        let response = get("/healthz").await.unwrap();
+        assert_eq!(response.status(), reqwest::StatusCode::OK);
+
+        let response = get("/readyz").await.unwrap();
+        assert_eq!(response.status(), reqwest::StatusCode::SERVICE_UNAVAILABLE);
+        assert!(response.text().await.unwrap().starts_with("telegram:"));
+
+        let response = get("/metrics").await.unwrap();
+        assert_eq!(response.status(), reqwest::StatusCode::OK);
+        assert!(response.text().await.unwrap().contains("bot_users"));
+    }
+}
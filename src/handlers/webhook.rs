@@ -0,0 +1,129 @@
+use crate::config::WebhookConfig;
+use log::{error, info};
+use std::convert::Infallible;
+use std::error::Error;
+use std::future::Future;
+use std::net::TcpListener;
+use teloxide::prelude::*;
+use teloxide::types::InputFile;
+use teloxide::update_listeners::{webhooks, UpdateListener};
+
+/// Register the webhook with Telegram and start the local listener
+///
+/// The socket is bound before `setWebhook` is called, so a busy port fails the
+/// startup instead of leaving Telegram posting to nowhere. The webhook is
+/// deleted again when the listener stops.
+///
+/// # Arguments
+///
+/// * `bot` - Bot
+/// * `config` - Webhook configuration
+///
+/// # Returns
+///
+/// * `UpdateListener` - Listener to pass to the dispatcher
+pub async fn webhook_listener(
+    bot: Bot,
+    config: &WebhookConfig,
+) -> Result<impl UpdateListener<Err = Infallible>, Box<dyn Error>> {
+    let address = config.address()?;
+    let url = config.public_url()?.ok_or("webhook.url is not set")?;
+
+    let mut options = webhooks::Options::new(address, url.clone());
+    if let Some(secret) = &config.secret_token {
+        options = options.secret_token(secret.clone());
+    }
+    if let Some(file) = &config.certificate {
+        options = options.certificate(InputFile::file(file));
+    }
+    if let Some(max_connections) = config.max_connections {
+        options = options.max_connections(max_connections);
+    }
+    if config.drop_pending_updates {
+        options = options.drop_pending_updates();
+    }
+
+    let socket = TcpListener::bind(address)?;
+    let (listener, stop, router) = webhooks::axum_to_router(bot, options).await?;
+    serve(socket, router, stop)?;
+    info!("Listening for webhook updates on {} ({})", address, url);
+
+    Ok(listener)
+}
+
+/// Serve the webhook router on a bound socket until `stop` resolves
+///
+/// # Arguments
+///
+/// * `socket` - Bound listener socket
+/// * `router` - Webhook router
+/// * `stop` - Resolves when the update listener is stopped
+pub fn serve(
+    socket: TcpListener,
+    router: axum::Router,
+    stop: impl Future<Output = ()> + Send + 'static,
+) -> Result<(), Box<dyn Error>> {
+    let server = axum::Server::from_tcp(socket)?
+        .serve(router.into_make_service())
+        .with_graceful_shutdown(stop);
+
+    tokio::spawn(async move {
+        if let Err(err) = server.await {
+            error!("Webhook server error: {}", err);
+        }
+    });
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::StreamExt;
+    use teloxide::update_listeners::AsUpdateStream;
+
+    const UPDATE: &str = r#"{
+        "update_id": 1,
+        "message": {
+            "message_id": 1,
+            "date": 0,
+            "chat": {"id": 1, "type": "private", "first_name": "Test"},
+            "from": {"id": 1, "is_bot": false, "first_name": "Test"},
+            "text": "/start"
+        }
+    }"#;
+
+    #[tokio::test]
+    async fn test_webhook_accepts_only_secret_token() {
+        let socket = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = socket.local_addr().unwrap();
+        let options =
+            webhooks::Options::new(address, "https://bot.example.com/telegram".parse().unwrap())
+                .secret_token("s3cret".to_string());
+
+        let (mut listener, stop, router) = webhooks::axum_no_setup(options);
+        serve(socket, router, stop).unwrap();
+
+        let client = reqwest::Client::new();
+        let url = format!("http://{}/telegram", address);
+        let post = |secret: &'static str| {
+            client
+                .post(&url)
+                .header("X-Telegram-Bot-Api-Secret-Token", secret)
+                .header("Content-Type", "application/json")
+                .body(UPDATE)
+                .send()
+        };
+
+        let response = post("wrong").await.unwrap();
+        assert_eq!(response.status(), reqwest::StatusCode::UNAUTHORIZED);
+
+        let response = post("s3cret").await.unwrap();
+        assert_eq!(response.status(), reqwest::StatusCode::OK);
+
+        let stream = listener.as_stream();
+        futures::pin_mut!(stream);
+        let update = stream.next().await.unwrap().unwrap();
+        assert_eq!(update.id, 1);
+    }
+}
@@ -0,0 +1,195 @@
+use crate::tools::html;
+use fluent_bundle::concurrent::FluentBundle;
+use fluent_bundle::{FluentArgs, FluentResource, FluentValue};
+use log::{error, warn};
+use serde::{Deserialize, Serialize};
+use std::sync::OnceLock;
+use unic_langid::LanguageIdentifier;
+
+/// Message catalogs, compiled into the binary
+///
+/// Messages are Telegram HTML, string arguments are escaped when formatting.
+const CATALOGS: [(Lang, &str); 3] = [
+    (Lang::En, include_str!("../locales/en.ftl")),
+    (Lang::Ru, include_str!("../locales/ru.ftl")),
+    (Lang::Uk, include_str!("../locales/uk.ftl")),
+];
+
+/// Language of the bot replies
+///
+/// Stored per user as its ISO 639-1 code. English is the default and the
+/// fallback for messages missing from another catalog.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Lang {
+    #[default]
+    En,
+    Ru,
+    Uk,
+}
+
+impl Lang {
+    /// Every supported language
+    pub const ALL: [Lang; 3] = [Lang::En, Lang::Ru, Lang::Uk];
+
+    /// ISO 639-1 code, as used by Telegram
+    pub fn code(self) -> &'static str {
+        match self {
+            Lang::En => "en",
+            Lang::Ru => "ru",
+            Lang::Uk => "uk",
+        }
+    }
+
+    /// Name of the language in that language, used on the /language buttons
+    pub fn name(self) -> &'static str {
+        match self {
+            Lang::En => "English",
+            Lang::Ru => "Русский",
+            Lang::Uk => "Українська",
+        }
+    }
+
+    /// Supported language for a code like `ru` or `uk-UA`
+    pub fn from_code(code: &str) -> Option<Lang> {
+        let primary = code.split(['-', '_']).next()?.to_lowercase();
+        Lang::ALL.into_iter().find(|lang| lang.code() == primary)
+    }
+
+    /// Language for Telegram's `language_code`, English if it is unknown or missing
+    pub fn detect(code: Option<&str>) -> Lang {
+        code.and_then(Lang::from_code).unwrap_or_default()
+    }
+
+    /// Translate a message without arguments
+    pub fn tr(self, key: &str) -> String {
+        self.tr_with(key, &[])
+    }
+
+    /// Translate a message
+    ///
+    /// # Arguments
+    ///
+    /// * `key` - Message id in the catalogs
+    /// * `args` - Message arguments, numbers select the plural forms and
+    ///   strings are HTML escaped
+    ///
+    /// # Returns
+    ///
+    /// * `String` - The message, the English one if it is missing in this
+    ///   language, or `key` if it is missing everywhere
+    pub fn tr_with(self, key: &str, args: &[(&str, FluentValue)]) -> String {
+        let mut fluent_args = FluentArgs::new();
+        for (name, value) in args {
+            let value = match value {
+                FluentValue::String(text) => FluentValue::from(html::escape(text)),
+                value => value.clone(),
+            };
+            fluent_args.set(*name, value);
+        }
+
+        for lang in [self, Lang::En] {
+            let bundle = bundle(lang);
+            let pattern = match bundle.get_message(key).and_then(|message| message.value()) {
+                Some(pattern) => pattern,
+                None => continue,
+            };
+            let mut errors = vec![];
+            let text = bundle.format_pattern(pattern, Some(&fluent_args), &mut errors);
+            if !errors.is_empty() {
+                warn!("Errors formatting {} in {}: {:?}", key, lang.code(), errors);
+            }
+            return text.into_owned();
+        }
+
+        error!("Missing message {}", key);
+        key.to_string()
+    }
+}
+
+/// Bundle of a language, the catalogs are parsed on first use
+fn bundle(lang: Lang) -> &'static FluentBundle<FluentResource> {
+    static BUNDLES: OnceLock<Vec<(Lang, FluentBundle<FluentResource>)>> = OnceLock::new();
+
+    let bundles = BUNDLES.get_or_init(|| {
+        CATALOGS
+            .iter()
+            .map(|(lang, source)| (*lang, build_bundle(*lang, source)))
+            .collect()
+    });
+    bundles
+        .iter()
+        .find(|(bundle_lang, _)| *bundle_lang == lang)
+        .map(|(_, bundle)| bundle)
+        .expect("every language has a catalog")
+}
+
+fn build_bundle(lang: Lang, source: &str) -> FluentBundle<FluentResource> {
+    let resource =
+        FluentResource::try_new(source.to_string()).unwrap_or_else(|(resource, errors)| {
+            error!("Errors parsing the {} catalog: {:?}", lang.code(), errors);
+            resource
+        });
+
+    let langid: LanguageIdentifier = lang.code().parse().expect("valid language code");
+    let mut bundle = FluentBundle::new_concurrent(vec![langid]);
+    // Telegram shows the bidi isolation marks around arguments as is
+    bundle.set_use_isolating(false);
+    if let Err(errors) = bundle.add_resource(resource) {
+        error!("Errors loading the {} catalog: {:?}", lang.code(), errors);
+    }
+    bundle
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Message ids defined in a catalog, messages start at the beginning of a line
+    fn message_ids(source: &str) -> Vec<&str> {
+        source
+            .lines()
+            .filter(|line| line.starts_with(|c: char| c.is_ascii_lowercase()))
+            .filter_map(|line| line.split_once(" =").map(|(id, _)| id))
+            .collect()
+    }
+
+    #[test]
+    fn test_catalogs_complete() {
+        for (lang, _) in CATALOGS.iter() {
+            for id in message_ids(CATALOGS[0].1) {
+                assert!(
+                    bundle(*lang).has_message(id),
+                    "{} is missing in {}",
+                    id,
+                    lang.code()
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_detect() {
+        assert_eq!(Lang::detect(Some("ru")), Lang::Ru);
+        assert_eq!(Lang::detect(Some("uk-UA")), Lang::Uk);
+        assert_eq!(Lang::detect(Some("de")), Lang::En);
+        assert_eq!(Lang::detect(None), Lang::En);
+    }
+
+    #[test]
+    fn test_translate() {
+        assert_eq!(
+            Lang::Ru.tr_with("currency-removed", &[("symbol", "BTC".into())]),
+            "Удалили валюту \"BTC\""
+        );
+        assert_eq!(
+            Lang::Uk.tr_with("currency-removed", &[("symbol", "BTC".into())]),
+            "Видалили валюту \"BTC\""
+        );
+        assert_eq!(
+            Lang::En.tr_with("currency-unknown", &[("symbol", "<b>".into())]),
+            "Unknown coin &lt;b&gt;"
+        );
+        assert_eq!(Lang::En.tr("no-such-message"), "no-such-message");
+    }
+}
@@ -2,19 +2,30 @@ use teloxide::prelude::*;
 
 use dotenvy::dotenv;
 mod commands;
+mod config;
 mod db;
 mod handlers;
+mod i18n;
 mod models;
+mod storage;
 mod tests;
 mod tools;
 
 use flexi_logger::{colored_opt_format, opt_format, FileSpec, Logger};
 
+use crate::config::Config;
 use crate::db::DatabaseManager;
 use log::*;
-use std::env::var;
+use std::sync::Arc;
 
 use crate::handlers::currency::register_currency_handlers;
+use crate::handlers::status::start_status_server;
+use crate::models::preferences::USER_SCHEMA_VERSION;
+#[cfg(feature = "sql")]
+use crate::storage::sql::{import_users, SqlStorage};
+use crate::storage::Storage;
+use crate::tools::asset_registry::AssetRegistry;
+use crate::tools::shutdown::Shutdown;
 
 #[tokio::main]
 async fn main() {
@@ -35,22 +46,95 @@ async fn main() {
 
     info!("Starting command bot...");
 
-    let bot = Bot::from_env();
+    let config = match Config::load() {
+        Ok(config) => Arc::new(config),
+        Err(err) => {
+            error!("{}", err);
+            std::process::exit(1);
+        }
+    };
 
-    let db = connect_to_db().await;
+    let bot = Bot::new(config.telegram_token.clone());
 
-    register_currency_handlers(bot.clone(), db.clone()).await;
+    #[cfg(feature = "sql")]
+    if std::env::args().nth(1).as_deref() == Some("migrate-mongo") {
+        migrate_mongo(&config).await;
+        return;
+    }
+
+    let storage = connect_storage(&config).await;
+
+    let registry = AssetRegistry::new(storage.assets.clone()).await;
+
+    if let Err(err) = start_status_server(bot.clone(), storage.users.clone(), &config) {
+        error!("Error starting status server: {}", err);
+        std::process::exit(1);
+    }
+
+    register_currency_handlers(bot.clone(), storage, registry, config, Shutdown::new()).await;
+}
+
+/// MongoDB by default, SQL when built with the `sql` feature and `DATABASE_URL` is set
+async fn connect_storage(config: &Config) -> Storage {
+    #[cfg(feature = "sql")]
+    if let Some(url) = &config.database.url {
+        let db = SqlStorage::new(url)
+            .await
+            .expect("Failed to connect to SQL database");
+        return Storage::new(db);
+    }
+
+    Storage::new(connect_to_db(config).await)
 }
 
-async fn connect_to_db() -> DatabaseManager {
-    let db_user: String = var("DB_USER").expect("db_user must be set");
-    let db_password: String = var("DB_PASSWORD").expect("db_password must be set");
-    let db_host: String = var("DB_HOST").expect("db_host must be set");
-    let db_name: String = var("DB_NAME").expect("db_name must be set");
+/// Copy users from the MongoDB `user` collection into the `DATABASE_URL` database
+#[cfg(feature = "sql")]
+async fn migrate_mongo(config: &Config) {
+    let url = config
+        .database
+        .url
+        .as_ref()
+        .expect("DATABASE_URL must be set");
+    let target = SqlStorage::new(url)
+        .await
+        .expect("Failed to connect to SQL database");
+    let source = connect_to_db(config).await;
 
-    let uri = format!("mongodb://{}:{}@{}:27017", db_user, db_password, db_host);
+    match import_users(&source, &target).await {
+        Ok(count) => info!("Migrated {} users from MongoDB", count),
+        Err(err) => error!("Migration failed: {}", err),
+    }
+}
 
-    DatabaseManager::new(&uri, &db_name)
+async fn connect_to_db(config: &Config) -> DatabaseManager {
+    let database = &config.database;
+    let db = DatabaseManager::connect(
+        &database.mongo_uri(),
+        &database.name,
+        database.connect_retries,
+    )
+    .await
+    .expect("Failed to connect to MongoDB");
+    db.create_indexes()
         .await
-        .expect("Failed to connect to MongoDB")
+        .expect("Failed to create MongoDB indexes");
+    let migrated = db
+        .migrate_users()
+        .await
+        .expect("Failed to migrate MongoDB users");
+    if migrated > 0 {
+        info!(
+            "Migrated {} users to schema version {}",
+            migrated, USER_SCHEMA_VERSION
+        );
+    }
+    let migrated = db
+        .migrate_chats()
+        .await
+        .expect("Failed to migrate MongoDB chats");
+    if migrated > 0 {
+        info!("Migrated the CoinMarketCap ids of {} chats", migrated);
+    }
+
+    db
 }
@@ -0,0 +1,69 @@
+use crate::models::preferences::Fiat;
+use mongodb::bson;
+use serde::{Deserialize, Serialize};
+
+/// Alert model
+///
+/// A price a user waits for. The alert fires once, when the price crosses
+/// `target` in the direction it was set, and is removed.
+///
+/// # Fields
+///
+/// * `id` - Alert id, `{user_id}-{created_at millis}`
+/// * `user_id` - User id, the alert is sent to the private chat
+/// * `symbol` - Coin, e.g. `BTC`
+/// * `fiat` - Currency of `target`
+/// * `target` - Price to wait for
+/// * `above` - Fire when the price rises to `target`, else when it falls to it
+/// * `created_at` - Alert created at
+///
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct Alert {
+    /// Alert id
+    pub id: String,
+    /// User id
+    pub user_id: i64,
+    /// Coin symbol
+    pub symbol: String,
+    /// Currency of the target
+    pub fiat: Fiat,
+    /// Target price
+    pub target: f64,
+    /// Direction of the price move
+    pub above: bool,
+    /// Alert created at
+    pub created_at: bson::DateTime,
+}
+
+impl Alert {
+    /// Create new alert
+    ///
+    /// # Arguments
+    ///
+    /// * `user_id` - User id
+    /// * `symbol` - Coin symbol
+    /// * `fiat` - Currency of the target
+    /// * `target` - Target price
+    /// * `current` - Price when the alert is set, picks the direction
+    pub fn new(user_id: i64, symbol: String, fiat: Fiat, target: f64, current: f64) -> Self {
+        let created_at = bson::DateTime::now();
+        Self {
+            id: format!("{}-{}", user_id, created_at.timestamp_millis()),
+            user_id,
+            symbol: symbol.to_uppercase(),
+            fiat,
+            target,
+            above: target >= current,
+            created_at,
+        }
+    }
+
+    /// Returns true if `price` reached the target
+    pub fn is_triggered(&self, price: f64) -> bool {
+        if self.above {
+            price >= self.target
+        } else {
+            price <= self.target
+        }
+    }
+}
@@ -0,0 +1,62 @@
+use mongodb::bson;
+use serde::{Deserialize, Serialize};
+
+/// Asset model
+///
+/// One entry of the CoinMarketCap id map, cached locally so that tickers can be
+/// resolved without hitting the API on every command.
+///
+/// # Fields
+///
+/// * `cmc_id` - CoinMarketCap id
+/// * `symbol` - Ticker symbol, e.g. `BTC`
+/// * `name` - Human readable name, e.g. `Bitcoin`
+/// * `slug` - CoinMarketCap slug, e.g. `bitcoin`
+/// * `rank` - CoinMarketCap rank, lower is bigger
+/// * `updated_at` - Asset updated at
+///
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct Asset {
+    /// CoinMarketCap id
+    pub cmc_id: i64,
+    /// Ticker symbol
+    pub symbol: String,
+    /// Asset name
+    pub name: String,
+    /// CoinMarketCap slug
+    pub slug: String,
+    /// CoinMarketCap rank
+    pub rank: Option<i64>,
+    /// Asset updated at
+    pub updated_at: bson::DateTime,
+}
+
+impl Asset {
+    /// Create new asset
+    ///
+    /// # Arguments
+    ///
+    /// * `cmc_id` - CoinMarketCap id
+    /// * `symbol` - Ticker symbol
+    /// * `name` - Asset name
+    /// * `slug` - CoinMarketCap slug
+    /// * `rank` - CoinMarketCap rank
+    pub fn new(cmc_id: i64, symbol: String, name: String, slug: String, rank: Option<i64>) -> Self {
+        Self {
+            cmc_id,
+            symbol,
+            name,
+            slug,
+            rank,
+            updated_at: bson::DateTime::now(),
+        }
+    }
+
+    /// Label used on disambiguation buttons, e.g. `Bitcoin (BTC) #1`
+    pub fn label(&self) -> String {
+        match self.rank {
+            Some(rank) => format!("{} ({}) #{}", self.name, self.symbol, rank),
+            None => format!("{} ({})", self.name, self.symbol),
+        }
+    }
+}
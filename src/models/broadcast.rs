@@ -0,0 +1,45 @@
+use mongodb::bson;
+use serde::{Deserialize, Serialize};
+
+/// Broadcast model
+///
+/// A digest or /sendall run that has not reached every user yet. It is saved
+/// while sending, so a broadcast interrupted by a restart resumes where it
+/// stopped instead of starting over.
+///
+/// # Fields
+///
+/// * `id` - Broadcast id, e.g. `digest-2024-05-07`
+/// * `text` - Message of /sendall, `None` for the digest built per user
+/// * `pending` - Users that have not received the message yet
+/// * `updated_at` - Broadcast updated at
+///
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct Broadcast {
+    /// Broadcast id
+    pub id: String,
+    /// Message of /sendall, `None` for the digest
+    pub text: Option<String>,
+    /// Users still waiting for the message
+    pub pending: Vec<i64>,
+    /// Broadcast updated at
+    pub updated_at: bson::DateTime,
+}
+
+impl Broadcast {
+    /// Create new broadcast
+    ///
+    /// # Arguments
+    ///
+    /// * `id` - Broadcast id
+    /// * `text` - Message of /sendall, `None` for the digest
+    /// * `pending` - Recipients
+    pub fn new(id: String, text: Option<String>, pending: Vec<i64>) -> Self {
+        Self {
+            id,
+            text,
+            pending,
+            updated_at: bson::DateTime::now(),
+        }
+    }
+}
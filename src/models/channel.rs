@@ -0,0 +1,105 @@
+use crate::i18n::Lang;
+use crate::models::preferences::Fiat;
+use chrono_tz::Tz;
+use mongodb::bson;
+use serde::{Deserialize, Serialize};
+
+/// Channel post schedule model
+///
+/// A market summary the bot publishes to a channel every day. A channel can
+/// have several schedules, e.g. a morning and an evening post.
+///
+/// # Fields
+///
+/// * `id` - Schedule id, `{chat_id}-{created_at millis}`
+/// * `chat_id` - Channel id
+/// * `title` - Channel title at the time the schedule was added
+/// * `symbols` - Coins of the summary, the first one gets the chart
+/// * `time` - Local post time, `HH:MM`
+/// * `timezone` - IANA time zone of `time`, `None` for the server's
+/// * `fiat` - Currency of the prices
+/// * `language` - Language of the posts
+/// * `chart` - Post a 24h chart of the first coin before the summary
+/// * `pin` - Pin the latest summary, unpinning the previous one
+/// * `last_message_id` - Latest pinned summary
+/// * `last_posted_at` - Latest scheduled summary, `None` before the first one
+/// * `created_at` - Schedule created at
+///
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ChannelPost {
+    /// Schedule id
+    pub id: String,
+    /// Channel id
+    pub chat_id: i64,
+    /// Channel title
+    #[serde(default)]
+    pub title: String,
+    /// Coins of the summary
+    pub symbols: Vec<String>,
+    /// Local post time
+    pub time: String,
+    /// Time zone of the post time
+    #[serde(default)]
+    pub timezone: Option<String>,
+    /// Currency of the prices
+    #[serde(default)]
+    pub fiat: Fiat,
+    /// Language of the posts
+    #[serde(default)]
+    pub language: Lang,
+    /// Post a chart
+    #[serde(default)]
+    pub chart: bool,
+    /// Pin the latest summary
+    #[serde(default)]
+    pub pin: bool,
+    /// Latest pinned summary
+    #[serde(default)]
+    pub last_message_id: Option<i32>,
+    /// Latest scheduled summary
+    #[serde(default)]
+    pub last_posted_at: Option<bson::DateTime>,
+    /// Schedule created at
+    pub created_at: bson::DateTime,
+}
+
+impl ChannelPost {
+    /// Create new schedule with a chart, unpinned, in USD
+    ///
+    /// # Arguments
+    ///
+    /// * `chat_id` - Channel id
+    /// * `title` - Channel title
+    /// * `symbols` - Coins of the summary
+    /// * `time` - Local post time, `HH:MM`
+    /// * `language` - Language of the posts
+    pub fn new(
+        chat_id: i64,
+        title: String,
+        symbols: Vec<String>,
+        time: String,
+        language: Lang,
+    ) -> Self {
+        let created_at = bson::DateTime::now();
+        Self {
+            id: format!("{}-{}", chat_id, created_at.timestamp_millis()),
+            chat_id,
+            title,
+            symbols,
+            time,
+            timezone: None,
+            fiat: Fiat::default(),
+            language,
+            chart: true,
+            pin: false,
+            last_message_id: None,
+            last_posted_at: None,
+            created_at,
+        }
+    }
+
+    /// Parsed time zone, `None` for the server's
+    pub fn tz(&self) -> Option<Tz> {
+        self.timezone.as_deref().and_then(|tz| tz.parse().ok())
+    }
+}
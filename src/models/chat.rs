@@ -0,0 +1,97 @@
+use crate::models::preferences::Preferences;
+use mongodb::bson;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::time::Duration;
+
+/// Cooldowns offered in the group /settings, in seconds
+pub const COOLDOWNS: [u64; 4] = [0, 30, 60, 300];
+
+/// Group chat model
+///
+/// Groups have their own watchlist and digest, set up by the chat admins.
+///
+/// # Fields
+///
+/// * `chat_id` - Chat id, negative for groups
+/// * `title` - Chat title, refreshed by every group command
+/// * `currency` - Group watchlist
+/// * `cmc_ids` - Resolved CoinMarketCap ids of the watchlist
+/// * `preferences` - Digest, fiat, chart and answer settings of the group
+/// * `mention_only` - Answer plain messages only when the bot is mentioned or replied to
+/// * `cooldown_secs` - Quiet time after an answer to a plain message, `None` for `groups.cooldown_secs`
+/// * `created_at` - Chat created at
+/// * `updated_at` - Chat updated at
+///
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Chat {
+    /// Chat id
+    pub chat_id: i64,
+    /// Chat title
+    #[serde(default)]
+    pub title: String,
+    /// Group watchlist
+    #[serde(default)]
+    pub currency: Vec<String>,
+    /// Resolved CoinMarketCap ids, keyed by currency symbol
+    #[serde(default, with = "crate::models::cmc_ids")]
+    pub cmc_ids: HashMap<String, i64>,
+    /// Group settings shared with the user preferences
+    #[serde(default)]
+    pub preferences: Preferences,
+    /// Mention or reply-only mode
+    #[serde(default)]
+    pub mention_only: bool,
+    /// Cooldown picked in /settings
+    #[serde(default)]
+    pub cooldown_secs: Option<u64>,
+    /// Chat created at
+    pub created_at: bson::DateTime,
+    /// Chat updated at
+    pub updated_at: bson::DateTime,
+}
+
+impl Chat {
+    /// Create new chat
+    ///
+    /// # Arguments
+    ///
+    /// * `chat_id` - Chat id
+    /// * `title` - Chat title
+    ///
+    pub fn new(chat_id: i64, title: String) -> Self {
+        Self {
+            chat_id,
+            title,
+            currency: vec![],
+            cmc_ids: HashMap::new(),
+            preferences: Preferences::default(),
+            mention_only: false,
+            cooldown_secs: None,
+            created_at: bson::DateTime::now(),
+            updated_at: bson::DateTime::now(),
+        }
+    }
+
+    /// Apply a change made in the group /settings
+    pub fn apply(&mut self, setting: &ChatSetting) {
+        match setting {
+            ChatSetting::MentionOnly(on) => self.mention_only = *on,
+            ChatSetting::Cooldown(secs) => self.cooldown_secs = *secs,
+        }
+    }
+
+    /// Quiet time after an answer, `default` unless the admins picked one
+    pub fn cooldown(&self, default: Duration) -> Duration {
+        self.cooldown_secs
+            .map(Duration::from_secs)
+            .unwrap_or(default)
+    }
+}
+
+/// One group-only setting change, the others are `Preference`s
+#[derive(Clone, Debug, PartialEq)]
+pub enum ChatSetting {
+    MentionOnly(bool),
+    Cooldown(Option<u64>),
+}
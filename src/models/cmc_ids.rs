@@ -0,0 +1,103 @@
+//! Resolved CoinMarketCap ids as stored in MongoDB, used with
+//! `#[serde(with = "crate::models::cmc_ids")]`
+//!
+//! Symbols are never document keys: tickers like `BTC.b` hold a dot, a path
+//! separator, and user input may hold a `$`. The ids are a list of
+//! `{symbol, cmc_id}` documents instead, changed with `$pull` and update pipelines.
+//! Documents written before keep a `{symbol: id}` sub-document, it is read as
+//! well.
+
+use mongodb::bson::{doc, Bson, Document};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::collections::HashMap;
+
+/// One stored id
+#[derive(Debug, Deserialize)]
+struct CmcId {
+    symbol: String,
+    cmc_id: i64,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum Stored {
+    List(Vec<CmcId>),
+    Map(HashMap<String, Bson>),
+}
+
+pub fn serialize<S: Serializer>(
+    ids: &HashMap<String, i64>,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    to_bson(ids).serialize(serializer)
+}
+
+pub fn deserialize<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<HashMap<String, i64>, D::Error> {
+    Ok(match Stored::deserialize(deserializer)? {
+        Stored::List(list) => list.into_iter().map(|id| (id.symbol, id.cmc_id)).collect(),
+        // Dotted symbols were stored as nested documents, they are left out
+        Stored::Map(map) => map
+            .into_iter()
+            .filter_map(|(symbol, id)| match id {
+                Bson::Int64(id) => Some((symbol, id)),
+                Bson::Int32(id) => Some((symbol, i64::from(id))),
+                _ => None,
+            })
+            .collect(),
+    })
+}
+
+/// Stored form of one id, for the watchlist updates
+pub fn entry(symbol: &str, cmc_id: i64) -> Document {
+    doc! {"symbol": symbol, "cmc_id": cmc_id}
+}
+
+/// Stored form of all ids, for whole documents
+pub fn to_bson(ids: &HashMap<String, i64>) -> Bson {
+    let mut list: Vec<(&String, &i64)> = ids.iter().collect();
+    list.sort();
+    Bson::Array(
+        list.into_iter()
+            .map(|(symbol, cmc_id)| Bson::Document(entry(symbol, *cmc_id)))
+            .collect(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mongodb::bson;
+
+    #[derive(Debug, Serialize, Deserialize)]
+    struct Owner {
+        #[serde(default, with = "super")]
+        cmc_ids: HashMap<String, i64>,
+    }
+
+    #[test]
+    fn test_cmc_ids_round_trip() {
+        let owner = Owner {
+            cmc_ids: HashMap::from([("BTC.b".to_string(), 1), ("USDC.e".to_string(), 2)]),
+        };
+        let stored = bson::to_document(&owner).unwrap();
+        assert_eq!(stored.get("cmc_ids"), Some(&to_bson(&owner.cmc_ids)));
+        let read: Owner = bson::from_document(stored).unwrap();
+        assert_eq!(read.cmc_ids, owner.cmc_ids);
+    }
+
+    #[test]
+    fn test_cmc_ids_legacy_document() {
+        // `cmc_ids.BTC.b` used to be stored as a nested document
+        let stored = doc! {"cmc_ids": {"ETH": 1027_i64, "UNI": 7083_i32, "BTC": {"b": 1}}};
+        let read: Owner = bson::from_document(stored).unwrap();
+        assert_eq!(
+            read.cmc_ids,
+            HashMap::from([("ETH".to_string(), 1027), ("UNI".to_string(), 7083)])
+        );
+
+        let read: Owner = bson::from_document(doc! {}).unwrap();
+        assert!(read.cmc_ids.is_empty());
+    }
+}
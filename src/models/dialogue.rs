@@ -0,0 +1,62 @@
+use mongodb::bson;
+use serde::{Deserialize, Serialize};
+
+/// Question the bot is waiting an answer to
+///
+/// Commands without arguments ask for them in the next message. The state is
+/// kept per chat and dropped by /cancel, the Cancel button, another command or
+/// after `dialogue.timeout_secs`.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "step", rename_all = "snake_case")]
+pub enum DialogueState {
+    /// No question asked
+    #[default]
+    Idle,
+    /// /addcurrency, waiting for symbols
+    AddCurrency,
+    /// /alert, waiting for a coin
+    AlertSymbol,
+    /// /alert, waiting for the target price of `symbol`
+    AlertPrice { symbol: String },
+}
+
+impl DialogueState {
+    /// Returns true if the bot waits for an answer
+    pub fn is_active(&self) -> bool {
+        *self != DialogueState::Idle
+    }
+}
+
+/// Dialogue model
+///
+/// # Fields
+///
+/// * `chat_id` - Chat the question was asked in
+/// * `state` - Question asked
+/// * `updated_at` - Time the question was asked, used for the timeout
+///
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct StoredDialogue {
+    /// Chat id
+    pub chat_id: i64,
+    /// Question asked
+    pub state: DialogueState,
+    /// Dialogue updated at
+    pub updated_at: bson::DateTime,
+}
+
+impl StoredDialogue {
+    /// Create new dialogue, asked now
+    ///
+    /// # Arguments
+    ///
+    /// * `chat_id` - Chat id
+    /// * `state` - Question asked
+    pub fn new(chat_id: i64, state: DialogueState) -> Self {
+        Self {
+            chat_id,
+            state,
+            updated_at: bson::DateTime::now(),
+        }
+    }
+}
@@ -1,3 +1,4 @@
+use crate::tools::http::HttpError;
 use std::fmt;
 
 #[derive(Debug)]
@@ -7,6 +8,7 @@ pub enum CustomError {
     InvalidHeader(reqwest::header::InvalidHeaderValue),
     HttpStatus(reqwest::StatusCode),
     Deserialize(serde_json::Error),
+    Http(HttpError),
 }
 
 // Updated Display implementation for CustomError
@@ -18,10 +20,13 @@ impl fmt::Display for CustomError {
             CustomError::InvalidHeader(e) => write!(f, "Invalid header error: {}", e),
             CustomError::HttpStatus(e) => write!(f, "HTTP status error: {}", e),
             CustomError::Deserialize(e) => write!(f, "Deserialize error: {}", e),
+            CustomError::Http(e) => write!(f, "HTTP error: {}", e),
         }
     }
 }
 
+impl std::error::Error for CustomError {}
+
 // Update the From implementations for CustomError
 impl From<regex::Error> for CustomError {
     fn from(err: regex::Error) -> CustomError {
@@ -46,3 +51,9 @@ impl From<serde_json::Error> for CustomError {
         CustomError::Deserialize(err)
     }
 }
+
+impl From<HttpError> for CustomError {
+    fn from(err: HttpError) -> CustomError {
+        CustomError::Http(err)
+    }
+}
@@ -1,2 +1,11 @@
+pub mod alert;
+pub mod asset;
+pub mod broadcast;
+pub mod channel;
+pub mod chat;
+pub mod cmc_ids;
+pub mod dialogue;
 pub mod errors;
+pub mod preferences;
+pub mod quota;
 pub mod user;
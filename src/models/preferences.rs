@@ -0,0 +1,234 @@
+use crate::i18n::Lang;
+use chrono_tz::Tz;
+use serde::{Deserialize, Serialize};
+
+/// Version of the user document layout, bumped with every migration
+///
+/// * `1` - Flat `notification` and `language` fields, documents without a version
+/// * `2` - `preferences` sub-document
+/// * `3` - `cmc_ids` list of `{symbol, cmc_id}`, see `models::cmc_ids`
+pub const USER_SCHEMA_VERSION: i32 = 3;
+
+/// Digest times offered in /settings
+pub const DIGEST_TIMES: [&str; 6] = ["07:00", "09:00", "11:00", "13:00", "18:00", "21:00"];
+
+/// Time zones offered in /settings
+pub const TIMEZONES: [&str; 8] = [
+    "UTC",
+    "Europe/London",
+    "Europe/Berlin",
+    "Europe/Kiev",
+    "Europe/Moscow",
+    "Asia/Dubai",
+    "Asia/Singapore",
+    "America/New_York",
+];
+
+/// `(hour, minute)` of a `HH:MM` time, `None` if it is not one
+pub fn parse_time(time: &str) -> Option<(u32, u32)> {
+    let (hour, minute) = time.split_once(':')?;
+    let hour: u32 = hour.trim().parse().ok()?;
+    let minute: u32 = minute.trim().parse().ok()?;
+    (hour <= 23 && minute <= 59).then_some((hour, minute))
+}
+
+/// Per-user preferences
+///
+/// # Fields
+///
+/// * `digest` - Daily digest on or off
+/// * `digest_time` - Local digest time, `HH:MM`, `None` for `schedule.digest_time`
+/// * `timezone` - IANA time zone of `digest_time`, `None` for the server's
+/// * `fiat` - Currency prices are shown in
+/// * `language` - Language picked with /language or detected on /start
+/// * `chart_theme` - Colours of the charts
+/// * `expanders` - Free-text parsers active for the user
+///
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Preferences {
+    pub digest: bool,
+    pub digest_time: Option<String>,
+    pub timezone: Option<String>,
+    pub fiat: Fiat,
+    pub language: Option<Lang>,
+    pub chart_theme: ChartTheme,
+    pub expanders: Expanders,
+}
+
+impl Preferences {
+    /// Apply a change made in /settings or /notify
+    pub fn apply(&mut self, preference: &Preference) {
+        match preference {
+            Preference::Digest(on) => self.digest = *on,
+            Preference::DigestTime(time) => self.digest_time = time.clone(),
+            Preference::Timezone(timezone) => self.timezone = timezone.clone(),
+            Preference::Fiat(fiat) => self.fiat = *fiat,
+            Preference::ChartTheme(theme) => self.chart_theme = *theme,
+            Preference::Expander(expander, on) => self.expanders.set(*expander, *on),
+        }
+    }
+
+    /// Parsed time zone, `None` for the server's
+    pub fn tz(&self) -> Option<Tz> {
+        self.timezone.as_deref().and_then(|tz| tz.parse().ok())
+    }
+
+    /// Local digest time, `default` unless the user picked a valid one
+    pub fn digest_at(&self, default: (u32, u32)) -> (u32, u32) {
+        self.digest_time
+            .as_deref()
+            .and_then(parse_time)
+            .unwrap_or(default)
+    }
+}
+
+/// One preference change
+///
+/// The language is changed through `UserRepository::set_language`.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Preference {
+    Digest(bool),
+    DigestTime(Option<String>),
+    Timezone(Option<String>),
+    Fiat(Fiat),
+    ChartTheme(ChartTheme),
+    Expander(Expander, bool),
+}
+
+/// Currency prices are shown in
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "UPPERCASE")]
+pub enum Fiat {
+    #[default]
+    Usd,
+    Eur,
+    Gbp,
+    Uah,
+}
+
+impl Fiat {
+    /// Every supported fiat currency
+    pub const ALL: [Fiat; 4] = [Fiat::Usd, Fiat::Eur, Fiat::Gbp, Fiat::Uah];
+
+    /// ISO 4217 code, as used by the price APIs
+    pub fn code(self) -> &'static str {
+        match self {
+            Fiat::Usd => "USD",
+            Fiat::Eur => "EUR",
+            Fiat::Gbp => "GBP",
+            Fiat::Uah => "UAH",
+        }
+    }
+
+    /// Currency sign shown before amounts
+    pub fn sign(self) -> &'static str {
+        match self {
+            Fiat::Usd => "$",
+            Fiat::Eur => "€",
+            Fiat::Gbp => "£",
+            Fiat::Uah => "₴",
+        }
+    }
+
+    /// Fiat currency of an ISO 4217 code
+    pub fn from_code(code: &str) -> Option<Fiat> {
+        Fiat::ALL
+            .into_iter()
+            .find(|fiat| fiat.code().eq_ignore_ascii_case(code))
+    }
+}
+
+/// Colours of the charts
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ChartTheme {
+    #[default]
+    Light,
+    Dark,
+}
+
+impl ChartTheme {
+    /// Stored name of the theme
+    pub fn code(self) -> &'static str {
+        match self {
+            ChartTheme::Light => "light",
+            ChartTheme::Dark => "dark",
+        }
+    }
+
+    /// Theme of a stored name
+    pub fn from_code(code: &str) -> Option<ChartTheme> {
+        [ChartTheme::Light, ChartTheme::Dark]
+            .into_iter()
+            .find(|theme| theme.code() == code)
+    }
+}
+
+/// Free-text parser a user can switch off
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Expander {
+    Currency,
+    Twitter,
+    Eden,
+}
+
+impl Expander {
+    /// Every expander, in the order of the settings buttons
+    pub const ALL: [Expander; 3] = [Expander::Currency, Expander::Twitter, Expander::Eden];
+
+    /// Field name in the `expanders` sub-document
+    pub fn code(self) -> &'static str {
+        match self {
+            Expander::Currency => "currency",
+            Expander::Twitter => "twitter",
+            Expander::Eden => "eden",
+        }
+    }
+
+    /// Expander of a field name
+    pub fn from_code(code: &str) -> Option<Expander> {
+        Expander::ALL
+            .into_iter()
+            .find(|expander| expander.code() == code)
+    }
+}
+
+/// Free-text parsers active for a user, on top of the `features` toggles
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Expanders {
+    pub currency: bool,
+    pub twitter: bool,
+    pub eden: bool,
+}
+
+impl Expanders {
+    /// Returns true if the expander is on
+    pub fn get(&self, expander: Expander) -> bool {
+        match expander {
+            Expander::Currency => self.currency,
+            Expander::Twitter => self.twitter,
+            Expander::Eden => self.eden,
+        }
+    }
+
+    /// Switch an expander on or off
+    pub fn set(&mut self, expander: Expander, on: bool) {
+        match expander {
+            Expander::Currency => self.currency = on,
+            Expander::Twitter => self.twitter = on,
+            Expander::Eden => self.eden = on,
+        }
+    }
+}
+
+impl Default for Expanders {
+    fn default() -> Self {
+        Self {
+            currency: true,
+            twitter: true,
+            eden: true,
+        }
+    }
+}
@@ -0,0 +1,23 @@
+use serde::{Deserialize, Serialize};
+
+/// Upstream API usage of one day
+///
+/// # Fields
+///
+/// * `provider` - Upstream, its `api` metrics label, e.g. `cmc`
+/// * `day` - UTC day, `YYYY-MM-DD`
+/// * `calls` - Requests sent, retries included
+/// * `credits` - Credits charged, as reported by the API where it does, one
+///   per request otherwise
+///
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct QuotaUsage {
+    /// Upstream
+    pub provider: String,
+    /// UTC day
+    pub day: String,
+    /// Requests sent
+    pub calls: i64,
+    /// Credits charged
+    pub credits: i64,
+}
@@ -1,8 +1,7 @@
-use crate::db::DatabaseManager;
-use log::debug;
+use crate::models::preferences::{Preferences, USER_SCHEMA_VERSION};
 use mongodb::bson;
 use serde::{Deserialize, Serialize};
-use std::error::Error;
+use std::collections::HashMap;
 
 /// User model
 ///
@@ -11,6 +10,9 @@ use std::error::Error;
 /// * `user_id` - User id
 /// * `username` - User username
 /// * `currency` - User currency
+/// * `cmc_ids` - Resolved CoinMarketCap ids of user currency
+/// * `preferences` - Digest, language and display preferences
+/// * `schema_version` - Layout of the stored document, see `USER_SCHEMA_VERSION`
 /// * `created_at` - User created at
 /// * `updated_at` - User updated at
 ///
@@ -27,12 +29,19 @@ pub struct User {
     pub username: String,
     /// User currency
     pub currency: Vec<String>,
+    /// Resolved CoinMarketCap ids, keyed by currency symbol
+    #[serde(default, with = "crate::models::cmc_ids")]
+    pub cmc_ids: HashMap<String, i64>,
+    /// User preferences, changed with /settings
+    #[serde(default)]
+    pub preferences: Preferences,
+    /// Document layout version, 0 for documents older than the versioning
+    #[serde(default)]
+    pub schema_version: i32,
     /// User created at
     pub created_at: bson::DateTime,
     /// User updated at
     pub updated_at: bson::DateTime,
-    /// User notification
-    pub notification: bool,
 }
 
 impl User {
@@ -44,24 +53,16 @@ impl User {
     /// * `username` - User username
     /// * `currency` - User currency
     ///
-
     pub fn new(user_id: i64, username: String, currency: Vec<String>) -> Self {
         Self {
             user_id,
             username,
             currency,
+            cmc_ids: HashMap::new(),
+            preferences: Preferences::default(),
+            schema_version: USER_SCHEMA_VERSION,
             created_at: bson::DateTime::now(),
             updated_at: bson::DateTime::now(),
-            notification: false,
-        }
-    }
-
-    pub async fn save(&self, db: DatabaseManager) -> Result<(), Box<dyn Error>> {
-        let res = db.insert_user(self.clone()).await;
-        match res {
-            Ok(_) => (),
-            Err(_) => debug!("user already exists"),
         }
-        Ok(())
     }
 }
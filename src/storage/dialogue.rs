@@ -0,0 +1,114 @@
+use crate::models::dialogue::{DialogueState, StoredDialogue};
+use crate::storage::DialogueRepository;
+use futures::future::BoxFuture;
+use mongodb::bson;
+use std::error::Error;
+use std::sync::Arc;
+use std::time::Duration;
+use teloxide::dispatching::dialogue::{Dialogue, Storage};
+use teloxide::types::ChatId;
+
+/// Dialogue of a chat, handed to the handlers by `enter_dialogue`
+pub type BotDialogue = Dialogue<DialogueState, DialogueStorage>;
+
+/// teloxide dialogue storage on top of a `DialogueRepository`
+///
+/// Dialogues survive restarts in the bot storage. A dialogue not answered
+/// within `timeout` is dropped when it is read next, so a late answer is
+/// handled as a plain message.
+pub struct DialogueStorage {
+    repository: Arc<dyn DialogueRepository>,
+    timeout: Duration,
+}
+
+impl DialogueStorage {
+    /// Storage dropping dialogues older than `timeout`
+    pub fn new(repository: Arc<dyn DialogueRepository>, timeout: Duration) -> Arc<Self> {
+        Arc::new(Self {
+            repository,
+            timeout,
+        })
+    }
+
+    fn is_expired(&self, dialogue: &StoredDialogue) -> bool {
+        let age = bson::DateTime::now().timestamp_millis() - dialogue.updated_at.timestamp_millis();
+        age > self.timeout.as_millis() as i64
+    }
+}
+
+impl Storage<DialogueState> for DialogueStorage {
+    type Error = Box<dyn Error + Send + Sync>;
+
+    fn remove_dialogue(
+        self: Arc<Self>,
+        chat_id: ChatId,
+    ) -> BoxFuture<'static, Result<(), Self::Error>> {
+        Box::pin(async move { self.repository.delete_dialogue(chat_id.0).await })
+    }
+
+    fn update_dialogue(
+        self: Arc<Self>,
+        chat_id: ChatId,
+        dialogue: DialogueState,
+    ) -> BoxFuture<'static, Result<(), Self::Error>> {
+        Box::pin(async move {
+            if dialogue.is_active() {
+                let stored = StoredDialogue::new(chat_id.0, dialogue);
+                self.repository.save_dialogue(&stored).await
+            } else {
+                self.repository.delete_dialogue(chat_id.0).await
+            }
+        })
+    }
+
+    fn get_dialogue(
+        self: Arc<Self>,
+        chat_id: ChatId,
+    ) -> BoxFuture<'static, Result<Option<DialogueState>, Self::Error>> {
+        Box::pin(async move {
+            match self.repository.get_dialogue(chat_id.0).await? {
+                Some(stored) if self.is_expired(&stored) => {
+                    self.repository.delete_dialogue(chat_id.0).await?;
+                    Ok(None)
+                }
+                Some(stored) => Ok(Some(stored.state)),
+                None => Ok(None),
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::memory::MemoryStorage;
+
+    #[tokio::test]
+    async fn test_dialogue_timeout() {
+        let repository = Arc::new(MemoryStorage::new());
+        let storage = DialogueStorage::new(repository.clone(), Duration::from_secs(60));
+        let dialogue = BotDialogue::new(storage, ChatId(1));
+        assert_eq!(
+            dialogue.get_or_default().await.unwrap(),
+            DialogueState::Idle
+        );
+
+        dialogue.update(DialogueState::AddCurrency).await.unwrap();
+        assert_eq!(
+            dialogue.get().await.unwrap(),
+            Some(DialogueState::AddCurrency)
+        );
+
+        // Asked two minutes ago
+        let mut stored = repository.get_dialogue(1).await.unwrap().unwrap();
+        stored.updated_at =
+            bson::DateTime::from_millis(stored.updated_at.timestamp_millis() - 120_000);
+        repository.save_dialogue(&stored).await.unwrap();
+        assert_eq!(dialogue.get().await.unwrap(), None);
+        assert!(repository.get_dialogue(1).await.unwrap().is_none());
+
+        dialogue.update(DialogueState::AlertSymbol).await.unwrap();
+        dialogue.exit().await.unwrap();
+        assert!(repository.get_dialogue(1).await.unwrap().is_none());
+    }
+}
@@ -0,0 +1,398 @@
+use crate::i18n::Lang;
+use crate::models::alert::Alert;
+use crate::models::asset::Asset;
+use crate::models::broadcast::Broadcast;
+use crate::models::channel::ChannelPost;
+use crate::models::chat::Chat;
+use crate::models::dialogue::StoredDialogue;
+use crate::models::preferences::{Preference, Preferences};
+use crate::models::quota::QuotaUsage;
+use crate::models::user::User;
+use crate::storage::{
+    AlertRepository, AssetRepository, BroadcastRepository, ChannelRepository, ChatRepository,
+    DialogueRepository, QuotaRepository, StorageResult, UserFilter, UserRepository,
+};
+use async_trait::async_trait;
+use mongodb::bson;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+/// In-memory storage
+///
+/// Behaves like the MongoDB storage but keeps everything in process, used by
+/// the offline tests.
+#[derive(Clone, Default)]
+pub struct MemoryStorage {
+    users: Arc<Mutex<HashMap<i64, User>>>,
+    assets: Arc<Mutex<Vec<Asset>>>,
+    broadcasts: Arc<Mutex<HashMap<String, Broadcast>>>,
+    dialogues: Arc<Mutex<HashMap<i64, StoredDialogue>>>,
+    alerts: Arc<Mutex<Vec<Alert>>>,
+    chats: Arc<Mutex<HashMap<i64, Chat>>>,
+    channels: Arc<Mutex<Vec<ChannelPost>>>,
+    quotas: Arc<Mutex<Vec<QuotaUsage>>>,
+}
+
+impl MemoryStorage {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+/// CoinMarketCap assets of the offline tests, `UNI` is shared by two coins
+pub fn test_assets() -> Vec<Asset> {
+    let asset = |cmc_id, symbol: &str, name: &str, rank| {
+        Asset::new(
+            cmc_id,
+            symbol.to_string(),
+            name.to_string(),
+            name.to_lowercase(),
+            Some(rank),
+        )
+    };
+    vec![
+        asset(1, "BTC", "Bitcoin", 1),
+        asset(1027, "ETH", "Ethereum", 2),
+        asset(7083, "UNI", "Uniswap", 20),
+        asset(5000, "UNI", "Universe", 900),
+    ]
+}
+
+/// Returns true if the user passes the filter
+fn matches_filter(filter: &UserFilter, user: &User) -> bool {
+    !matches!(filter.digest, Some(digest) if digest != user.preferences.digest)
+        && (!filter.with_currency || !user.currency.is_empty())
+        && filter
+            .digest_slots
+            .as_ref()
+            .is_none_or(|slots| slots.iter().any(|slot| slot.contains(&user.preferences)))
+}
+
+#[async_trait]
+impl UserRepository for MemoryStorage {
+    async fn insert_user(&self, user: User) -> StorageResult<bool> {
+        let mut users = self.users.lock().map_err(|err| err.to_string())?;
+        if users.contains_key(&user.user_id) {
+            return Ok(false);
+        }
+        users.insert(user.user_id, user);
+        Ok(true)
+    }
+
+    async fn get_user(&self, user_id: i64) -> Option<User> {
+        let mut users = self.users.lock().ok()?;
+        let user = users
+            .entry(user_id)
+            .or_insert_with(|| User::new(user_id, "".to_string(), vec![]));
+        Some(user.clone())
+    }
+
+    async fn change_user_currency(
+        &self,
+        user_id: i64,
+        currency: String,
+        cmc_id: Option<i64>,
+        max: usize,
+    ) -> StorageResult<bool> {
+        let mut users = self.users.lock().map_err(|err| err.to_string())?;
+        let user = users
+            .entry(user_id)
+            .or_insert_with(|| User::new(user_id, "".to_string(), vec![]));
+        if user.currency.len() >= max && !user.currency.contains(&currency) {
+            return Ok(false);
+        }
+        if let Some(cmc_id) = cmc_id {
+            user.cmc_ids.insert(currency.clone(), cmc_id);
+        }
+        if !user.currency.contains(&currency) {
+            user.currency.push(currency);
+        }
+        user.updated_at = bson::DateTime::now();
+        Ok(true)
+    }
+
+    async fn remove_user_currency(&self, user_id: i64, currency: String) -> StorageResult<()> {
+        let mut users = self.users.lock().map_err(|err| err.to_string())?;
+        let user = users.get_mut(&user_id).ok_or("user not found")?;
+        user.currency
+            .retain(|symbol| !symbol.eq_ignore_ascii_case(&currency));
+        user.cmc_ids
+            .retain(|symbol, _| !symbol.eq_ignore_ascii_case(&currency));
+        user.updated_at = bson::DateTime::now();
+        Ok(())
+    }
+
+    async fn get_all_users(&self, filter: UserFilter) -> StorageResult<Vec<User>> {
+        let users = self.users.lock().map_err(|err| err.to_string())?;
+        Ok(users
+            .values()
+            .filter(|user| matches_filter(&filter, user))
+            .cloned()
+            .collect())
+    }
+
+    async fn count_users(&self, filter: UserFilter) -> StorageResult<u64> {
+        let users = self.users.lock().map_err(|err| err.to_string())?;
+        Ok(users
+            .values()
+            .filter(|user| matches_filter(&filter, user))
+            .count() as u64)
+    }
+
+    async fn get_preferences(&self, user_id: i64) -> StorageResult<Option<Preferences>> {
+        let users = self.users.lock().map_err(|err| err.to_string())?;
+        Ok(users.get(&user_id).map(|user| user.preferences.clone()))
+    }
+
+    async fn set_preference(&self, user_id: i64, preference: Preference) -> StorageResult<()> {
+        let mut users = self.users.lock().map_err(|err| err.to_string())?;
+        let user = users
+            .entry(user_id)
+            .or_insert_with(|| User::new(user_id, "".to_string(), vec![]));
+        user.preferences.apply(&preference);
+        user.updated_at = bson::DateTime::now();
+        Ok(())
+    }
+
+    async fn get_language(&self, user_id: i64) -> StorageResult<Option<Lang>> {
+        let users = self.users.lock().map_err(|err| err.to_string())?;
+        Ok(users
+            .get(&user_id)
+            .and_then(|user| user.preferences.language))
+    }
+
+    async fn set_language(&self, user_id: i64, language: Lang) -> StorageResult<()> {
+        let mut users = self.users.lock().map_err(|err| err.to_string())?;
+        let user = users
+            .entry(user_id)
+            .or_insert_with(|| User::new(user_id, "".to_string(), vec![]));
+        user.preferences.language = Some(language);
+        user.updated_at = bson::DateTime::now();
+        Ok(())
+    }
+
+    async fn ping(&self) -> StorageResult<()> {
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl AssetRepository for MemoryStorage {
+    async fn replace_assets(&self, assets: Vec<Asset>) -> StorageResult<()> {
+        let mut cached = self.assets.lock().map_err(|err| err.to_string())?;
+        *cached = assets;
+        Ok(())
+    }
+
+    async fn get_all_assets(&self) -> StorageResult<Vec<Asset>> {
+        let cached = self.assets.lock().map_err(|err| err.to_string())?;
+        Ok(cached.clone())
+    }
+}
+
+#[async_trait]
+impl BroadcastRepository for MemoryStorage {
+    async fn save_broadcast(&self, broadcast: &Broadcast) -> StorageResult<()> {
+        let mut broadcasts = self.broadcasts.lock().map_err(|err| err.to_string())?;
+        broadcasts.insert(broadcast.id.clone(), broadcast.clone());
+        Ok(())
+    }
+
+    async fn get_broadcasts(&self) -> StorageResult<Vec<Broadcast>> {
+        let broadcasts = self.broadcasts.lock().map_err(|err| err.to_string())?;
+        Ok(broadcasts.values().cloned().collect())
+    }
+
+    async fn delete_broadcast(&self, id: &str) -> StorageResult<()> {
+        let mut broadcasts = self.broadcasts.lock().map_err(|err| err.to_string())?;
+        broadcasts.remove(id);
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl DialogueRepository for MemoryStorage {
+    async fn get_dialogue(&self, chat_id: i64) -> StorageResult<Option<StoredDialogue>> {
+        let dialogues = self.dialogues.lock().map_err(|err| err.to_string())?;
+        Ok(dialogues.get(&chat_id).cloned())
+    }
+
+    async fn save_dialogue(&self, dialogue: &StoredDialogue) -> StorageResult<()> {
+        let mut dialogues = self.dialogues.lock().map_err(|err| err.to_string())?;
+        dialogues.insert(dialogue.chat_id, dialogue.clone());
+        Ok(())
+    }
+
+    async fn delete_dialogue(&self, chat_id: i64) -> StorageResult<()> {
+        let mut dialogues = self.dialogues.lock().map_err(|err| err.to_string())?;
+        dialogues.remove(&chat_id);
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl AlertRepository for MemoryStorage {
+    async fn add_alert(&self, alert: &Alert) -> StorageResult<()> {
+        let mut alerts = self.alerts.lock().map_err(|err| err.to_string())?;
+        alerts.push(alert.clone());
+        Ok(())
+    }
+
+    async fn get_alerts(&self) -> StorageResult<Vec<Alert>> {
+        let alerts = self.alerts.lock().map_err(|err| err.to_string())?;
+        Ok(alerts.clone())
+    }
+
+    async fn get_user_alerts(&self, user_id: i64) -> StorageResult<Vec<Alert>> {
+        let alerts = self.alerts.lock().map_err(|err| err.to_string())?;
+        Ok(alerts
+            .iter()
+            .filter(|alert| alert.user_id == user_id)
+            .cloned()
+            .collect())
+    }
+
+    async fn delete_alert(&self, id: &str) -> StorageResult<bool> {
+        let mut alerts = self.alerts.lock().map_err(|err| err.to_string())?;
+        let count = alerts.len();
+        alerts.retain(|alert| alert.id != id);
+        Ok(alerts.len() < count)
+    }
+}
+
+#[async_trait]
+impl ChatRepository for MemoryStorage {
+    async fn get_chat(&self, chat_id: i64) -> StorageResult<Option<Chat>> {
+        let chats = self.chats.lock().map_err(|err| err.to_string())?;
+        Ok(chats.get(&chat_id).cloned())
+    }
+
+    async fn save_chat(&self, chat: &Chat) -> StorageResult<()> {
+        let mut chats = self.chats.lock().map_err(|err| err.to_string())?;
+        match chats.get_mut(&chat.chat_id) {
+            Some(stored) => {
+                stored.title = chat.title.clone();
+                stored.preferences = chat.preferences.clone();
+                stored.mention_only = chat.mention_only;
+                stored.cooldown_secs = chat.cooldown_secs;
+                stored.updated_at = chat.updated_at;
+            }
+            None => {
+                chats.insert(chat.chat_id, chat.clone());
+            }
+        }
+        Ok(())
+    }
+
+    async fn add_chat_currency(
+        &self,
+        chat: &Chat,
+        currency: String,
+        cmc_id: Option<i64>,
+        max: usize,
+    ) -> StorageResult<bool> {
+        let mut chats = self.chats.lock().map_err(|err| err.to_string())?;
+        let stored = chats.entry(chat.chat_id).or_insert_with(|| Chat {
+            currency: vec![],
+            cmc_ids: HashMap::new(),
+            ..chat.clone()
+        });
+        stored.title = chat.title.clone();
+        if stored.currency.len() >= max && !stored.currency.contains(&currency) {
+            return Ok(false);
+        }
+        if let Some(cmc_id) = cmc_id {
+            stored.cmc_ids.insert(currency.clone(), cmc_id);
+        }
+        if !stored.currency.contains(&currency) {
+            stored.currency.push(currency);
+        }
+        stored.updated_at = bson::DateTime::now();
+        Ok(true)
+    }
+
+    async fn remove_chat_currency(&self, chat_id: i64, currency: String) -> StorageResult<()> {
+        let mut chats = self.chats.lock().map_err(|err| err.to_string())?;
+        if let Some(chat) = chats.get_mut(&chat_id) {
+            chat.currency
+                .retain(|symbol| !symbol.eq_ignore_ascii_case(&currency));
+            chat.cmc_ids
+                .retain(|symbol, _| !symbol.eq_ignore_ascii_case(&currency));
+            chat.updated_at = bson::DateTime::now();
+        }
+        Ok(())
+    }
+
+    async fn get_digest_chats(&self) -> StorageResult<Vec<Chat>> {
+        let chats = self.chats.lock().map_err(|err| err.to_string())?;
+        Ok(chats
+            .values()
+            .filter(|chat| chat.preferences.digest && !chat.currency.is_empty())
+            .cloned()
+            .collect())
+    }
+}
+
+#[async_trait]
+impl ChannelRepository for MemoryStorage {
+    async fn add_channel_post(&self, post: &ChannelPost) -> StorageResult<()> {
+        let mut channels = self.channels.lock().map_err(|err| err.to_string())?;
+        channels.push(post.clone());
+        Ok(())
+    }
+
+    async fn get_channel_posts(&self) -> StorageResult<Vec<ChannelPost>> {
+        let channels = self.channels.lock().map_err(|err| err.to_string())?;
+        Ok(channels.clone())
+    }
+
+    async fn delete_channel_post(&self, id: &str) -> StorageResult<bool> {
+        let mut channels = self.channels.lock().map_err(|err| err.to_string())?;
+        let count = channels.len();
+        channels.retain(|post| post.id != id);
+        Ok(channels.len() < count)
+    }
+
+    async fn set_last_message(&self, id: &str, message_id: i32) -> StorageResult<()> {
+        let mut channels = self.channels.lock().map_err(|err| err.to_string())?;
+        if let Some(post) = channels.iter_mut().find(|post| post.id == id) {
+            post.last_message_id = Some(message_id);
+        }
+        Ok(())
+    }
+
+    async fn set_last_posted(&self, id: &str, at: bson::DateTime) -> StorageResult<()> {
+        let mut channels = self.channels.lock().map_err(|err| err.to_string())?;
+        if let Some(post) = channels.iter_mut().find(|post| post.id == id) {
+            post.last_posted_at = Some(at);
+        }
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl QuotaRepository for MemoryStorage {
+    async fn add_usage(&self, usage: &QuotaUsage) -> StorageResult<()> {
+        let mut quotas = self.quotas.lock().map_err(|err| err.to_string())?;
+        match quotas
+            .iter_mut()
+            .find(|stored| stored.provider == usage.provider && stored.day == usage.day)
+        {
+            Some(stored) => {
+                stored.calls += usage.calls;
+                stored.credits += usage.credits;
+            }
+            None => quotas.push(usage.clone()),
+        }
+        Ok(())
+    }
+
+    async fn get_usage(&self, since: &str) -> StorageResult<Vec<QuotaUsage>> {
+        let quotas = self.quotas.lock().map_err(|err| err.to_string())?;
+        Ok(quotas
+            .iter()
+            .filter(|usage| usage.day.as_str() >= since)
+            .cloned()
+            .collect())
+    }
+}
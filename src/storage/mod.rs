@@ -0,0 +1,259 @@
+pub mod dialogue;
+#[cfg(test)]
+pub mod memory;
+#[cfg(feature = "sql")]
+pub mod sql;
+pub mod watchlist;
+
+use crate::i18n::Lang;
+use crate::models::alert::Alert;
+use crate::models::asset::Asset;
+use crate::models::broadcast::Broadcast;
+use crate::models::channel::ChannelPost;
+use crate::models::chat::Chat;
+use crate::models::dialogue::StoredDialogue;
+use crate::models::preferences::{Preference, Preferences};
+use crate::models::quota::QuotaUsage;
+use crate::models::user::User;
+use async_trait::async_trait;
+use mongodb::bson;
+use std::error::Error;
+use std::sync::Arc;
+
+/// Result of a storage operation
+pub type StorageResult<T> = Result<T, Box<dyn Error + Send + Sync>>;
+
+/// Repositories of the selected backend
+#[derive(Clone)]
+pub struct Storage {
+    pub users: Arc<dyn UserRepository>,
+    pub assets: Arc<dyn AssetRepository>,
+    pub broadcasts: Arc<dyn BroadcastRepository>,
+    pub dialogues: Arc<dyn DialogueRepository>,
+    pub alerts: Arc<dyn AlertRepository>,
+    pub chats: Arc<dyn ChatRepository>,
+    pub channels: Arc<dyn ChannelRepository>,
+    pub quotas: Arc<dyn QuotaRepository>,
+}
+
+impl Storage {
+    /// Use one backend for every repository
+    pub fn new<S>(db: S) -> Self
+    where
+        S: UserRepository
+            + AssetRepository
+            + BroadcastRepository
+            + DialogueRepository
+            + AlertRepository
+            + ChatRepository
+            + ChannelRepository
+            + QuotaRepository
+            + Clone
+            + 'static,
+    {
+        Self {
+            users: Arc::new(db.clone()),
+            assets: Arc::new(db.clone()),
+            broadcasts: Arc::new(db.clone()),
+            dialogues: Arc::new(db.clone()),
+            alerts: Arc::new(db.clone()),
+            chats: Arc::new(db.clone()),
+            channels: Arc::new(db.clone()),
+            quotas: Arc::new(db),
+        }
+    }
+}
+
+/// Filter for `UserRepository::get_all_users`
+///
+/// # Fields
+///
+/// * `digest` - Only users with the daily digest on or off
+/// * `with_currency` - Only users with at least one currency in the list
+/// * `digest_slots` - Only users whose digest time and time zone are in one
+///   of the slots
+///
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct UserFilter {
+    pub digest: Option<bool>,
+    pub with_currency: bool,
+    pub digest_slots: Option<Vec<DigestSlot>>,
+}
+
+/// Digest times and the time zones where it is one of them
+///
+/// # Fields
+///
+/// * `times` - `HH:MM` digest times, `None` for the default time
+/// * `timezones` - IANA time zones, `None` for the server's
+///
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct DigestSlot {
+    pub times: Vec<Option<String>>,
+    pub timezones: Vec<Option<String>>,
+}
+
+#[cfg(test)]
+impl DigestSlot {
+    /// Returns true if the preferences are in the slot, the storages match it in their queries
+    pub fn contains(&self, preferences: &Preferences) -> bool {
+        self.times.contains(&preferences.digest_time)
+            && self.timezones.contains(&preferences.timezone)
+    }
+}
+
+/// Storage of users and their watchlists
+///
+/// # Methods
+///
+/// * `insert_user` - Insert user, `Ok(false)` if the user already exists
+/// * `get_user` - Get user, creating an empty one on first access
+/// * `change_user_currency` - Add currency to the watchlist of at most `max`
+///   coins, `Ok(false)` if it is full
+/// * `remove_user_currency` - Remove currency from the watchlist, case-insensitive
+/// * `get_all_users` - Get users matching the filter
+/// * `count_users` - Count users matching the filter, without loading them
+/// * `get_preferences` - Get the stored preferences, without creating the user
+/// * `set_preference` - Change one preference, creating the user if needed
+/// * `get_language` - Get the stored language, without creating the user
+/// * `set_language` - Store the language, creating the user if needed
+/// * `ping` - Check that the storage is reachable
+///
+#[async_trait]
+pub trait UserRepository: Send + Sync {
+    async fn insert_user(&self, user: User) -> StorageResult<bool>;
+
+    async fn get_user(&self, user_id: i64) -> Option<User>;
+
+    async fn change_user_currency(
+        &self,
+        user_id: i64,
+        currency: String,
+        cmc_id: Option<i64>,
+        max: usize,
+    ) -> StorageResult<bool>;
+
+    async fn remove_user_currency(&self, user_id: i64, currency: String) -> StorageResult<()>;
+
+    async fn get_all_users(&self, filter: UserFilter) -> StorageResult<Vec<User>>;
+
+    async fn count_users(&self, filter: UserFilter) -> StorageResult<u64>;
+
+    async fn get_preferences(&self, user_id: i64) -> StorageResult<Option<Preferences>>;
+
+    async fn set_preference(&self, user_id: i64, preference: Preference) -> StorageResult<()>;
+
+    async fn get_language(&self, user_id: i64) -> StorageResult<Option<Lang>>;
+
+    async fn set_language(&self, user_id: i64, language: Lang) -> StorageResult<()>;
+
+    async fn ping(&self) -> StorageResult<()>;
+}
+
+/// Storage of the cached CoinMarketCap asset map
+#[async_trait]
+pub trait AssetRepository: Send + Sync {
+    /// Replace all cached assets
+    async fn replace_assets(&self, assets: Vec<Asset>) -> StorageResult<()>;
+
+    /// Get all cached assets
+    async fn get_all_assets(&self) -> StorageResult<Vec<Asset>>;
+}
+
+/// Storage of unfinished broadcasts
+#[async_trait]
+pub trait BroadcastRepository: Send + Sync {
+    /// Insert or replace the broadcast with the same id
+    async fn save_broadcast(&self, broadcast: &Broadcast) -> StorageResult<()>;
+
+    /// Get all unfinished broadcasts
+    async fn get_broadcasts(&self) -> StorageResult<Vec<Broadcast>>;
+
+    /// Remove a finished broadcast
+    async fn delete_broadcast(&self, id: &str) -> StorageResult<()>;
+}
+
+/// Storage of open dialogues, see `storage::dialogue::DialogueStorage`
+#[async_trait]
+pub trait DialogueRepository: Send + Sync {
+    /// Get the dialogue of a chat
+    async fn get_dialogue(&self, chat_id: i64) -> StorageResult<Option<StoredDialogue>>;
+
+    /// Insert or replace the dialogue of a chat
+    async fn save_dialogue(&self, dialogue: &StoredDialogue) -> StorageResult<()>;
+
+    /// Remove the dialogue of a chat
+    async fn delete_dialogue(&self, chat_id: i64) -> StorageResult<()>;
+}
+
+/// Storage of price alerts
+#[async_trait]
+pub trait AlertRepository: Send + Sync {
+    /// Insert an alert
+    async fn add_alert(&self, alert: &Alert) -> StorageResult<()>;
+
+    /// Get every alert, for the alert check
+    async fn get_alerts(&self) -> StorageResult<Vec<Alert>>;
+
+    /// Get the alerts of a user, oldest first
+    async fn get_user_alerts(&self, user_id: i64) -> StorageResult<Vec<Alert>>;
+
+    /// Remove an alert, `Ok(false)` if it was already gone
+    async fn delete_alert(&self, id: &str) -> StorageResult<bool>;
+}
+
+/// Storage of group chats
+#[async_trait]
+pub trait ChatRepository: Send + Sync {
+    /// Get a group chat, `None` before its first setting or watchlist change
+    async fn get_chat(&self, chat_id: i64) -> StorageResult<Option<Chat>>;
+
+    /// Insert the chat or update its title and settings, a stored watchlist
+    /// is left as it is
+    async fn save_chat(&self, chat: &Chat) -> StorageResult<()>;
+
+    /// Add currency to the watchlist of at most `max` coins, inserting the
+    /// chat if needed, `Ok(false)` if the list is full
+    async fn add_chat_currency(
+        &self,
+        chat: &Chat,
+        currency: String,
+        cmc_id: Option<i64>,
+        max: usize,
+    ) -> StorageResult<bool>;
+
+    /// Remove currency from the watchlist, case-insensitive
+    async fn remove_chat_currency(&self, chat_id: i64, currency: String) -> StorageResult<()>;
+
+    /// Get the chats with the daily digest on and a watchlist
+    async fn get_digest_chats(&self) -> StorageResult<Vec<Chat>>;
+}
+
+/// Storage of the channel post schedules
+#[async_trait]
+pub trait ChannelRepository: Send + Sync {
+    /// Insert a schedule
+    async fn add_channel_post(&self, post: &ChannelPost) -> StorageResult<()>;
+
+    /// Get every schedule, oldest first
+    async fn get_channel_posts(&self) -> StorageResult<Vec<ChannelPost>>;
+
+    /// Remove a schedule, `Ok(false)` if it was already gone
+    async fn delete_channel_post(&self, id: &str) -> StorageResult<bool>;
+
+    /// Remember the latest pinned post of a schedule
+    async fn set_last_message(&self, id: &str, message_id: i32) -> StorageResult<()>;
+
+    /// Remember when a schedule was last published
+    async fn set_last_posted(&self, id: &str, at: bson::DateTime) -> StorageResult<()>;
+}
+
+/// Storage of the upstream API usage
+#[async_trait]
+pub trait QuotaRepository: Send + Sync {
+    /// Add the calls and credits to the usage of the provider on the day
+    async fn add_usage(&self, usage: &QuotaUsage) -> StorageResult<()>;
+
+    /// Get the usage of every provider since a day, inclusive
+    async fn get_usage(&self, since: &str) -> StorageResult<Vec<QuotaUsage>>;
+}
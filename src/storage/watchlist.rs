@@ -0,0 +1,92 @@
+use crate::models::chat::Chat;
+use crate::storage::{ChatRepository, StorageResult, UserRepository};
+use async_trait::async_trait;
+use std::sync::Arc;
+
+/// Watchlist of a user or a group chat, changed by /addcurrency and /removecurrency
+///
+/// # Methods
+///
+/// * `symbols` - Coins in the list, `None` if the owner can't be loaded
+/// * `add` - Add a coin with its resolved CoinMarketCap id to a list of at
+///   most `max` coins, `Ok(false)` if the list is full
+/// * `remove` - Remove a coin, case-insensitive
+///
+#[async_trait]
+pub trait Watchlist: Send + Sync {
+    async fn symbols(&self) -> Option<Vec<String>>;
+
+    async fn add(&self, symbol: String, cmc_id: Option<i64>, max: usize) -> StorageResult<bool>;
+
+    async fn remove(&self, symbol: String) -> StorageResult<()>;
+}
+
+/// Watchlist of a user
+pub struct UserWatchlist {
+    pub users: Arc<dyn UserRepository>,
+    pub user_id: i64,
+}
+
+#[async_trait]
+impl Watchlist for UserWatchlist {
+    async fn symbols(&self) -> Option<Vec<String>> {
+        self.users
+            .get_user(self.user_id)
+            .await
+            .map(|user| user.currency)
+    }
+
+    async fn add(&self, symbol: String, cmc_id: Option<i64>, max: usize) -> StorageResult<bool> {
+        self.users
+            .change_user_currency(self.user_id, symbol, cmc_id, max)
+            .await
+    }
+
+    async fn remove(&self, symbol: String) -> StorageResult<()> {
+        self.users.remove_user_currency(self.user_id, symbol).await
+    }
+}
+
+/// Watchlist of a group chat
+///
+/// # Fields
+///
+/// * `chats` - Chat storage
+/// * `chat` - Chat as seen in the update, stored on the first change
+///
+pub struct ChatWatchlist {
+    pub chats: Arc<dyn ChatRepository>,
+    pub chat: Chat,
+}
+
+impl ChatWatchlist {
+    /// Stored chat with the current title
+    async fn chat(&self) -> StorageResult<Chat> {
+        Ok(match self.chats.get_chat(self.chat.chat_id).await? {
+            Some(chat) => Chat {
+                title: self.chat.title.clone(),
+                ..chat
+            },
+            None => self.chat.clone(),
+        })
+    }
+}
+
+#[async_trait]
+impl Watchlist for ChatWatchlist {
+    async fn symbols(&self) -> Option<Vec<String>> {
+        self.chat().await.ok().map(|chat| chat.currency)
+    }
+
+    async fn add(&self, symbol: String, cmc_id: Option<i64>, max: usize) -> StorageResult<bool> {
+        self.chats
+            .add_chat_currency(&self.chat, symbol, cmc_id, max)
+            .await
+    }
+
+    async fn remove(&self, symbol: String) -> StorageResult<()> {
+        self.chats
+            .remove_chat_currency(self.chat.chat_id, symbol)
+            .await
+    }
+}
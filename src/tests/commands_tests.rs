@@ -0,0 +1,215 @@
+use crate::commands::currency::{
+    add_currency_command, remove_currency_command, select_asset_command, AddCurrencyReply,
+};
+use crate::commands::language::set_language_command;
+use crate::commands::notify::notify_command;
+use crate::commands::start::start_command;
+use crate::config::Config;
+use crate::i18n::Lang;
+use crate::storage::memory::{test_assets, MemoryStorage};
+use crate::storage::{UserFilter, UserRepository};
+use crate::tools::asset_registry::AssetRegistry;
+use std::sync::Arc;
+
+fn storage() -> (Arc<dyn UserRepository>, AssetRegistry) {
+    let storage = Arc::new(MemoryStorage::new());
+    let registry = AssetRegistry::with_assets(storage.clone(), test_assets());
+    (storage, registry)
+}
+
+fn text(reply: AddCurrencyReply) -> String {
+    match reply {
+        AddCurrencyReply::Text(text) => text,
+        AddCurrencyReply::Choose(text, _) => panic!("unexpected keyboard: {}", text),
+    }
+}
+
+#[tokio::test]
+async fn test_start_registers_user_once() {
+    let (db, _) = storage();
+    let result = start_command(1, "alice".to_string(), None, db.clone()).await;
+    assert_eq!(result, "Hello with start");
+    start_command(1, "alice".to_string(), None, db.clone()).await;
+
+    let users = db.get_all_users(UserFilter::default()).await.unwrap();
+    assert_eq!(users.len(), 1);
+    assert_eq!(users[0].username, "alice");
+}
+
+#[tokio::test]
+async fn test_add_currency_several_symbols() {
+    let (db, registry) = storage();
+    let result = text(
+        add_currency_command(
+            1,
+            "btc eth".to_string(),
+            Lang::En,
+            db.clone(),
+            registry,
+            &Config::default(),
+        )
+        .await,
+    );
+    assert!(result.contains("BTC, ETH"));
+
+    let user = db.get_user(1).await.unwrap();
+    assert_eq!(user.currency, vec!["BTC".to_string(), "ETH".to_string()]);
+    assert_eq!(user.cmc_ids.get("ETH"), Some(&1027));
+}
+
+#[tokio::test]
+async fn test_add_currency_resolved_duplicate_and_full() {
+    let (db, registry) = storage();
+    let mut config = Config::default();
+    config.max_watchlist_size = 1;
+    // A name resolving to a listed symbol is no new coin
+    let result = text(
+        add_currency_command(
+            1,
+            "btc bitcoin".to_string(),
+            Lang::En,
+            db.clone(),
+            registry.clone(),
+            &config,
+        )
+        .await,
+    );
+    assert!(result.contains("BTC is already in your list"));
+
+    let result = text(
+        add_currency_command(
+            1,
+            "ethereum".to_string(),
+            Lang::En,
+            db.clone(),
+            registry,
+            &config,
+        )
+        .await,
+    );
+    assert!(!result.contains("Added"));
+    assert!(result.contains("ETH"));
+    let user = db.get_user(1).await.unwrap();
+    assert_eq!(user.currency, vec!["BTC".to_string()]);
+}
+
+#[tokio::test]
+async fn test_add_currency_unknown_symbol_suggests() {
+    let (db, registry) = storage();
+    let result = text(
+        add_currency_command(
+            1,
+            "btcc".to_string(),
+            Lang::En,
+            db.clone(),
+            registry,
+            &Config::default(),
+        )
+        .await,
+    );
+    assert_eq!(result, "Unknown coin BTCC, did you mean BTC?");
+    assert!(db.get_user(1).await.unwrap().currency.is_empty());
+}
+
+#[tokio::test]
+async fn test_add_currency_ambiguous_symbol_then_pick() {
+    let (db, registry) = storage();
+    let reply = add_currency_command(
+        1,
+        "uni".to_string(),
+        Lang::En,
+        db.clone(),
+        registry.clone(),
+        &Config::default(),
+    )
+    .await;
+    assert!(matches!(reply, AddCurrencyReply::Choose(_, _)));
+    assert!(db.get_user(1).await.unwrap().currency.is_empty());
+
+    let result =
+        select_asset_command(1, 5000, Lang::En, db.clone(), registry, &Config::default()).await;
+    assert!(result.contains("Universe"));
+    let user = db.get_user(1).await.unwrap();
+    assert_eq!(user.cmc_ids.get("UNI"), Some(&5000));
+}
+
+#[tokio::test]
+async fn test_remove_currency() {
+    let (db, registry) = storage();
+    add_currency_command(
+        1,
+        "btc eth".to_string(),
+        Lang::En,
+        db.clone(),
+        registry,
+        &Config::default(),
+    )
+    .await;
+
+    let result = remove_currency_command(1, "btc".to_string(), Lang::Ru, db.clone()).await;
+    assert_eq!(result, "Удалили валюту \"btc\"");
+    let user = db.get_user(1).await.unwrap();
+    assert_eq!(user.currency, vec!["ETH".to_string()]);
+    assert!(!user.cmc_ids.contains_key("BTC"));
+}
+
+#[tokio::test]
+async fn test_notify_on_off() {
+    let (db, registry) = storage();
+    add_currency_command(
+        1,
+        "btc".to_string(),
+        Lang::En,
+        db.clone(),
+        registry,
+        &Config::default(),
+    )
+    .await;
+
+    assert_eq!(
+        notify_command(1, "on", Lang::En, db.clone()).await,
+        "successfully turned on"
+    );
+    let filter = UserFilter {
+        digest: Some(true),
+        with_currency: true,
+        digest_slots: None,
+    };
+    assert_eq!(db.get_all_users(filter.clone()).await.unwrap().len(), 1);
+    assert_eq!(db.count_users(filter.clone()).await.unwrap(), 1);
+
+    // Explicit states, a repeated "on" keeps the digest on
+    notify_command(1, "on", Lang::En, db.clone()).await;
+    assert_eq!(db.get_all_users(filter.clone()).await.unwrap().len(), 1);
+    assert_eq!(
+        notify_command(1, "", Lang::En, db.clone()).await,
+        Lang::En.tr("notify-state-on")
+    );
+
+    assert_eq!(
+        notify_command(1, "OFF", Lang::En, db.clone()).await,
+        "successfully turned off"
+    );
+    assert!(db.get_all_users(filter).await.unwrap().is_empty());
+    assert_eq!(
+        notify_command(1, "maybe", Lang::En, db.clone()).await,
+        Lang::En.tr("notify-usage")
+    );
+}
+
+#[tokio::test]
+async fn test_start_detects_language() {
+    let (db, _) = storage();
+    let result = start_command(1, "alice".to_string(), Some("uk-UA"), db.clone()).await;
+    assert!(result.starts_with("Привіт"));
+    assert_eq!(db.get_language(1).await.unwrap(), Some(Lang::Uk));
+
+    // A picked language survives the next /start
+    let result = set_language_command(1, Lang::Ru, db.clone()).await;
+    assert_eq!(result, "Язык изменён на русский");
+    start_command(1, "alice".to_string(), Some("en"), db.clone()).await;
+    assert_eq!(
+        db.get_user(1).await.unwrap().preferences.language,
+        Some(Lang::Ru)
+    );
+}
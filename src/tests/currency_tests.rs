@@ -1,15 +1,29 @@
+use crate::commands::currency::parse_symbols;
 use crate::commands::price::price_command;
-use crate::commands::price_all::price_all_command;
+use crate::commands::price_all::{price_all_command, select_entry};
+use crate::config::Config;
+use crate::i18n::Lang;
+use crate::models::preferences::Fiat;
 use crate::models::user::User;
+use crate::storage::memory::{test_assets, MemoryStorage};
+use crate::tools::asset_registry::AssetRegistry;
 use crate::tools::parse_currency::parse_currency;
 use dotenvy::dotenv;
+use std::sync::Arc;
 //use super::*;
 
 #[tokio::test]
 async fn test_price_command_invalid_currency() {
     dotenv().ok();
     let currency = "CURRENCY".to_string();
-    let result = price_command(currency).await;
+    let result = price_command(
+        currency,
+        Lang::En,
+        Fiat::Usd,
+        &AssetRegistry::with_assets(Arc::new(MemoryStorage::new()), vec![]),
+        &Config::from_env(),
+    )
+    .await;
     assert_eq!(
         result,
         "Error fetching data for CURRENCY: Currency not found"
@@ -32,7 +46,7 @@ async fn test_price_all_command_contain() {
         "".to_string(),
         vec!["BTC".to_string(), "ETH".to_string()],
     );
-    let result = price_all_command(user).await;
+    let result = price_all_command(user, Lang::En, &Config::from_env()).await;
     assert!(result.to_lowercase().contains("btc"));
 }
 
@@ -44,25 +58,32 @@ async fn test_price_all_command_not_contain() {
         "".to_string(),
         vec!["BTC".to_string(), "NOTREALCURRENCY".to_string()],
     );
-    let result = price_all_command(user).await;
+    let result = price_all_command(user, Lang::En, &Config::from_env()).await;
     assert!(!result.to_lowercase().contains("NOTREALCURRENCY"));
 }
 
 #[tokio::test]
 async fn test_price_all_command_empty() {
     let user = User::new(1, "".to_string(), vec![]);
-    let result = price_all_command(user).await;
+    let result = price_all_command(user, Lang::En, &Config::from_env()).await;
     assert_eq!(
         result,
         "You don't have any currency, type /addcurency curency-name"
     );
 }
 
+fn registry() -> AssetRegistry {
+    AssetRegistry::with_assets(Arc::new(MemoryStorage::new()), test_assets())
+}
+
 #[tokio::test]
 async fn test_parse_currency() {
     dotenv().ok();
     let text = "Hello, I want to buy 1 BTC";
-    let result = parse_currency(text).await.unwrap();
+    let result = parse_currency(text, Lang::En, Fiat::Usd, &registry(), &Config::from_env())
+        .await
+        .unwrap()
+        .unwrap();
     assert!(result.contains("BTC"));
 }
 
@@ -70,7 +91,10 @@ async fn test_parse_currency() {
 async fn test_parse_currency_with_multiple_currencies() {
     dotenv().ok();
     let text = "I have 0.5 BTC and 1000 ETH";
-    let result = parse_currency(text).await.unwrap();
+    let result = parse_currency(text, Lang::En, Fiat::Usd, &registry(), &Config::from_env())
+        .await
+        .unwrap()
+        .unwrap();
     assert!(result.contains("BTC"));
     assert!(result.contains("ETH"));
 }
@@ -79,6 +103,34 @@ async fn test_parse_currency_with_multiple_currencies() {
 async fn test_parse_currency_invalid_regex() {
     dotenv().ok();
     let text = "I have 1000BTC";
-    let result = parse_currency(text).await;
-    assert!(result.is_none());
+    let result = parse_currency(text, Lang::En, Fiat::Usd, &registry(), &Config::from_env()).await;
+    assert!(matches!(result, Ok(None)));
+}
+
+#[tokio::test]
+async fn test_parse_currency_fiat_only() {
+    dotenv().ok();
+    let text = "It costs 100 USD or 90 EUR";
+    let result = parse_currency(text, Lang::En, Fiat::Usd, &registry(), &Config::from_env()).await;
+    assert!(matches!(result, Ok(None)));
+}
+
+#[test]
+fn test_select_entry_prefers_resolved_id() {
+    let entries = serde_json::json!([
+        {"id": 5000, "cmc_rank": 900, "quote": {"USD": {"price": 0.1}}},
+        {"id": 7083, "cmc_rank": 20, "quote": {"USD": {"price": 5.0}}}
+    ]);
+    assert_eq!(select_entry(&entries, Some(5000)).unwrap()["id"], 5000);
+    assert_eq!(select_entry(&entries, None).unwrap()["id"], 7083);
+    assert_eq!(select_entry(&entries, Some(1)).unwrap()["id"], 7083);
+}
+
+#[test]
+fn test_parse_symbols_normalises_and_dedups() {
+    assert_eq!(
+        parse_symbols("btc, eth  sol BTC"),
+        vec!["BTC".to_string(), "ETH".to_string(), "SOL".to_string()]
+    );
+    assert!(parse_symbols("   ").is_empty());
 }
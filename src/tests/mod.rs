@@ -1,3 +1,5 @@
 #[cfg(test)]
+pub mod commands_tests;
+#[cfg(test)]
 pub mod currency_tests;
 pub mod twitter_tests;
@@ -1,5 +1,7 @@
 #[cfg(test)]
 mod tests {
+    use crate::config::Config;
+    use crate::i18n::Lang;
     use crate::tools::parse_twitter::parse_twitter_links;
     use dotenvy::dotenv;
 
@@ -7,7 +9,10 @@ mod tests {
     async fn test_parse_twitter_links_valid() {
         dotenv().ok();
         let text = "Check out this Twitter account: https://twitter.com/elonmusk";
-        let result = parse_twitter_links(text).await.unwrap();
+        let result = parse_twitter_links(text, Lang::En, &Config::from_env())
+            .await
+            .unwrap()
+            .unwrap();
         assert!(result.contains("Twitter"));
         assert!(result.contains("elonmusk"));
     }
@@ -16,7 +21,11 @@ mod tests {
     async fn test_parse_twitter_links_invalid() {
         dotenv().ok();
         let text = "This is not a valid Twitter link: https://twitter.com/not-a-real-user";
-        let result = parse_twitter_links(text).await.unwrap_or_default();
+        let result = parse_twitter_links(text, Lang::En, &Config::from_env())
+            .await
+            .ok()
+            .flatten()
+            .unwrap_or_default();
         assert!(!result.contains("Twitter"));
         assert!(!result.contains("not-a-real-user"));
     }
@@ -25,7 +34,7 @@ mod tests {
     async fn test_parse_twitter_links_no_links() {
         dotenv().ok();
         let text = "There are no Twitter links in this text.";
-        let result = parse_twitter_links(text).await;
-        assert!(result.is_none());
+        let result = parse_twitter_links(text, Lang::En, &Config::from_env()).await;
+        assert!(matches!(result, Ok(None)));
     }
 }
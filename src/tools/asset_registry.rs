@@ -0,0 +1,284 @@
+use crate::config::Config;
+use crate::models::asset::Asset;
+use crate::storage::AssetRepository;
+use crate::tools::metrics::Upstream;
+use crate::tools::quota::record_cmc_credits;
+use crate::tools::supervisor::JobResult;
+use log::{error, info};
+use mongodb::bson;
+use serde::Deserialize;
+use serde_json::Value;
+use std::error::Error;
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+use tokio::time;
+
+/// Locally cached CoinMarketCap id map
+///
+/// Resolves symbols, names and slugs to CoinMarketCap ids. The map is stored
+/// in the `asset` collection and kept in memory for lookups.
+#[derive(Clone)]
+pub struct AssetRegistry {
+    db: Arc<dyn AssetRepository>,
+    assets: Arc<RwLock<Vec<Asset>>>,
+}
+
+#[derive(Debug, Deserialize)]
+struct CmcMapResponse {
+    data: Vec<CmcMapEntry>,
+    #[serde(default)]
+    status: Value,
+}
+
+#[derive(Debug, Deserialize)]
+struct CmcMapEntry {
+    id: i64,
+    rank: Option<i64>,
+    name: String,
+    symbol: String,
+    slug: String,
+}
+
+impl AssetRegistry {
+    /// Create registry and load the cached assets from the database
+    pub async fn new(db: Arc<dyn AssetRepository>) -> Self {
+        let assets = match db.get_all_assets().await {
+            Ok(assets) => assets,
+            Err(err) => {
+                error!("Error loading asset registry: {}", err);
+                vec![]
+            }
+        };
+        info!("Loaded {} assets from registry", assets.len());
+
+        Self::with_assets(db, assets)
+    }
+
+    /// Create registry from already known assets
+    pub fn with_assets(db: Arc<dyn AssetRepository>, assets: Vec<Asset>) -> Self {
+        Self {
+            db,
+            assets: Arc::new(RwLock::new(assets)),
+        }
+    }
+
+    /// Number of assets in the registry
+    pub fn len(&self) -> usize {
+        self.assets.read().map(|assets| assets.len()).unwrap_or(0)
+    }
+
+    /// Returns true if the registry has not been filled yet
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Get asset by CoinMarketCap id
+    pub fn get(&self, cmc_id: i64) -> Option<Asset> {
+        let assets = self.assets.read().ok()?;
+        assets.iter().find(|asset| asset.cmc_id == cmc_id).cloned()
+    }
+
+    /// Resolve a symbol, name or slug to the matching assets
+    ///
+    /// Symbol matches win over name and slug matches. Results are sorted by
+    /// rank, so the first entry is the biggest coin sharing the ticker.
+    pub fn resolve(&self, query: &str) -> Vec<Asset> {
+        let query = query.trim().to_lowercase();
+        let assets = match self.assets.read() {
+            Ok(assets) => assets,
+            Err(_) => return vec![],
+        };
+
+        let mut found: Vec<Asset> = assets
+            .iter()
+            .filter(|asset| asset.symbol.to_lowercase() == query)
+            .cloned()
+            .collect();
+
+        if found.is_empty() {
+            found = assets
+                .iter()
+                .filter(|asset| asset.slug == query || asset.name.to_lowercase() == query)
+                .cloned()
+                .collect();
+        }
+
+        found.sort_by_key(|asset| asset.rank.unwrap_or(i64::MAX));
+        found
+    }
+
+    /// Suggest symbols close to an unknown query
+    ///
+    /// Uses the edit distance to symbols and names, closest and best ranked first.
+    pub fn suggest(&self, query: &str, limit: usize) -> Vec<Asset> {
+        let query = query.trim().to_lowercase();
+        let max_distance = if query.chars().count() <= 3 { 1 } else { 2 };
+        let assets = match self.assets.read() {
+            Ok(assets) => assets,
+            Err(_) => return vec![],
+        };
+
+        let mut found: Vec<(usize, Asset)> = assets
+            .iter()
+            .filter_map(|asset| {
+                let distance = levenshtein(&query, &asset.symbol.to_lowercase())
+                    .min(levenshtein(&query, &asset.name.to_lowercase()));
+                (distance <= max_distance).then(|| (distance, asset.clone()))
+            })
+            .collect();
+
+        found.sort_by_key(|(distance, asset)| (*distance, asset.rank.unwrap_or(i64::MAX)));
+        found
+            .into_iter()
+            .map(|(_, asset)| asset)
+            .take(limit)
+            .collect()
+    }
+
+    /// Time until the cached map is `interval` old, zero if there is none
+    pub fn next_refresh_in(&self, interval: Duration) -> Duration {
+        let newest = match self.assets.read() {
+            Ok(assets) => assets.iter().map(|asset| asset.updated_at).max(),
+            Err(_) => None,
+        };
+        let age = match newest {
+            Some(updated_at) => {
+                let millis =
+                    bson::DateTime::now().timestamp_millis() - updated_at.timestamp_millis();
+                Duration::from_millis(millis.max(0) as u64)
+            }
+            None => return Duration::ZERO,
+        };
+        interval.saturating_sub(age)
+    }
+
+    /// Fetch the id map from CoinMarketCap and store it
+    pub async fn refresh(&self, config: &Config) -> Result<usize, Box<dyn Error + Send + Sync>> {
+        let assets = fetch_cmc_map(config).await?;
+        let count = assets.len();
+        self.db.replace_assets(assets.clone()).await?;
+
+        if let Ok(mut cached) = self.assets.write() {
+            *cached = assets;
+        }
+
+        Ok(count)
+    }
+}
+
+/// Gets the active asset map from CoinMarketCap
+async fn fetch_cmc_map(config: &Config) -> Result<Vec<Asset>, Box<dyn Error + Send + Sync>> {
+    let url = "https://pro-api.coinmarketcap.com/v1/cryptocurrency/map";
+
+    let http = config.http();
+    let request = http
+        .get(url)
+        .query(&[("listing_status", "active"), ("sort", "cmc_rank")])
+        .header("X-CMC_PRO_API_KEY", &config.cmc_token)
+        .header("Accept", "application/json");
+    let response = http.send(Upstream::CoinMarketCap, request).await?;
+
+    if !response.status().is_success() {
+        return Err(format!(
+            "Error fetching asset map: Status code {}",
+            response.status().as_u16()
+        )
+        .into());
+    }
+
+    let map = response.json::<CmcMapResponse>().await?;
+    record_cmc_credits(&map.status);
+
+    Ok(map
+        .data
+        .into_iter()
+        .map(|entry| Asset::new(entry.id, entry.symbol, entry.name, entry.slug, entry.rank))
+        .collect())
+}
+
+/// Edit distance between two strings
+fn levenshtein(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut row: Vec<usize> = (0..=b.len()).collect();
+
+    for (i, ca) in a.chars().enumerate() {
+        let mut previous = row[0];
+        row[0] = i + 1;
+        for (j, cb) in b.iter().enumerate() {
+            let current = row[j + 1];
+            row[j + 1] = if ca == *cb {
+                previous
+            } else {
+                1 + previous.min(row[j]).min(current)
+            };
+            previous = current;
+        }
+    }
+
+    row[b.len()]
+}
+
+/// Asset refresh job, run by the supervisor
+///
+/// Refreshes `schedule.asset_refresh_hours` after the cached map was fetched,
+/// right away if there is none. A failed refresh ends the run and is retried
+/// by the supervisor with backoff.
+pub async fn refresh_assets_job(registry: AssetRegistry, config: Arc<Config>) -> JobResult {
+    loop {
+        time::sleep(registry.next_refresh_in(config.asset_refresh_interval())).await;
+
+        let count = registry.refresh(&config).await?;
+        info!("Asset registry refreshed: {} assets", count);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::memory::{test_assets, MemoryStorage};
+
+    fn registry() -> AssetRegistry {
+        AssetRegistry::with_assets(Arc::new(MemoryStorage::new()), test_assets())
+    }
+
+    #[test]
+    fn test_resolve_symbol_collision_sorted_by_rank() {
+        let found = registry().resolve("uni");
+        let ids: Vec<i64> = found.iter().map(|asset| asset.cmc_id).collect();
+        assert_eq!(ids, vec![7083, 5000]);
+    }
+
+    #[test]
+    fn test_resolve_name_and_slug() {
+        let registry = registry();
+        assert_eq!(registry.resolve("Bitcoin")[0].cmc_id, 1);
+        assert_eq!(registry.resolve("uniswap")[0].cmc_id, 7083);
+        assert!(registry.resolve("notacoin").is_empty());
+    }
+
+    #[test]
+    fn test_suggest_typo() {
+        let registry = registry();
+        let found = registry.suggest("btcc", 3);
+        assert_eq!(found[0].symbol, "BTC");
+        assert!(registry.suggest("zzzzzz", 3).is_empty());
+    }
+
+    #[test]
+    fn test_next_refresh_in() {
+        let interval = Duration::from_secs(60 * 60);
+        let registry = registry();
+        assert!(registry.next_refresh_in(interval) > Duration::from_secs(59 * 60));
+
+        let empty = AssetRegistry::with_assets(Arc::new(MemoryStorage::new()), vec![]);
+        assert_eq!(empty.next_refresh_in(interval), Duration::ZERO);
+    }
+
+    #[test]
+    fn test_levenshtein() {
+        assert_eq!(levenshtein("btc", "btc"), 0);
+        assert_eq!(levenshtein("btcc", "btc"), 1);
+        assert_eq!(levenshtein("eth", "etc"), 1);
+        assert_eq!(levenshtein("", "sol"), 3);
+    }
+}
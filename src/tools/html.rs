@@ -0,0 +1,155 @@
+//! Helpers for Telegram HTML messages
+//!
+//! Replies are sent with `ParseMode::Html`, so every user or API provided
+//! string has to go through [`escape`] or one of the helpers below.
+
+/// Escape text for a Telegram HTML message or attribute
+pub fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Bold text
+pub fn bold(text: &str) -> String {
+    format!("<b>{}</b>", escape(text))
+}
+
+/// Inline monospace text
+pub fn code(text: &str) -> String {
+    format!("<code>{}</code>", escape(text))
+}
+
+/// Clickable link
+pub fn link(url: &str, text: &str) -> String {
+    format!("<a href=\"{}\">{}</a>", escape(url), escape(text))
+}
+
+/// Plain text of an HTML message, for callback answers that show no markup
+pub fn strip_tags(text: &str) -> String {
+    let mut plain = String::with_capacity(text.len());
+    let mut in_tag = false;
+    for c in text.chars() {
+        match c {
+            '<' => in_tag = true,
+            '>' if in_tag => in_tag = false,
+            _ if !in_tag => plain.push(c),
+            _ => {}
+        }
+    }
+    plain
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&amp;", "&")
+}
+
+/// Monospace table with aligned columns
+///
+/// The first column is aligned left, the others right, so numbers line up.
+///
+/// # Arguments
+///
+/// * `rows` - Table cells, rows may have different lengths
+///
+/// # Returns
+///
+/// * `String` - The table in a `<pre>` block, empty if there are no rows
+pub fn table(rows: &[Vec<String>]) -> String {
+    if rows.is_empty() {
+        return String::new();
+    }
+
+    let columns = rows.iter().map(|row| row.len()).max().unwrap_or(0);
+    let widths: Vec<usize> = (0..columns)
+        .map(|column| {
+            rows.iter()
+                .filter_map(|row| row.get(column))
+                .map(|cell| cell.chars().count())
+                .max()
+                .unwrap_or(0)
+        })
+        .collect();
+
+    let lines: Vec<String> = rows
+        .iter()
+        .map(|row| {
+            let cells: Vec<String> = row
+                .iter()
+                .enumerate()
+                .map(|(column, cell)| {
+                    let width = widths[column];
+                    if column == 0 {
+                        format!("{:<width$}", cell, width = width)
+                    } else {
+                        format!("{:>width$}", cell, width = width)
+                    }
+                })
+                .collect();
+            escape(cells.join("  ").trim_end())
+        })
+        .collect();
+
+    format!("<pre>{}</pre>", lines.join("\n"))
+}
+
+/// CoinMarketCap page of a coin
+pub fn cmc_url(slug: &str) -> String {
+    format!("https://coinmarketcap.com/currencies/{}/", slug)
+}
+
+/// Binance spot market of a coin against USDT
+pub fn binance_url(symbol: &str) -> String {
+    format!(
+        "https://www.binance.com/en/trade/{}_USDT",
+        symbol.to_uppercase()
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_escape() {
+        assert_eq!(
+            escape("<b>Tom & \"Jerry\"</b>"),
+            "&lt;b&gt;Tom &amp; &quot;Jerry&quot;&lt;/b&gt;"
+        );
+        assert_eq!(
+            link("https://x.com/?a=1&b=2", "a<b"),
+            "<a href=\"https://x.com/?a=1&amp;b=2\">a&lt;b</a>"
+        );
+    }
+
+    #[test]
+    fn test_strip_tags() {
+        assert_eq!(
+            strip_tags("Added <b>BTC &amp; ETH</b> &lt;3"),
+            "Added BTC & ETH <3"
+        );
+        assert_eq!(strip_tags(&escape("&lt;")), "&lt;");
+    }
+
+    #[test]
+    fn test_table() {
+        let rows = vec![
+            vec!["BTC".to_string(), "67012.55".to_string()],
+            vec!["ETH".to_string(), "3200.10".to_string()],
+            vec!["A&B".to_string(), "0.10".to_string()],
+        ];
+        assert_eq!(
+            table(&rows),
+            "<pre>BTC  67012.55\nETH   3200.10\nA&amp;B      0.10</pre>"
+        );
+        assert_eq!(table(&[]), "");
+    }
+}
@@ -0,0 +1,513 @@
+use crate::config::{Config, QuotasConfig};
+use crate::tools::metrics::{metrics, Upstream};
+use crate::tools::quota::{quotas, QuotaLevel};
+use log::warn;
+use rand::Rng;
+use reqwest::header::{HeaderMap, RETRY_AFTER};
+use reqwest::{IntoUrl, RequestBuilder, Response, StatusCode};
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime};
+
+/// Error of an upstream request
+#[derive(Debug)]
+pub enum HttpError {
+    /// Transport error or timeout, after the retries
+    Request(reqwest::Error),
+    /// Too many failures of the host in a row, it is not asked for a while
+    CircuitOpen(String),
+    /// The credit budget of the API is used up
+    QuotaExceeded(&'static str),
+}
+
+impl fmt::Display for HttpError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            HttpError::Request(e) => write!(f, "Request error: {}", e),
+            HttpError::CircuitOpen(host) => write!(f, "Circuit open for {}", host),
+            HttpError::QuotaExceeded(api) => write!(f, "Quota of {} exceeded", api),
+        }
+    }
+}
+
+impl Error for HttpError {}
+
+impl From<reqwest::Error> for HttpError {
+    fn from(err: reqwest::Error) -> HttpError {
+        HttpError::Request(err)
+    }
+}
+
+/// HTTP client of the upstream APIs
+///
+/// Requests are limited by `timeouts.http_secs` and `http.connect_timeout_secs`.
+/// Timeouts, 5xx and 429 responses are retried with exponential backoff and
+/// jitter, or after the `Retry-After` of the response. A host failing
+/// `http.breaker_failures` times in a row is not asked for
+/// `http.breaker_cooldown_secs`. Responses are counted against the `quotas`,
+/// and requests to an API with its budget used up fail right away.
+#[derive(Clone, Debug)]
+pub struct Http {
+    client: reqwest::Client,
+    retries: u32,
+    retry_base: Duration,
+    max_retry_wait: Duration,
+    breaker: Arc<CircuitBreaker>,
+    quotas: QuotasConfig,
+}
+
+impl Http {
+    /// Build the client, failing on settings reqwest rejects, e.g. a user
+    /// agent with a new line
+    pub fn new(config: &Config) -> Result<Self, HttpError> {
+        let client = reqwest::Client::builder()
+            .timeout(Duration::from_secs(config.timeouts.http_secs))
+            .connect_timeout(Duration::from_secs(config.http.connect_timeout_secs))
+            .user_agent(config.http.user_agent.as_str())
+            .build()?;
+        Ok(Self {
+            client,
+            retries: config.http.retries,
+            retry_base: Duration::from_millis(config.http.retry_base_ms),
+            max_retry_wait: Duration::from_secs(config.http.max_retry_wait_secs),
+            breaker: Arc::new(CircuitBreaker::new(
+                config.http.breaker_failures,
+                Duration::from_secs(config.http.breaker_cooldown_secs),
+            )),
+            quotas: config.quotas.clone(),
+        })
+    }
+
+    /// Start a GET request, sent with [`Http::send`]
+    pub fn get<U: IntoUrl>(&self, url: U) -> RequestBuilder {
+        self.client.get(url)
+    }
+
+    /// Send a request, retrying it on timeouts, 5xx and 429 responses
+    ///
+    /// # Arguments
+    ///
+    /// * `api` - Upstream API, for the metrics
+    /// * `request` - Request made with [`Http::get`]
+    ///
+    /// # Returns
+    ///
+    /// * `Result<Response, HttpError>` - Last response, also when its status is
+    ///   an error, or the error of the last attempt
+    pub async fn send(
+        &self,
+        api: Upstream,
+        request: RequestBuilder,
+    ) -> Result<Response, HttpError> {
+        let mut request = request.build()?;
+        let host = host(request.url());
+        if !self.breaker.allow(&host) {
+            return Err(HttpError::CircuitOpen(host));
+        }
+
+        let mut attempt = 0;
+        loop {
+            if quotas().level(api, &self.quotas) == QuotaLevel::Exhausted {
+                return Err(HttpError::QuotaExceeded(api.label()));
+            }
+            // Requests with a streamed body can't be sent again
+            let next = request.try_clone();
+            let result = metrics().track(api, self.client.execute(request)).await;
+
+            let (failed, retry) = match &result {
+                Ok(response) => {
+                    let status = response.status();
+                    let retry = (status.is_server_error()
+                        || status == StatusCode::TOO_MANY_REQUESTS)
+                        .then(|| retry_after(response.headers()));
+                    (status.is_server_error(), retry)
+                }
+                Err(err) => (true, (err.is_timeout() || err.is_connect()).then_some(None)),
+            };
+            self.breaker.record(&host, failed);
+
+            let delay = match retry {
+                Some(Some(wait)) if wait > self.max_retry_wait => None,
+                Some(wait) => Some(wait.unwrap_or_else(|| self.backoff(attempt))),
+                None => None,
+            };
+            match (delay, next) {
+                (Some(delay), Some(next))
+                    if attempt < self.retries && self.breaker.allow(&host) =>
+                {
+                    warn!(
+                        "Retrying {} request to {} in {:?}",
+                        api.label(),
+                        host,
+                        delay
+                    );
+                    tokio::time::sleep(delay).await;
+                    request = next;
+                    attempt += 1;
+                }
+                _ => {
+                    // Only the answer the caller gets counts against the quota
+                    if let Ok(response) = &result {
+                        count(api, response);
+                    }
+                    return result.map_err(HttpError::from);
+                }
+            }
+        }
+    }
+
+    /// Delay of a retry, `retry_base * 2^attempt` with its upper half random
+    fn backoff(&self, attempt: u32) -> Duration {
+        let backoff = self.retry_base.saturating_mul(2u32.saturating_pow(attempt));
+        let half = backoff / 2;
+        half + half.mul_f64(rand::thread_rng().gen::<f64>())
+    }
+}
+
+/// Count a response against the quota of its API, a call always and a credit
+/// when the API bills it
+fn count(api: Upstream, response: &Response) {
+    let credits = i64::from(billable(api, response.status()));
+    quotas().record(api, 1, credits);
+    let remaining = response
+        .headers()
+        .get("x-rate-limit-remaining")
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.parse().ok());
+    if let Some(remaining) = remaining {
+        quotas().set_remaining(api, remaining);
+    }
+}
+
+/// Returns true if the API charges a credit for a response of the status
+///
+/// CoinMarketCap bills successful calls only, the others are assumed to bill
+/// every request they served, throttled and failed ones aside.
+fn billable(api: Upstream, status: StatusCode) -> bool {
+    match api {
+        Upstream::CoinMarketCap => status.is_success(),
+        _ => !status.is_server_error() && status != StatusCode::TOO_MANY_REQUESTS,
+    }
+}
+
+/// Host and port of a URL, the key of its circuit
+fn host(url: &reqwest::Url) -> String {
+    format!(
+        "{}:{}",
+        url.host_str().unwrap_or_default(),
+        url.port_or_known_default().unwrap_or_default()
+    )
+}
+
+/// Delay asked by a `Retry-After` header, `None` without a valid one
+///
+/// Both forms are read, delay seconds and an HTTP date. A date in the past
+/// asks for no delay.
+fn retry_after(headers: &HeaderMap) -> Option<Duration> {
+    retry_after_at(headers, SystemTime::now())
+}
+
+fn retry_after_at(headers: &HeaderMap, now: SystemTime) -> Option<Duration> {
+    let value = headers.get(RETRY_AFTER)?.to_str().ok()?.trim();
+    if let Ok(seconds) = value.parse() {
+        return Some(Duration::from_secs(seconds));
+    }
+    let date = httpdate::parse_http_date(value).ok()?;
+    Some(date.duration_since(now).unwrap_or_default())
+}
+
+/// Failures in a row of each host
+#[derive(Debug)]
+struct CircuitBreaker {
+    threshold: u32,
+    cooldown: Duration,
+    hosts: Mutex<HashMap<String, HostState>>,
+}
+
+#[derive(Debug, Default)]
+struct HostState {
+    failures: u32,
+    open_until: Option<Instant>,
+    /// A request is testing the host after the cooldown
+    probing: bool,
+}
+
+impl CircuitBreaker {
+    fn new(threshold: u32, cooldown: Duration) -> Self {
+        Self {
+            threshold,
+            cooldown,
+            hosts: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Returns false while the circuit of the host is open
+    ///
+    /// After the cooldown a single request probes the host, the others wait
+    /// for its outcome: a success closes the circuit, a failure opens it for
+    /// another cooldown. A probe that never reports back frees its place
+    /// after a cooldown as well.
+    fn allow(&self, host: &str) -> bool {
+        let mut hosts = self.hosts.lock().unwrap();
+        let Some(state) = hosts.get_mut(host) else {
+            return true;
+        };
+        match state.open_until {
+            Some(open_until) if Instant::now() < open_until => false,
+            Some(_) => {
+                state.probing = true;
+                state.open_until = Some(Instant::now() + self.cooldown);
+                true
+            }
+            None => true,
+        }
+    }
+
+    /// Count a response or an error of the host
+    fn record(&self, host: &str, failed: bool) {
+        if self.threshold == 0 {
+            return;
+        }
+        let mut hosts = self.hosts.lock().unwrap();
+        if !failed {
+            hosts.remove(host);
+            return;
+        }
+        let state = hosts.entry(host.to_string()).or_default();
+        state.failures += 1;
+        if state.failures >= self.threshold {
+            if state.open_until.is_none() {
+                warn!("Too many failures of {}, pausing its requests", host);
+            } else if state.probing {
+                warn!("{} is still failing, pausing its requests again", host);
+            }
+            state.open_until = Some(Instant::now() + self.cooldown);
+            state.probing = false;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::extract::State;
+    use axum::http::{header, HeaderMap as AxumHeaders, StatusCode as AxumStatus};
+    use axum::response::IntoResponse;
+    use axum::routing::get;
+    use axum::Router;
+    use std::net::TcpListener;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    /// Requests the mock server got
+    type Hits = Arc<AtomicUsize>;
+
+    /// Start a mock server, returning its URL
+    fn serve(router: Router) -> String {
+        let socket = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}", socket.local_addr().unwrap());
+        let server = axum::Server::from_tcp(socket)
+            .unwrap()
+            .serve(router.into_make_service());
+        tokio::spawn(server);
+        url
+    }
+
+    /// Answers with the statuses in turn, the last one from then on
+    fn statuses(statuses: &'static [u16], retry_after: Option<&'static str>) -> (String, Hits) {
+        let hits = Hits::default();
+        let handler = move |State(hits): State<Hits>| async move {
+            let hit = hits.fetch_add(1, Ordering::SeqCst);
+            let status = statuses[hit.min(statuses.len() - 1)];
+            let mut headers = AxumHeaders::new();
+            if let Some(retry_after) = retry_after {
+                headers.insert(header::RETRY_AFTER, retry_after.parse().unwrap());
+            }
+            (AxumStatus::from_u16(status).unwrap(), headers, "ok")
+        };
+        let router = Router::new()
+            .route("/", get(handler))
+            .with_state(hits.clone());
+        (serve(router), hits)
+    }
+
+    fn http(retries: u32, breaker_failures: u32) -> Http {
+        let mut config = Config::default();
+        config.timeouts.http_secs = 1;
+        config.http.retries = retries;
+        config.http.retry_base_ms = 1;
+        config.http.breaker_failures = breaker_failures;
+        config.http.user_agent = "test-agent".to_string();
+        Http::new(&config).unwrap()
+    }
+
+    async fn status(http: &Http, url: &str) -> Result<StatusCode, HttpError> {
+        let response = http.send(Upstream::Binance, http.get(url)).await?;
+        Ok(response.status())
+    }
+
+    #[test]
+    fn test_billable() {
+        assert!(billable(Upstream::CoinMarketCap, StatusCode::OK));
+        assert!(!billable(Upstream::CoinMarketCap, StatusCode::BAD_REQUEST));
+        assert!(!billable(
+            Upstream::CoinMarketCap,
+            StatusCode::TOO_MANY_REQUESTS
+        ));
+        assert!(!billable(
+            Upstream::CoinMarketCap,
+            StatusCode::INTERNAL_SERVER_ERROR
+        ));
+        assert!(billable(Upstream::Twitter, StatusCode::NOT_FOUND));
+        assert!(!billable(Upstream::Twitter, StatusCode::TOO_MANY_REQUESTS));
+        assert!(!billable(Upstream::Twitter, StatusCode::BAD_GATEWAY));
+    }
+
+    #[tokio::test]
+    async fn test_retries_server_errors() {
+        let (url, hits) = statuses(&[503, 500, 200], None);
+        assert_eq!(status(&http(2, 0), &url).await.unwrap(), StatusCode::OK);
+        assert_eq!(hits.load(Ordering::SeqCst), 3);
+
+        // Out of retries the last response is returned
+        let (url, hits) = statuses(&[503], None);
+        let result = status(&http(1, 0), &url).await.unwrap();
+        assert_eq!(result, StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(hits.load(Ordering::SeqCst), 2);
+
+        // Client errors are final
+        let (url, hits) = statuses(&[404, 200], None);
+        let result = status(&http(2, 0), &url).await.unwrap();
+        assert_eq!(result, StatusCode::NOT_FOUND);
+        assert_eq!(hits.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn test_retry_after() {
+        let (url, hits) = statuses(&[429, 200], Some("1"));
+        let started = Instant::now();
+        assert_eq!(status(&http(2, 0), &url).await.unwrap(), StatusCode::OK);
+        assert_eq!(hits.load(Ordering::SeqCst), 2);
+        assert!(started.elapsed() >= Duration::from_secs(1));
+
+        // Waits over `max_retry_wait_secs` are not retried
+        let (url, hits) = statuses(&[429, 200], Some("3600"));
+        let result = status(&http(2, 0), &url).await.unwrap();
+        assert_eq!(result, StatusCode::TOO_MANY_REQUESTS);
+        assert_eq!(hits.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn test_retry_after_forms() {
+        let headers = |value: &str| {
+            let mut headers = HeaderMap::new();
+            headers.insert(RETRY_AFTER, value.parse().unwrap());
+            headers
+        };
+        let now = SystemTime::UNIX_EPOCH + Duration::from_secs(1_445_410_800);
+
+        let wait = retry_after_at(&headers(" 120 "), now);
+        assert_eq!(wait, Some(Duration::from_secs(120)));
+        let wait = retry_after_at(&headers("Wed, 21 Oct 2015 07:30:00 GMT"), now);
+        assert_eq!(wait, Some(Duration::from_secs(1_800)));
+        // Dates gone by ask for no wait
+        let wait = retry_after_at(&headers("Wed, 21 Oct 2015 06:00:00 GMT"), now);
+        assert_eq!(wait, Some(Duration::ZERO));
+        assert_eq!(retry_after_at(&headers("soon"), now), None);
+        assert_eq!(retry_after_at(&HeaderMap::new(), now), None);
+    }
+
+    #[test]
+    fn test_invalid_client_settings() {
+        let mut config = Config::default();
+        config.http.user_agent = "bot\nagent".to_string();
+        assert!(matches!(Http::new(&config), Err(HttpError::Request(_))));
+    }
+
+    #[tokio::test]
+    async fn test_timeout_and_user_agent() {
+        let router = Router::new()
+            .route(
+                "/slow",
+                get(|| async {
+                    tokio::time::sleep(Duration::from_secs(5)).await;
+                    "late"
+                }),
+            )
+            .route(
+                "/agent",
+                get(|headers: AxumHeaders| async move {
+                    headers[header::USER_AGENT]
+                        .to_str()
+                        .unwrap()
+                        .to_string()
+                        .into_response()
+                }),
+            );
+        let url = serve(router);
+        let http = http(0, 0);
+
+        let started = Instant::now();
+        match status(&http, &format!("{}/slow", url)).await {
+            Err(HttpError::Request(err)) => assert!(err.is_timeout()),
+            other => panic!("expected a timeout, got {:?}", other),
+        }
+        assert!(started.elapsed() < Duration::from_secs(3));
+
+        let response = http
+            .send(Upstream::Binance, http.get(format!("{}/agent", url)))
+            .await
+            .unwrap();
+        assert_eq!(response.text().await.unwrap(), "test-agent");
+    }
+
+    #[tokio::test]
+    async fn test_circuit_breaker() {
+        let (url, hits) = statuses(&[500], None);
+        let http = http(5, 2);
+
+        // The second failure opens the circuit and ends the retries
+        let result = status(&http, &url).await.unwrap();
+        assert_eq!(result, StatusCode::INTERNAL_SERVER_ERROR);
+        assert_eq!(hits.load(Ordering::SeqCst), 2);
+
+        match status(&http, &url).await {
+            Err(HttpError::CircuitOpen(_)) => {}
+            other => panic!("expected an open circuit, got {:?}", other),
+        }
+        assert_eq!(hits.load(Ordering::SeqCst), 2);
+    }
+
+    #[test]
+    fn test_circuit_breaker_cooldown() {
+        let breaker = CircuitBreaker::new(2, Duration::from_millis(50));
+        breaker.record("a:80", true);
+        assert!(breaker.allow("a:80"));
+        breaker.record("a:80", true);
+        assert!(!breaker.allow("a:80"));
+        assert!(breaker.allow("b:80"));
+
+        // Half open after the cooldown, one probe at a time and a failure opens it again
+        std::thread::sleep(Duration::from_millis(60));
+        assert!(breaker.allow("a:80"));
+        assert!(!breaker.allow("a:80"));
+        breaker.record("a:80", true);
+        assert!(!breaker.allow("a:80"));
+
+        // A probe that succeeds closes the circuit for everyone
+        std::thread::sleep(Duration::from_millis(60));
+        assert!(breaker.allow("a:80"));
+        assert!(!breaker.allow("a:80"));
+        breaker.record("a:80", false);
+        assert!(breaker.allow("a:80"));
+        assert!(breaker.allow("a:80"));
+        breaker.record("a:80", true);
+        breaker.record("a:80", true);
+
+        std::thread::sleep(Duration::from_millis(60));
+        breaker.record("a:80", false);
+        breaker.record("a:80", true);
+        assert!(breaker.allow("a:80"));
+    }
+}
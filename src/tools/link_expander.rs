@@ -0,0 +1,186 @@
+use crate::config::Config;
+use crate::i18n::Lang;
+use crate::models::errors::CustomError;
+use crate::models::preferences::Expander;
+use crate::tools::http::Http;
+use crate::tools::parse_eden::MagicEden;
+use crate::tools::parse_twitter::Twitter;
+use async_trait::async_trait;
+use futures::future::join_all;
+use log::error;
+use regex::Regex;
+use std::sync::OnceLock;
+
+/// Answers to links of one site in a free-text message
+///
+/// A new link type is a module with an implementation of this trait, added to
+/// [`registry`] and to [`Expander`] so users and chats can switch it off.
+#[async_trait]
+pub trait LinkExpander: Send + Sync {
+    /// Upstream data of one link
+    type Data: Send;
+
+    /// Switch of the expander in `expanders`
+    fn expander(&self) -> Expander;
+
+    /// Links of the site, the first group captures what `fetch` looks up
+    fn pattern(&self) -> &Regex;
+
+    /// Load the data of a link
+    ///
+    /// # Arguments
+    ///
+    /// * `id` - First group of `pattern`, e.g. a username
+    /// * `http` - Shared HTTP client
+    /// * `config` - Bot configuration, for the API tokens
+    async fn fetch(
+        &self,
+        id: &str,
+        http: &Http,
+        config: &Config,
+    ) -> Result<Self::Data, CustomError>;
+
+    /// Text of a link, ending with a new line
+    fn render(&self, id: &str, data: &Self::Data, lang: Lang) -> String;
+}
+
+/// Object safe side of [`LinkExpander`], what the registry runs
+#[async_trait]
+pub trait ExpandLinks: Send + Sync {
+    /// Switch of the expander in `expanders`
+    fn expander(&self) -> Expander;
+
+    /// Answer to the links of a text
+    ///
+    /// # Returns
+    ///
+    /// * `Result<Option<String>, CustomError>` - `None` without links, the
+    ///   answers of the links in the order of the text otherwise, failed ones
+    ///   left out. An error if none of the links could be answered.
+    async fn expand(
+        &self,
+        text: &str,
+        lang: Lang,
+        config: &Config,
+    ) -> Result<Option<String>, CustomError>;
+}
+
+#[async_trait]
+impl<T: LinkExpander> ExpandLinks for T {
+    fn expander(&self) -> Expander {
+        LinkExpander::expander(self)
+    }
+
+    async fn expand(
+        &self,
+        text: &str,
+        lang: Lang,
+        config: &Config,
+    ) -> Result<Option<String>, CustomError> {
+        // A link repeated in the text is looked up and answered once
+        let mut ids: Vec<&str> = Vec::new();
+        for id in self
+            .pattern()
+            .captures_iter(text)
+            .filter_map(|captures| captures.get(1))
+        {
+            if !ids.contains(&id.as_str()) {
+                ids.push(id.as_str());
+            }
+        }
+        if ids.is_empty() {
+            return Ok(None);
+        }
+
+        let answers = join_all(ids.into_iter().map(|id| async move {
+            let answer = self.fetch(id, config.http(), config).await;
+            if let Err(e) = &answer {
+                let code = LinkExpander::expander(self).code();
+                error!("Error expanding {} link {}: {}", code, id, e);
+            }
+            answer.map(|data| self.render(id, &data, lang))
+        }))
+        .await;
+
+        let mut text = String::new();
+        let mut error = None;
+        for answer in answers {
+            match answer {
+                Ok(answer) => text += &answer,
+                Err(e) => error = error.or(Some(e)),
+            }
+        }
+        match error {
+            Some(e) if text.is_empty() => Err(e),
+            _ => Ok(Some(text)),
+        }
+    }
+}
+
+/// Every link expander, in the order of their answers
+pub fn registry() -> &'static [Box<dyn ExpandLinks>] {
+    static REGISTRY: OnceLock<Vec<Box<dyn ExpandLinks>>> = OnceLock::new();
+    REGISTRY.get_or_init(|| vec![Box::new(Twitter::new()), Box::new(MagicEden::new())])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Answers `https://example.com/{name}` with the length of the name
+    struct Example {
+        pattern: Regex,
+    }
+
+    #[async_trait]
+    impl LinkExpander for Example {
+        type Data = usize;
+
+        fn expander(&self) -> Expander {
+            Expander::Twitter
+        }
+
+        fn pattern(&self) -> &Regex {
+            &self.pattern
+        }
+
+        async fn fetch(&self, id: &str, _: &Http, _: &Config) -> Result<usize, CustomError> {
+            if id == "missing" {
+                return Err(CustomError::HttpStatus(reqwest::StatusCode::NOT_FOUND));
+            }
+            Ok(id.len())
+        }
+
+        fn render(&self, id: &str, data: &usize, _: Lang) -> String {
+            format!("{}: {}\n", id, data)
+        }
+    }
+
+    #[tokio::test]
+    async fn test_expand_links() {
+        let example = Example {
+            pattern: Regex::new(r"https://example\.com/(\w+)").unwrap(),
+        };
+        let config = Config::default();
+
+        let text = "https://example.com/rust, https://example.com/missing, \
+             https://example.com/go and https://example.com/rust again";
+        let answer = example.expand(text, Lang::En, &config).await.unwrap();
+        assert_eq!(answer.as_deref(), Some("rust: 4\ngo: 2\n"));
+
+        let answer = example.expand("no links", Lang::En, &config).await.unwrap();
+        assert_eq!(answer, None);
+
+        // Failing alone is an error, not a text without links
+        let answer = example
+            .expand("https://example.com/missing", Lang::En, &config)
+            .await;
+        assert!(matches!(answer, Err(CustomError::HttpStatus(_))));
+    }
+
+    #[test]
+    fn test_registry() {
+        let expanders: Vec<Expander> = registry().iter().map(|link| link.expander()).collect();
+        assert_eq!(expanders, vec![Expander::Twitter, Expander::Eden]);
+    }
+}
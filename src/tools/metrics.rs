@@ -0,0 +1,222 @@
+use crate::storage::{UserFilter, UserRepository};
+use crate::tools::rate_limit::Refusal;
+use prometheus::{
+    Encoder, HistogramOpts, HistogramVec, IntCounterVec, IntGaugeVec, Opts, Registry, TextEncoder,
+};
+use std::future::Future;
+use std::sync::OnceLock;
+use std::time::Instant;
+
+/// Upstream APIs the bot depends on
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Upstream {
+    CoinMarketCap,
+    CryptoCompare,
+    Binance,
+    Twitter,
+    MagicEden,
+}
+
+impl Upstream {
+    /// Value of the `api` label
+    pub fn label(&self) -> &'static str {
+        match self {
+            Upstream::CoinMarketCap => "cmc",
+            Upstream::CryptoCompare => "cryptocompare",
+            Upstream::Binance => "binance",
+            Upstream::Twitter => "twitter",
+            Upstream::MagicEden => "magiceden",
+        }
+    }
+}
+
+/// Prometheus metrics of the bot
+///
+/// A single instance lives for the whole process, see [`metrics`].
+pub struct Metrics {
+    registry: Registry,
+    commands: IntCounterVec,
+    upstream_latency: HistogramVec,
+    upstream_errors: IntCounterVec,
+    broadcast: IntCounterVec,
+    rate_limited: IntCounterVec,
+    users: IntGaugeVec,
+}
+
+impl Metrics {
+    fn new() -> Result<Self, prometheus::Error> {
+        let registry = Registry::new();
+
+        let commands = IntCounterVec::new(
+            Opts::new("bot_commands_total", "Commands handled, by command"),
+            &["command"],
+        )?;
+        let upstream_latency = HistogramVec::new(
+            HistogramOpts::new(
+                "bot_upstream_request_duration_seconds",
+                "Latency of upstream API requests",
+            )
+            .buckets(vec![0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0]),
+            &["api"],
+        )?;
+        let upstream_errors = IntCounterVec::new(
+            Opts::new(
+                "bot_upstream_errors_total",
+                "Failed upstream API requests, transport errors and non-2xx responses",
+            ),
+            &["api"],
+        )?;
+        let broadcast = IntCounterVec::new(
+            Opts::new(
+                "bot_broadcast_messages_total",
+                "Digest and /sendall deliveries, by result",
+            ),
+            &["result"],
+        )?;
+        let rate_limited = IntCounterVec::new(
+            Opts::new(
+                "bot_rate_limited_total",
+                "Updates refused by the rate limiter, by answer",
+            ),
+            &["answer"],
+        )?;
+        let users = IntGaugeVec::new(Opts::new("bot_users", "Known users, by kind"), &["kind"])?;
+
+        registry.register(Box::new(commands.clone()))?;
+        registry.register(Box::new(upstream_latency.clone()))?;
+        registry.register(Box::new(upstream_errors.clone()))?;
+        registry.register(Box::new(broadcast.clone()))?;
+        registry.register(Box::new(rate_limited.clone()))?;
+        registry.register(Box::new(users.clone()))?;
+
+        Ok(Self {
+            registry,
+            commands,
+            upstream_latency,
+            upstream_errors,
+            broadcast,
+            rate_limited,
+            users,
+        })
+    }
+
+    /// Count a handled command
+    pub fn command(&self, command: &str) {
+        self.commands.with_label_values(&[command]).inc();
+    }
+
+    /// Count a broadcast message, `sent` tells if Telegram accepted it
+    pub fn broadcast(&self, sent: bool) {
+        let result = if sent { "sent" } else { "failed" };
+        self.broadcast.with_label_values(&[result]).inc();
+    }
+
+    /// Count an update refused by the rate limiter
+    pub fn rate_limited(&self, refusal: Refusal) {
+        let answer = match refusal {
+            Refusal::SlowDown(_) => "slow_down",
+            Refusal::Banned(_) => "banned",
+            Refusal::Silent => "silent",
+        };
+        self.rate_limited.with_label_values(&[answer]).inc();
+    }
+
+    /// Time an upstream request and count it as failed on errors and non-2xx statuses
+    ///
+    /// # Arguments
+    ///
+    /// * `api` - Upstream API
+    /// * `request` - Request future, e.g. `client.get(url).send()`
+    pub async fn track<F>(&self, api: Upstream, request: F) -> reqwest::Result<reqwest::Response>
+    where
+        F: Future<Output = reqwest::Result<reqwest::Response>>,
+    {
+        let start = Instant::now();
+        let response = request.await;
+        self.upstream_latency
+            .with_label_values(&[api.label()])
+            .observe(start.elapsed().as_secs_f64());
+
+        let failed = match &response {
+            Ok(response) => !response.status().is_success(),
+            Err(_) => true,
+        };
+        if failed {
+            self.upstream_errors.with_label_values(&[api.label()]).inc();
+        }
+
+        response
+    }
+
+    /// Refresh the user gauges from the storage
+    pub async fn update_users(&self, db: &dyn UserRepository) {
+        let kinds = [
+            ("total", UserFilter::default()),
+            (
+                "notified",
+                UserFilter {
+                    digest: Some(true),
+                    with_currency: false,
+                    digest_slots: None,
+                },
+            ),
+            (
+                "with_currency",
+                UserFilter {
+                    digest: None,
+                    with_currency: true,
+                    digest_slots: None,
+                },
+            ),
+        ];
+        for (kind, filter) in kinds {
+            if let Ok(count) = db.count_users(filter).await {
+                self.users.with_label_values(&[kind]).set(count as i64);
+            }
+        }
+    }
+
+    /// Metrics in the Prometheus text format
+    pub fn render(&self) -> String {
+        let mut buffer = Vec::new();
+        if let Err(err) = TextEncoder::new().encode(&self.registry.gather(), &mut buffer) {
+            log::error!("Error encoding metrics: {}", err);
+        }
+        String::from_utf8(buffer).unwrap_or_default()
+    }
+}
+
+/// Process wide metrics
+pub fn metrics() -> &'static Metrics {
+    static METRICS: OnceLock<Metrics> = OnceLock::new();
+    METRICS.get_or_init(|| Metrics::new().expect("invalid metric definition"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::user::User;
+    use crate::storage::memory::MemoryStorage;
+
+    #[tokio::test]
+    async fn test_render_metrics() {
+        let db = MemoryStorage::new();
+        db.insert_user(User::new(1, "".to_string(), vec!["BTC".to_string()]))
+            .await
+            .unwrap();
+
+        let metrics = Metrics::new().unwrap();
+        metrics.command("price");
+        metrics.broadcast(true);
+        metrics.broadcast(false);
+        metrics.rate_limited(Refusal::Silent);
+        metrics.update_users(&db).await;
+
+        let text = metrics.render();
+        assert!(text.contains("bot_commands_total{command=\"price\"} 1"));
+        assert!(text.contains("bot_broadcast_messages_total{result=\"failed\"} 1"));
+        assert!(text.contains("bot_rate_limited_total{answer=\"silent\"} 1"));
+        assert!(text.contains("bot_users{kind=\"with_currency\"} 1"));
+        assert!(text.contains("bot_users{kind=\"notified\"} 0"));
+    }
+}
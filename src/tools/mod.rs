@@ -1,4 +1,14 @@
+pub mod asset_registry;
+pub mod html;
+pub mod http;
+pub mod link_expander;
+pub mod metrics;
+pub mod numbers;
 pub mod parse_currency;
 pub mod parse_eden;
 pub mod parse_text;
 pub mod parse_twitter;
+pub mod quota;
+pub mod rate_limit;
+pub mod shutdown;
+pub mod supervisor;
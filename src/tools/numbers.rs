@@ -0,0 +1,222 @@
+use crate::i18n::Lang;
+
+/// Significant digits shown for prices below 1
+const SIGNIFICANT_DIGITS: i32 = 4;
+/// Most decimals shown for tiny prices
+const MAX_DECIMALS: i32 = 12;
+
+/// Decimal and thousands separators of a language
+fn separators(lang: Lang) -> (char, char) {
+    match lang {
+        Lang::En => ('.', ','),
+        // No-break space, so Telegram never wraps a number
+        Lang::Ru | Lang::Uk => (',', '\u{a0}'),
+    }
+}
+
+/// Decimals needed to show `SIGNIFICANT_DIGITS` of a value below 1
+fn decimals_for(value: f64) -> usize {
+    let value = value.abs();
+    if value == 0.0 || value >= 1.0 || !value.is_finite() {
+        return 2;
+    }
+    let leading_zeros = -value.log10().floor() as i32 - 1;
+    (leading_zeros + SIGNIFICANT_DIGITS).clamp(2, MAX_DECIMALS) as usize
+}
+
+/// Format with a fixed number of decimals, separators of `lang` and trailing
+/// zeros dropped down to `min_decimals`
+fn format_decimal(value: f64, decimals: usize, min_decimals: usize, lang: Lang) -> String {
+    let (decimal_separator, thousands_separator) = separators(lang);
+    let formatted = format!("{:.*}", decimals, value.abs());
+    let (integer, fraction) = formatted
+        .split_once('.')
+        .unwrap_or((formatted.as_str(), ""));
+
+    let mut fraction = fraction.to_string();
+    while fraction.len() > min_decimals && fraction.ends_with('0') {
+        fraction.pop();
+    }
+
+    let mut result = String::new();
+    if value < 0.0 && formatted.chars().any(|c| c.is_ascii_digit() && c != '0') {
+        result.push('-');
+    }
+    for (i, digit) in integer.chars().enumerate() {
+        if i > 0 && (integer.len() - i) % 3 == 0 {
+            result.push(thousands_separator);
+        }
+        result.push(digit);
+    }
+    if !fraction.is_empty() {
+        result.push(decimal_separator);
+        result.push_str(&fraction);
+    }
+    result
+}
+
+/// Price with a precision that fits its size
+///
+/// Prices of 1 and more get two decimals and thousands separators, smaller
+/// ones keep four significant digits, so tiny prices never show as 0.00.
+///
+/// # Arguments
+///
+/// * `value` - Price
+/// * `lang` - Language of the separators
+///
+/// # Returns
+///
+/// * `String` - e.g. `68,234.12`, `0.5123` or `0.00001234` in English
+pub fn price(value: f64, lang: Lang) -> String {
+    format_decimal(value, decimals_for(value), 2, lang)
+}
+
+/// Whole number with thousands separators, e.g. `128,455,001`
+pub fn integer(value: i64, lang: Lang) -> String {
+    format_decimal(value as f64, 0, 0, lang)
+}
+
+/// Compact notation for big values like market caps, e.g. `1.2B` or `1,2 млрд`
+///
+/// The unit is picked after rounding, so 999,950 is `1M` and not `1000K`.
+pub fn compact(value: f64, lang: Lang) -> String {
+    let units = [
+        (1.0, None),
+        (1e3, Some("number-thousand")),
+        (1e6, Some("number-million")),
+        (1e9, Some("number-billion")),
+        (1e12, Some("number-trillion")),
+    ];
+    let mut unit = units
+        .iter()
+        .rposition(|(size, _)| value.abs() >= *size)
+        .unwrap_or(0);
+    loop {
+        let (size, key) = units[unit];
+        let scaled = value / size;
+        let decimals = match key {
+            Some(_) if rounded(scaled, 1).abs() < 100.0 => 1,
+            _ => 0,
+        };
+        if rounded(scaled, decimals).abs() >= 1000.0 && unit + 1 < units.len() {
+            unit += 1;
+            continue;
+        }
+        let number = format_decimal(scaled, decimals, 0, lang);
+        return match key {
+            Some(key) => lang.tr_with(key, &[("number", number.into())]),
+            None => number,
+        };
+    }
+}
+
+/// Value rounded the way `format_decimal` prints it
+fn rounded(value: f64, decimals: usize) -> f64 {
+    format!("{:.*}", decimals, value).parse().unwrap_or(value)
+}
+
+/// Signed percentage change with a coloured marker, e.g. `🟢 +2.35%`
+pub fn percent_change(value: f64, lang: Lang) -> String {
+    let percent = signed_percent(value, lang);
+    let marker = match percent.chars().next() {
+        Some('+') => "🟢",
+        Some('-') => "🔴",
+        _ => "⚪",
+    };
+    format!("{} {}", marker, percent)
+}
+
+/// Signed percentage change without a marker, e.g. `+2.35%`
+///
+/// The markers are wider than one monospace cell, tables use this form.
+pub fn signed_percent(value: f64, lang: Lang) -> String {
+    let number = format_decimal(value, 2, 2, lang);
+    if number.chars().all(|c| !c.is_ascii_digit() || c == '0') {
+        format!("{}%", number.trim_start_matches('-'))
+    } else if value > 0.0 {
+        format!("+{}%", number)
+    } else {
+        format!("{}%", number)
+    }
+}
+
+/// Positive amount typed by a user, e.g. `70,000.5` or `$70000`
+///
+/// The separators of `lang` are expected, `.` is always taken as a decimal
+/// point in Russian and Ukrainian too.
+pub fn parse_amount(text: &str, lang: Lang) -> Option<f64> {
+    let (decimal_separator, thousands_separator) = separators(lang);
+    let digits: String = text
+        .trim()
+        .trim_start_matches(['$', '€', '£', '₴'])
+        .chars()
+        .filter(|c| !c.is_whitespace() && *c != thousands_separator)
+        .map(|c| if c == decimal_separator { '.' } else { c })
+        .collect();
+    digits
+        .parse::<f64>()
+        .ok()
+        .filter(|value| value.is_finite() && *value > 0.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_price_precision() {
+        assert_eq!(price(68234.123456, Lang::En), "68,234.12");
+        assert_eq!(price(1234567.5, Lang::Ru), "1\u{a0}234\u{a0}567,50");
+        assert_eq!(price(12.3, Lang::En), "12.30");
+        assert_eq!(price(0.5123, Lang::En), "0.5123");
+        assert_eq!(price(0.1, Lang::En), "0.10");
+        assert_eq!(price(0.00001234, Lang::En), "0.00001234");
+        assert_eq!(price(0.000000001, Lang::Uk), "0,000000001");
+        assert_eq!(price(0.0, Lang::En), "0.00");
+        assert_eq!(price(-0.004, Lang::En), "-0.004");
+    }
+
+    #[test]
+    fn test_integer_and_compact() {
+        assert_eq!(integer(128455001, Lang::En), "128,455,001");
+        assert_eq!(integer(999, Lang::En), "999");
+        assert_eq!(compact(1_234_000_000.0, Lang::En), "1.2B");
+        assert_eq!(compact(1_234_000_000.0, Lang::Ru), "1,2 млрд");
+        assert_eq!(compact(512_000_000_000.0, Lang::En), "512B");
+        assert_eq!(compact(950.0, Lang::En), "950");
+        // Values rounding up to the next unit take that unit
+        assert_eq!(compact(999_950.0, Lang::En), "1M");
+        assert_eq!(compact(999_950_000.0, Lang::En), "1B");
+        assert_eq!(compact(-999_950.0, Lang::En), "-1M");
+        assert_eq!(compact(999.6, Lang::En), "1K");
+        assert_eq!(compact(99_960.0, Lang::En), "100K");
+        assert_eq!(compact(999_400.0, Lang::En), "999K");
+    }
+
+    #[test]
+    fn test_percent_change() {
+        assert_eq!(percent_change(2.345, Lang::En), "🟢 +2.35%");
+        assert_eq!(percent_change(-1.2, Lang::Ru), "🔴 -1,20%");
+        assert_eq!(percent_change(0.001, Lang::En), "⚪ 0.00%");
+        assert_eq!(percent_change(-0.001, Lang::En), "⚪ 0.00%");
+    }
+
+    #[test]
+    fn test_signed_percent() {
+        assert_eq!(signed_percent(2.345, Lang::En), "+2.35%");
+        assert_eq!(signed_percent(-1.2, Lang::Uk), "-1,20%");
+        assert_eq!(signed_percent(-0.001, Lang::En), "0.00%");
+    }
+
+    #[test]
+    fn test_parse_amount() {
+        assert_eq!(parse_amount("70,000.5", Lang::En), Some(70000.5));
+        assert_eq!(parse_amount(" $0.00012 ", Lang::En), Some(0.00012));
+        assert_eq!(parse_amount("69 500,25", Lang::Ru), Some(69500.25));
+        assert_eq!(parse_amount("1.5", Lang::Uk), Some(1.5));
+        assert_eq!(parse_amount("0", Lang::En), None);
+        assert_eq!(parse_amount("-5", Lang::En), None);
+        assert_eq!(parse_amount("btc", Lang::En), None);
+    }
+}
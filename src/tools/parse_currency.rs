@@ -1,51 +1,72 @@
+use crate::commands::price_all::select_entry;
+use crate::config::Config;
+use crate::i18n::Lang;
+use crate::models::preferences::Fiat;
+use crate::tools::asset_registry::AssetRegistry;
+use crate::tools::html;
+use crate::tools::metrics::Upstream;
+use crate::tools::numbers;
+use crate::tools::quota::record_cmc_credits;
 use regex::Regex;
 use serde_json::{Map, Value};
-use std::env;
 use std::error::Error;
 
-pub async fn parse_currency(text: &str) -> Option<String> {
-    let re = match Regex::new(r"([0-9.,]+)\s+([a-zA-Z]+)") {
-        Ok(regex) => regex,
-        Err(_) => return None,
-    };
+/// Amounts of coins in a text converted to `fiat`, `Ok(None)` without any
+pub async fn parse_currency(
+    text: &str,
+    lang: Lang,
+    fiat: Fiat,
+    registry: &AssetRegistry,
+    config: &Config,
+) -> Result<Option<String>, Box<dyn Error + Send + Sync>> {
+    let re = Regex::new(r"([0-9.,]+)\s+([a-zA-Z]+)")?;
     let text = text.to_uppercase();
     let result = re.captures_iter(&text);
     let mut coins = Vec::new();
 
     for cap in result {
-        let amount = cap.get(1)?.as_str().to_owned();
-        let currency = cap.get(2)?.as_str().to_owned();
+        let amount = cap[1].to_owned();
+        let currency = cap[2].to_owned();
         coins.push((amount, currency));
     }
 
-    if !coins.is_empty() {
-        Some(parser_coins_mult(&coins).await.ok()?) // Вернуть None, если parser_coins_mult вернет ошибку
-    } else {
-        None
+    if coins.is_empty() {
+        return Ok(None);
     }
+    let result = parser_coins_mult(&coins, lang, fiat, registry, config).await?;
+    Ok((!result.is_empty()).then_some(result))
 }
 
-async fn get_mult_value(coins: &[(String, String)]) -> Result<Map<String, Value>, Box<dyn Error>> {
+async fn get_mult_value(
+    coins: &[(String, String)],
+    fiat: Fiat,
+    config: &Config,
+) -> Result<Map<String, Value>, Box<dyn Error + Send + Sync>> {
     let url = "https://pro-api.coinmarketcap.com/v2/cryptocurrency/quotes/latest";
     let blacklist = &["NFT", "USD"];
     let crypto_symbols: Vec<&str> = coins
         .iter()
         .map(|(_, crypto)| crypto.as_str())
-        .filter(|crypto| !blacklist.contains(crypto))
+        .filter(|crypto| !blacklist.contains(crypto) && Fiat::from_code(crypto).is_none())
         .collect();
+    // Only fiat amounts, nothing to look up
+    if crypto_symbols.is_empty() {
+        return Ok(Map::new());
+    }
 
-    let token = env::var("CMC_TOKEN").expect("Fatality! CMC_PRO_API_KEY not set!");
-
-    let client = reqwest::Client::new();
-    let response = client
+    let http = config.http();
+    let request = http
         .get(url)
-        .query(&[("symbol", &crypto_symbols.join(","))])
-        .header("X-CMC_PRO_API_KEY", token)
-        .header("Accept", "application/json")
-        .send()
-        .await?;
+        .query(&[
+            ("symbol", crypto_symbols.join(",").as_str()),
+            ("convert", fiat.code()),
+        ])
+        .header("X-CMC_PRO_API_KEY", &config.cmc_token)
+        .header("Accept", "application/json");
+    let response = http.send(Upstream::CoinMarketCap, request).await?;
 
     let response_json = response.json::<Value>().await?;
+    record_cmc_credits(&response_json["status"]);
     let prices_data = response_json["data"]
         .as_object()
         .ok_or("Response does not contain valid prices data")?
@@ -54,16 +75,35 @@ async fn get_mult_value(coins: &[(String, String)]) -> Result<Map<String, Value>
     Ok(prices_data)
 }
 
-async fn parser_coins_mult(coins: &[(String, String)]) -> Result<String, Box<dyn Error>> {
-    let prices_data = get_mult_value(coins).await?;
+async fn parser_coins_mult(
+    coins: &[(String, String)],
+    lang: Lang,
+    fiat: Fiat,
+    registry: &AssetRegistry,
+    config: &Config,
+) -> Result<String, Box<dyn Error + Send + Sync>> {
+    let prices_data = get_mult_value(coins, fiat, config).await?;
 
     let result = coins
         .iter()
         .filter_map(|(amount, crypto)| {
             prices_data.get(crypto).and_then(|price_data| {
-                let price = price_data[0]["quote"]["USD"]["price"].as_f64()?;
-                let amount_usd = amount.parse::<f64>().ok()? * price;
-                Some(format!("💰{} {}\n{:.4} usd\n", amount, crypto, amount_usd))
+                // CoinMarketCap returns every coin sharing the ticker
+                let cmc_id = registry
+                    .resolve(crypto)
+                    .into_iter()
+                    .find(|asset| asset.symbol.eq_ignore_ascii_case(crypto))
+                    .map(|asset| asset.cmc_id);
+                let entry = select_entry(price_data, cmc_id)?;
+                let price = entry["quote"][fiat.code()]["price"].as_f64()?;
+                let amount_fiat = amount.parse::<f64>().ok()? * price;
+                Some(format!(
+                    "💰{} {}\n{} {}\n",
+                    html::escape(amount),
+                    html::bold(crypto),
+                    html::code(&numbers::price(amount_fiat, lang)),
+                    fiat.code()
+                ))
             })
         })
         .collect::<Vec<_>>()
@@ -1,52 +1,75 @@
+use crate::config::Config;
+use crate::i18n::Lang;
 use crate::models::errors::CustomError;
-use log::{debug, error};
+use crate::models::preferences::Expander;
+use crate::tools::html;
+use crate::tools::http::Http;
+use crate::tools::link_expander::LinkExpander;
+use crate::tools::metrics::Upstream;
+use crate::tools::numbers;
+use async_trait::async_trait;
+use log::debug;
 use regex::Regex;
-use reqwest::Client;
 use serde::{Deserialize, Serialize};
-use std::error::Error;
 
-pub async fn parse_eden_command(text_to_parse: &str) -> Option<String> {
-    let res = parse_eden_link(text_to_parse).await;
+/// Magic Eden collections, `https://magiceden.io/marketplace/{collection}`
+pub struct MagicEden {
+    pattern: Regex,
+}
 
-    match res {
-        Ok(text) => Some(text),
-        Err(e) => {
-            debug!("Eden Error: {}", e);
-            None
+impl MagicEden {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl Default for MagicEden {
+    fn default() -> Self {
+        Self {
+            pattern: Regex::new(r"https?://(?:www\.)?magiceden\.io/(?:[^?\s/]+/)*([^?\s/]+)")
+                .expect("valid regex"),
         }
     }
 }
 
-/// Parses the eden link and returns the text to be sent to the chat
-async fn parse_eden_link(text_to_parse: &str) -> Result<String, Box<dyn Error>> {
-    let re = Regex::new(r"(?:http|https)://(?:www\.)?magiceden\.io/[^? ]+")?;
-    let result = re.find_iter(text_to_parse);
+#[async_trait]
+impl LinkExpander for MagicEden {
+    /// Stats of the collection and its listings
+    type Data = (Collection, Vec<Listing>);
 
-    let client = Client::new();
+    fn expander(&self) -> Expander {
+        Expander::Eden
+    }
 
-    let mut text = String::new();
-    for item in result {
-        let parts: Vec<&str> = item.as_str().split('/').collect();
-        if let Some(part) = parts.last() {
-            match parse_eden(part.to_string(), &client).await {
-                Ok(parsed_text) => {
-                    text += &parsed_text;
-                }
-                Err(e) => {
-                    error!("Error parsing eden link: {}", e);
-                }
-            }
-        } else {
-            error!("Couldn't get the last part of the URL");
-        }
+    fn pattern(&self) -> &Regex {
+        &self.pattern
+    }
+
+    async fn fetch(
+        &self,
+        collection: &str,
+        http: &Http,
+        _: &Config,
+    ) -> Result<Self::Data, CustomError> {
+        debug!("Parsing eden link: {}", collection);
+        let stats = get_eden_stats(collection.to_string(), http).await?;
+        let listings = get_eden_prices(collection.to_string(), http, stats.listed_count).await?;
+        Ok((stats, listings))
     }
 
-    Ok(text)
+    fn render(&self, collection: &str, (stats, listings): &Self::Data, lang: Lang) -> String {
+        let url = format!("https://magiceden.io/marketplace/{}", collection);
+        format!(
+            "{}\n{}",
+            html::link(&url, collection),
+            sort_eden(listings, stats.floor_price, lang)
+        )
+    }
 }
 
 #[derive(Debug, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
-struct Listing {
+pub struct Listing {
     pda_address: String,
     auction_house: String,
     token_address: String,
@@ -58,25 +81,17 @@ struct Listing {
 
 #[derive(Debug, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
-struct Collection {
+pub struct Collection {
     symbol: String,
     floor_price: f64,
     listed_count: u32,
     volume_all: f64,
 }
 
-async fn parse_eden(collection: String, client: &Client) -> Result<String, CustomError> {
-    debug!("Parsing eden link: {}", collection);
-    let json_stats: Collection = get_eden_stats(collection.clone(), client).await?;
-    let json: Vec<Listing> = get_eden_prices(collection, client, json_stats.listed_count).await?;
-
-    Ok(sort_eden(&json, json_stats.floor_price).await)
-}
-
 /// Gets price data from eden
 async fn get_eden_prices(
     collection: String,
-    client: &Client,
+    http: &Http,
     listed_count: u32,
 ) -> Result<Vec<Listing>, CustomError> {
     let mut listings: Vec<Listing> = Vec::with_capacity((listed_count + 10) as usize); // Add 10 to the capacity to avoid reallocation's
@@ -89,7 +104,7 @@ async fn get_eden_prices(
             collection, offset
         );
 
-        let response = client.get(&url).send().await?;
+        let response = http.send(Upstream::MagicEden, http.get(&url)).await?;
 
         let status = response.status();
         if !status.is_success() {
@@ -109,13 +124,13 @@ async fn get_eden_prices(
 }
 
 /// Gets stats from eden
-async fn get_eden_stats(collection: String, client: &Client) -> Result<Collection, CustomError> {
+async fn get_eden_stats(collection: String, http: &Http) -> Result<Collection, CustomError> {
     let url = format!(
         "https://api-mainnet.magiceden.dev/v2/collections/{}/stats",
         collection
     );
 
-    let response = client.get(&url).send().await?;
+    let response = http.send(Upstream::MagicEden, http.get(&url)).await?;
 
     let status = response.status();
     if !status.is_success() {
@@ -126,7 +141,7 @@ async fn get_eden_stats(collection: String, client: &Client) -> Result<Collectio
 }
 
 /// Sorts the eden prices into 5 categories
-async fn sort_eden(data: &[Listing], floor_gwei: f64) -> String {
+fn sort_eden(data: &[Listing], floor_gwei: f64, lang: Lang) -> String {
     let floor = floor_gwei / 1_000_000_000.0; // convert to sol
 
     // Initialize the variables for each category
@@ -152,23 +167,29 @@ async fn sort_eden(data: &[Listing], floor_gwei: f64) -> String {
             _ => max_price += 1,
         }
     }
-    // Return the text to be sent to the chat
+    // Return the text to be sent to the chat: the floor and a table of the categories
+    let sol = |value: f64| numbers::price(value, lang);
+    let step = |n: f64| sol(floor + change * n);
+    let range = |from: f64, to: f64| format!("{} – {}", step(from), step(to));
+    let rows = vec![
+        vec![lang.tr("eden-price-column"), lang.tr("eden-count-column")],
+        vec![
+            lang.tr_with("eden-up-to", &[("price", step(1.0).into())]),
+            low_price.to_string(),
+        ],
+        vec![range(1.0, 2.0), sol_price.to_string()],
+        vec![range(2.0, 3.0), solhalf_price.to_string()],
+        vec![range(3.0, 4.0), two_sol_price.to_string()],
+        vec![
+            lang.tr_with("eden-over", &[("price", step(4.0).into())]),
+            max_price.to_string(),
+        ],
+    ];
+
     format!(
-        "💎Флор: {:.5}\nПредметов ценой до {:.5} sol: {}\nот {:.5} sol до {:.5} sol: {}\nот {:.5} sol до {:.5} sol: {}\nот {:.5} sol до {:.5} sol: {}\nБольше {:.5} sol: {}\n",
-        floor,
-        floor + change,
-        low_price,
-        floor + change,
-        floor + change * 2.0,
-        sol_price,
-        floor + change * 2.0,
-        floor + change * 3.0,
-        solhalf_price,
-        floor + change * 3.0,
-        floor + change * 4.0,
-        two_sol_price,
-        floor + change * 4.0,
-        max_price,
+        "{}\n{}\n",
+        lang.tr_with("eden-floor", &[("floor", sol(floor).into())]),
+        html::table(&rows)
     )
 }
 
@@ -225,20 +246,45 @@ mod tests {
                 price: 12.0, // 12 SOL
             },
         ];
-        let expected_output = "💎Флор: 1.00000\nПредметов ценой до 2.00000 sol: 1\nот 2.00000 sol до 3.00000 sol: 1\nот 3.00000 sol до 4.00000 sol: 0\nот 4.00000 sol до 5.00000 sol: 0\nБольше 5.00000 sol: 3\n";
-        assert_eq!(sort_eden(&listings, 1000000000.0).await, expected_output);
+        let expected_output = concat!(
+            "💎Флор: <b>1,00 sol</b>\n<pre>",
+            "Цена, sol    Предметов\n",
+            "до 2,00              1\n",
+            "2,00 – 3,00          1\n",
+            "3,00 – 4,00          0\n",
+            "4,00 – 5,00          0\n",
+            "больше 5,00          3</pre>\n"
+        );
+        assert_eq!(
+            sort_eden(&listings, 1000000000.0, Lang::Ru),
+            expected_output
+        );
+    }
+
+    #[test]
+    fn test_eden_pattern() {
+        let eden = MagicEden::new();
+        let text = "https://magiceden.io/marketplace/degods?tab=items and www.magiceden.io/y00ts";
+        let collections: Vec<&str> = eden
+            .pattern()
+            .captures_iter(text)
+            .map(|captures| captures.get(1).unwrap().as_str())
+            .collect();
+        assert_eq!(collections, vec!["degods"]);
+        let text = "https://www.magiceden.io/collections/solana/okay_bears";
+        assert_eq!(&eden.pattern().captures(text).unwrap()[1], "okay_bears");
     }
 
     #[tokio::test]
     async fn test_get_eden_stats() {
-        let client = Client::new();
+        let client = Http::new(&Config::default()).unwrap();
         let json: Collection = get_eden_stats("degods".to_string(), &client).await.unwrap();
         assert_eq!(json.symbol, "degods");
     }
 
     #[tokio::test]
     async fn test_invslid_get_eden_listings() {
-        let client = Client::new();
+        let client = Http::new(&Config::default()).unwrap();
         let json: Vec<Listing> = get_eden_prices("InvalidCollection2929".to_string(), &client, 10)
             .await
             .unwrap();
@@ -248,7 +294,7 @@ mod tests {
 
     #[tokio::test]
     async fn test_get_eden_listings() {
-        let client = Client::new();
+        let client = Http::new(&Config::default()).unwrap();
         let json: Vec<Listing> = get_eden_prices("degods".to_string(), &client, 10)
             .await
             .unwrap();
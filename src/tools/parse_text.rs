@@ -1,12 +1,163 @@
+use crate::config::Config;
+use crate::i18n::Lang;
+use crate::models::preferences::{Expander, Preferences};
+use crate::tools::asset_registry::AssetRegistry;
+use crate::tools::link_expander;
+use crate::tools::metrics::Upstream;
 use crate::tools::parse_currency::parse_currency;
-use crate::tools::parse_eden::parse_eden_command;
-use crate::tools::parse_twitter::parse_twitter_links;
+use crate::tools::quota::{quotas, QuotaLevel};
+use futures::future::{join_all, BoxFuture, FutureExt, TryFutureExt};
+use log::warn;
+use std::error::Error;
+use std::time::Duration;
 
-pub async fn parse_text(text: &str) -> String {
-    let mut result = String::new();
-    result += &parse_currency(text).await.unwrap_or_default(); // parse currency from CoinMarketCap API
-    result += &parse_twitter_links(text).await.unwrap_or_default(); // parse twitter links
-    result += &*parse_eden_command(text).await.unwrap_or_default(); // Parse collections from magic eden
+/// Answer to a free-text message, from the parsers on in both `features`
+/// and the expanders of the user's `preferences`
+///
+/// The expanders run concurrently, each limited to `timeouts.expander_secs`.
+/// Their answers come in a fixed order, prices first and then the links in
+/// the order of the [`link_expander::registry`]. The ones failing or running
+/// late are left out with a note. Expanders whose API is into its reserve of
+/// `quotas` are skipped, the credits left are kept for commands. Prices are
+/// in the fiat of the `preferences`, picked among the coins sharing a ticker
+/// through the `registry`.
+pub async fn parse_text(
+    text: &str,
+    lang: Lang,
+    preferences: &Preferences,
+    registry: &AssetRegistry,
+    config: &Config,
+) -> String {
+    let on = |expander: Expander| {
+        enabled(expander, config)
+            && preferences.expanders.get(expander)
+            && quotas().level(upstream(expander), &config.quotas) == QuotaLevel::Normal
+    };
 
+    let mut tasks: Vec<(Expander, BoxFuture<Answer>)> = vec![];
+    if on(Expander::Currency) {
+        // parse currency from CoinMarketCap API
+        tasks.push((
+            Expander::Currency,
+            parse_currency(text, lang, preferences.fiat, registry, config).boxed(),
+        ));
+    }
+    for link in link_expander::registry() {
+        if on(link.expander()) {
+            let answer = link.expand(text, lang, config).map_err(Into::into);
+            tasks.push((link.expander(), answer.boxed()));
+        }
+    }
+
+    let (mut result, partial) = run_expanders(tasks, config.expander_timeout()).await;
+    if partial && !result.is_empty() {
+        result += "\n";
+        result += &lang.tr("parse-partial");
+    }
     result
 }
+
+/// Returns true if the expander is on in `features`
+fn enabled(expander: Expander, config: &Config) -> bool {
+    match expander {
+        Expander::Currency => config.features.currency_parser,
+        Expander::Twitter => config.features.twitter_parser,
+        Expander::Eden => config.features.eden_parser,
+    }
+}
+
+/// API the expander looks up
+fn upstream(expander: Expander) -> Upstream {
+    match expander {
+        Expander::Currency => Upstream::CoinMarketCap,
+        Expander::Twitter => Upstream::Twitter,
+        Expander::Eden => Upstream::MagicEden,
+    }
+}
+
+/// Answer of an expander, `Ok(None)` when there is nothing to add
+type Answer = Result<Option<String>, Box<dyn Error + Send + Sync>>;
+
+/// Run the expanders concurrently
+///
+/// # Arguments
+///
+/// * `tasks` - Expanders and their answers
+/// * `timeout` - Time each expander gets
+///
+/// # Returns
+///
+/// * `(String, bool)` - Answers joined in the order of `tasks`, and whether
+///   any expander failed or ran out of time
+async fn run_expanders(
+    tasks: Vec<(Expander, BoxFuture<'_, Answer>)>,
+    timeout: Duration,
+) -> (String, bool) {
+    let answers = join_all(tasks.into_iter().map(|(expander, task)| async move {
+        match tokio::time::timeout(timeout, task).await {
+            Ok(Ok(answer)) => Some(answer.unwrap_or_default()),
+            Ok(Err(err)) => {
+                warn!("Expander {} failed: {}", expander.code(), err);
+                None
+            }
+            Err(_) => {
+                warn!("Expander {} timed out", expander.code());
+                None
+            }
+        }
+    }))
+    .await;
+
+    let partial = answers.iter().any(Option::is_none);
+    (answers.into_iter().flatten().collect(), partial)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn answer(text: &'static str, delay_ms: u64) -> BoxFuture<'static, Answer> {
+        async move {
+            tokio::time::sleep(Duration::from_millis(delay_ms)).await;
+            Ok(Some(text.to_string()))
+        }
+        .boxed()
+    }
+
+    #[tokio::test]
+    async fn test_run_expanders_order_and_timeout() {
+        // The slow first answer still comes first
+        let tasks = vec![
+            (Expander::Currency, answer("prices ", 50)),
+            (Expander::Twitter, answer("twitter ", 0)),
+            (Expander::Eden, async { Ok(None) }.boxed()),
+        ];
+        let (text, timed_out) = run_expanders(tasks, Duration::from_secs(1)).await;
+        assert_eq!(text, "prices twitter ");
+        assert!(!timed_out);
+
+        // A late expander is dropped without delaying the others
+        let started = std::time::Instant::now();
+        let tasks = vec![
+            (Expander::Currency, answer("prices ", 0)),
+            (Expander::Twitter, answer("twitter ", 5_000)),
+            (Expander::Eden, answer("eden", 10)),
+        ];
+        let (text, timed_out) = run_expanders(tasks, Duration::from_millis(200)).await;
+        assert_eq!(text, "prices eden");
+        assert!(timed_out);
+        assert!(started.elapsed() < Duration::from_secs(2));
+
+        // A failed expander is left out like a late one
+        let tasks = vec![
+            (Expander::Currency, answer("prices ", 0)),
+            (
+                Expander::Twitter,
+                async { Err("rate limited".into()) }.boxed(),
+            ),
+        ];
+        let (text, partial) = run_expanders(tasks, Duration::from_secs(1)).await;
+        assert_eq!(text, "prices ");
+        assert!(partial);
+    }
+}
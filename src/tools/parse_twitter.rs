@@ -1,59 +1,76 @@
+use crate::config::Config;
+use crate::i18n::Lang;
 use crate::models::errors::CustomError;
+use crate::models::preferences::Expander;
+use crate::tools::http::Http;
+use crate::tools::link_expander::LinkExpander;
+use crate::tools::metrics::Upstream;
+use crate::tools::numbers;
+use async_trait::async_trait;
 use chrono::DateTime;
 use chrono::Utc;
-use log::{debug, error};
 use regex::Regex;
 use reqwest::header::{HeaderMap, HeaderValue};
-use reqwest::Client;
-use serde_json::Value;
-use std::env;
-
-pub async fn parse_twitter_links(text_to_parse: &str) -> Option<String> {
-    let res = parse(text_to_parse).await;
-
-    match res {
-        Ok(Some(text)) => Some(text),
-        Ok(None) => None,
-        Err(e) => {
-            debug!("Twitter Error: {}", e);
-            None
-        }
+use serde_json::{Map, Value};
+
+/// Twitter profiles, `https://twitter.com/{username}`
+pub struct Twitter {
+    pattern: Regex,
+}
+
+impl Twitter {
+    pub fn new() -> Self {
+        Self::default()
     }
 }
 
-async fn parse(text_to_parse: &str) -> Result<Option<String>, CustomError> {
-    let re = Regex::new(r"https?://twitter.com/\w+")?;
-    let result = re.find_iter(text_to_parse);
-
-    let client = Client::new();
-
-    let mut text = String::new();
-    let mut found = false;
-    for item in result {
-        found = true;
-        let parts: Vec<&str> = item.as_str().split('/').collect();
-        if let Some(part) = parts.last() {
-            match parse_twitter(part.to_string(), &client).await {
-                Ok(parsed_text) => {
-                    text += &parsed_text;
-                }
-                Err(e) => {
-                    error!("Error parsing Twitter link: {}", e);
-                }
-            }
-        } else {
-            error!("Couldn't get the last part of the URL");
+impl Default for Twitter {
+    fn default() -> Self {
+        Self {
+            pattern: Regex::new(r"https?://twitter.com/(\w+)").expect("valid regex"),
         }
     }
+}
+
+#[async_trait]
+impl LinkExpander for Twitter {
+    type Data = Map<String, Value>;
+
+    fn expander(&self) -> Expander {
+        Expander::Twitter
+    }
+
+    fn pattern(&self) -> &Regex {
+        &self.pattern
+    }
+
+    async fn fetch(
+        &self,
+        username: &str,
+        http: &Http,
+        config: &Config,
+    ) -> Result<Self::Data, CustomError> {
+        get_twitter_user(username, http, &config.twitter_token).await
+    }
 
-    if found {
-        Ok(Some(text))
-    } else {
-        Ok(None)
+    fn render(&self, _: &str, data: &Self::Data, lang: Lang) -> String {
+        format_twitter(data, lang)
     }
 }
 
-async fn format_twitter(data: &serde_json::Map<String, Value>) -> String {
+/// Answer to the Twitter links of a text, `Ok(None)` without links
+#[cfg(test)]
+pub async fn parse_twitter_links(
+    text_to_parse: &str,
+    lang: Lang,
+    config: &Config,
+) -> Result<Option<String>, CustomError> {
+    use crate::tools::link_expander::ExpandLinks;
+
+    Twitter::new().expand(text_to_parse, lang, config).await
+}
+
+fn format_twitter(data: &Map<String, Value>, lang: Lang) -> String {
     // Try to parse the date and time from the input string
     let created_at = data["created_at"].as_str().and_then(|s| {
         DateTime::parse_from_rfc3339(s)
@@ -64,33 +81,49 @@ async fn format_twitter(data: &serde_json::Map<String, Value>) -> String {
     // Format the date and time as desired, or use a default value if parsing failed
     let formatted_created_at = match created_at {
         Some(dt) => dt.format("%Y-%m-%d %H:%M:%S").to_string(),
-        None => "время не найдено".to_string(),
+        None => lang.tr("twitter-created-unknown"),
     };
 
-    let text = format!(
-        "📨Twitter \nНазвание: {}\nПодписчики: {}\nТвитов: {}\nСоздан: {}\n",
-        data["username"],
-        data["public_metrics"]["followers_count"],
-        data["public_metrics"]["tweet_count"],
-        formatted_created_at
-    );
-    text
+    let count = |field: &str| {
+        data["public_metrics"][field]
+            .as_i64()
+            .map(|value| numbers::integer(value, lang))
+            .unwrap_or_default()
+    };
+
+    lang.tr_with(
+        "twitter-info",
+        &[
+            (
+                "username",
+                data["username"].as_str().unwrap_or_default().into(),
+            ),
+            ("followers", count("followers_count").into()),
+            ("tweets", count("tweet_count").into()),
+            ("created", formatted_created_at.into()),
+        ],
+    ) + "\n"
 }
 
-async fn parse_twitter(name: String, client: &Client) -> Result<String, CustomError> {
+/// Profile of a Twitter user
+async fn get_twitter_user(
+    name: &str,
+    http: &Http,
+    token: &str,
+) -> Result<Map<String, Value>, CustomError> {
     let url = format!(
         "https://api.twitter.com/2/users/by/username/{}?user.fields=public_metrics,created_at",
         name
     );
 
-    let token = env::var("TWITTER_TOKEN").expect("Fatality! TWITTER_TOKEN not set!");
     let mut headers = HeaderMap::new();
     headers.insert(
         reqwest::header::AUTHORIZATION,
         HeaderValue::from_str(&format!("Bearer {}", token))?,
     );
 
-    let response = client.get(&url).headers(headers).send().await?;
+    let request = http.get(&url).headers(headers);
+    let response = http.send(Upstream::Twitter, request).await?;
     let status = response.status();
 
     if !status.is_success() {
@@ -98,10 +131,8 @@ async fn parse_twitter(name: String, client: &Client) -> Result<String, CustomEr
     }
 
     let json = response.json::<Value>().await?;
-    if let Some(data) = json["data"].as_object() {
-        let formatted_data = format_twitter(data).await;
-        Ok(formatted_data)
-    } else {
-        Err(CustomError::HttpStatus(reqwest::StatusCode::BAD_REQUEST))
+    match json["data"].as_object() {
+        Some(data) => Ok(data.clone()),
+        None => Err(CustomError::HttpStatus(reqwest::StatusCode::BAD_REQUEST)),
     }
 }
@@ -0,0 +1,347 @@
+use crate::config::QuotasConfig;
+use crate::models::quota::QuotaUsage;
+use crate::storage::{QuotaRepository, StorageResult};
+use crate::tools::metrics::Upstream;
+use crate::tools::supervisor::JobResult;
+use chrono::Utc;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::fmt::Write;
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::Duration;
+use tokio::time;
+
+/// Time between saves of the usage
+const FLUSH_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Usage of a provider against its budgets
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum QuotaLevel {
+    /// Below the reserve of every budget
+    Normal,
+    /// Into the reserve of a budget, only essential lookups are made
+    Low,
+    /// A budget is used up, no requests are sent
+    Exhausted,
+}
+
+/// Calls and credits of a provider
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+struct Usage {
+    calls: i64,
+    credits: i64,
+}
+
+impl Usage {
+    fn add(&mut self, calls: i64, credits: i64) {
+        self.calls += calls;
+        self.credits += credits;
+    }
+}
+
+#[derive(Default)]
+struct QuotaState {
+    /// Current UTC day, `YYYY-MM-DD`
+    day: String,
+    /// Usage of the current day, by provider
+    today: HashMap<String, Usage>,
+    /// Usage of the current month, by provider
+    month: HashMap<String, Usage>,
+    /// Usage not saved yet, by provider and day
+    pending: HashMap<(String, String), Usage>,
+    /// Last `x-rate-limit-remaining` of each provider
+    remaining: HashMap<String, i64>,
+}
+
+impl QuotaState {
+    /// Start a new day, and a new month on its first day
+    fn roll(&mut self, day: &str) {
+        if self.day == day {
+            return;
+        }
+        if self.day.get(..7) != day.get(..7) {
+            self.month.clear();
+        }
+        self.today.clear();
+        self.day = day.to_string();
+    }
+}
+
+/// Upstream API usage of the current day and month
+///
+/// Requests are counted by `Http::send`, APIs reporting the credits a request
+/// cost add the rest. The usage is saved by [`quota_job`] and loaded on
+/// startup, so budgets carry over restarts. A single instance lives for the
+/// whole process, see [`quotas`].
+#[derive(Default)]
+pub struct Quotas {
+    state: Mutex<QuotaState>,
+}
+
+impl Quotas {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Count requests and credits of a provider today
+    pub fn record(&self, api: Upstream, calls: i64, credits: i64) {
+        self.record_on(&today(), api.label(), calls, credits);
+    }
+
+    fn record_on(&self, day: &str, provider: &str, calls: i64, credits: i64) {
+        let mut state = self.state.lock().unwrap();
+        state.roll(day);
+        state
+            .today
+            .entry(provider.to_string())
+            .or_default()
+            .add(calls, credits);
+        state
+            .month
+            .entry(provider.to_string())
+            .or_default()
+            .add(calls, credits);
+        state
+            .pending
+            .entry((provider.to_string(), day.to_string()))
+            .or_default()
+            .add(calls, credits);
+    }
+
+    /// Remember the requests left in the rate limit window of a provider
+    pub fn set_remaining(&self, api: Upstream, remaining: i64) {
+        let mut state = self.state.lock().unwrap();
+        state.remaining.insert(api.label().to_string(), remaining);
+    }
+
+    /// Usage level of a provider against its budgets
+    pub fn level(&self, api: Upstream, config: &QuotasConfig) -> QuotaLevel {
+        self.level_on(&today(), api.label(), config)
+    }
+
+    fn level_on(&self, day: &str, provider: &str, config: &QuotasConfig) -> QuotaLevel {
+        let budget = config.budget(provider);
+        let mut state = self.state.lock().unwrap();
+        state.roll(day);
+        let credits = |usage: &HashMap<String, Usage>| {
+            usage.get(provider).map(|usage| usage.credits).unwrap_or(0)
+        };
+        let spent = [
+            (credits(&state.today), budget.daily_credits),
+            (credits(&state.month), budget.monthly_credits),
+        ];
+
+        let mut level = QuotaLevel::Normal;
+        for (credits, limit) in spent.into_iter().filter(|(_, limit)| *limit > 0) {
+            let limit = limit as i64;
+            if credits >= limit {
+                return QuotaLevel::Exhausted;
+            }
+            if credits * 100 >= limit * i64::from(config.reserve_percent) {
+                level = QuotaLevel::Low;
+            }
+        }
+        level
+    }
+
+    /// Load the usage of the current month
+    pub async fn load(&self, repository: &dyn QuotaRepository) -> StorageResult<()> {
+        let day = today();
+        let usage = repository.get_usage(&format!("{}-01", &day[..7])).await?;
+
+        let mut state = self.state.lock().unwrap();
+        state.roll(&day);
+        for stored in usage {
+            state
+                .month
+                .entry(stored.provider.clone())
+                .or_default()
+                .add(stored.calls, stored.credits);
+            if stored.day == day {
+                state
+                    .today
+                    .entry(stored.provider)
+                    .or_default()
+                    .add(stored.calls, stored.credits);
+            }
+        }
+        Ok(())
+    }
+
+    /// Save the usage counted since the last save
+    ///
+    /// Usage that fails to save is kept for the next attempt.
+    pub async fn flush(&self, repository: &dyn QuotaRepository) -> StorageResult<()> {
+        let pending = std::mem::take(&mut self.state.lock().unwrap().pending);
+        let mut result = Ok(());
+        for ((provider, day), usage) in pending {
+            let stored = QuotaUsage {
+                provider: provider.clone(),
+                day: day.clone(),
+                calls: usage.calls,
+                credits: usage.credits,
+            };
+            if let Err(err) = repository.add_usage(&stored).await {
+                let mut state = self.state.lock().unwrap();
+                state
+                    .pending
+                    .entry((provider, day))
+                    .or_default()
+                    .add(usage.calls, usage.credits);
+                result = Err(err);
+            }
+        }
+        result
+    }
+
+    /// Usage and budgets of every provider used this month, for /quota
+    pub fn report(&self, config: &QuotasConfig) -> String {
+        let day = today();
+        let mut providers: Vec<String> = {
+            let mut state = self.state.lock().unwrap();
+            state.roll(&day);
+            state.month.keys().cloned().collect()
+        };
+        if providers.is_empty() {
+            return "No upstream requests this month".to_string();
+        }
+        providers.sort();
+
+        let limit = |limit: u64| match limit {
+            0 => "no limit".to_string(),
+            limit => limit.to_string(),
+        };
+        let mut report = String::new();
+        for provider in providers {
+            let level = self.level_on(&day, &provider, config);
+            let state = self.state.lock().unwrap();
+            let today = state.today.get(&provider).copied().unwrap_or_default();
+            let month = state.month.get(&provider).copied().unwrap_or_default();
+            let budget = config.budget(&provider);
+            let _ = write!(
+                report,
+                "{}: {:?}\n  today {} calls, {} / {} credits\n  month {} calls, {} / {} credits\n",
+                provider,
+                level,
+                today.calls,
+                today.credits,
+                limit(budget.daily_credits),
+                month.calls,
+                month.credits,
+                limit(budget.monthly_credits),
+            );
+            if let Some(remaining) = state.remaining.get(&provider) {
+                let _ = writeln!(report, "  rate limit: {} requests left", remaining);
+            }
+        }
+        report
+    }
+}
+
+/// Current UTC day, `YYYY-MM-DD`
+fn today() -> String {
+    Utc::now().format("%Y-%m-%d").to_string()
+}
+
+/// Quota tracker of the process
+pub fn quotas() -> &'static Quotas {
+    static QUOTAS: OnceLock<Quotas> = OnceLock::new();
+    QUOTAS.get_or_init(Quotas::new)
+}
+
+/// Count the credits of a CoinMarketCap response beyond the one of its request
+///
+/// # Arguments
+///
+/// * `status` - `status` object of the response, with its `credit_count`
+pub fn record_cmc_credits(status: &Value) {
+    if let Some(credits) = status["credit_count"].as_i64() {
+        if credits > 1 {
+            quotas().record(Upstream::CoinMarketCap, 0, credits - 1);
+        }
+    }
+}
+
+/// Saves the usage every minute, run under the supervisor
+pub async fn quota_job(repository: Arc<dyn QuotaRepository>) -> JobResult {
+    loop {
+        time::sleep(FLUSH_INTERVAL).await;
+        quotas().flush(repository.as_ref()).await?;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::BudgetConfig;
+    use crate::storage::memory::MemoryStorage;
+
+    fn config() -> QuotasConfig {
+        QuotasConfig {
+            cmc: BudgetConfig {
+                daily_credits: 10,
+                monthly_credits: 100,
+            },
+            twitter: BudgetConfig::default(),
+            reserve_percent: 80,
+        }
+    }
+
+    #[test]
+    fn test_quota_levels() {
+        let quotas = Quotas::new();
+        let config = config();
+        let level = |day| quotas.level_on(day, "cmc", &config);
+
+        quotas.record_on("2024-08-01", "cmc", 7, 7);
+        assert_eq!(level("2024-08-01"), QuotaLevel::Normal);
+        quotas.record_on("2024-08-01", "cmc", 1, 1);
+        assert_eq!(level("2024-08-01"), QuotaLevel::Low);
+        quotas.record_on("2024-08-01", "cmc", 1, 2);
+        assert_eq!(level("2024-08-01"), QuotaLevel::Exhausted);
+
+        // A new day resets the daily budget, not the monthly one
+        assert_eq!(level("2024-08-02"), QuotaLevel::Normal);
+        quotas.record_on("2024-08-02", "cmc", 75, 75);
+        assert_eq!(level("2024-08-02"), QuotaLevel::Exhausted);
+        assert_eq!(level("2024-08-03"), QuotaLevel::Low);
+        assert_eq!(level("2024-09-01"), QuotaLevel::Normal);
+
+        // Providers without budgets are only counted
+        quotas.record_on("2024-09-01", "twitter", 1000, 1000);
+        assert_eq!(
+            quotas.level_on("2024-09-01", "twitter", &config),
+            QuotaLevel::Normal
+        );
+    }
+
+    #[tokio::test]
+    async fn test_quota_flush_and_load() {
+        let storage = MemoryStorage::new();
+        let quotas = Quotas::new();
+        quotas.record(Upstream::CoinMarketCap, 2, 5);
+        quotas.record(Upstream::Twitter, 1, 1);
+        quotas.flush(&storage).await.unwrap();
+        quotas.record(Upstream::CoinMarketCap, 1, 1);
+        quotas.flush(&storage).await.unwrap();
+
+        let mut stored = storage.get_usage(&today()).await.unwrap();
+        stored.sort_by(|a, b| a.provider.cmp(&b.provider));
+        assert_eq!(stored[0].provider, "cmc");
+        assert_eq!((stored[0].calls, stored[0].credits), (3, 6));
+        assert_eq!((stored[1].calls, stored[1].credits), (1, 1));
+
+        // A restarted bot starts from the saved usage
+        let restarted = Quotas::new();
+        restarted.load(&storage).await.unwrap();
+        let mut config = config();
+        config.cmc.daily_credits = 6;
+        assert_eq!(
+            restarted.level(Upstream::CoinMarketCap, &config),
+            QuotaLevel::Exhausted
+        );
+        let report = restarted.report(&config);
+        assert!(report.contains("cmc: Exhausted"));
+        assert!(report.contains("today 3 calls, 6 / 6 credits"));
+    }
+}
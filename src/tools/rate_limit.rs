@@ -0,0 +1,301 @@
+use crate::config::RateLimitConfig;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// Buckets kept before the full ones are dropped, a full bucket is the same
+/// as none
+const PRUNE_ABOVE: usize = 10_000;
+
+/// Why a request was refused
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Refusal {
+    /// First refusal in a row, answered with the time until enough tokens
+    SlowDown(Duration),
+    /// The user was just banned for the time, answered once
+    Banned(Duration),
+    /// Another refusal in a row or a ban still running, not answered
+    Silent,
+}
+
+/// Tokens of a user or a chat
+#[derive(Clone, Copy, Debug)]
+struct Bucket {
+    tokens: f64,
+    updated: Instant,
+    /// Refusals since the last allowed request
+    refused: u32,
+}
+
+impl Bucket {
+    fn full(burst: u32, now: Instant) -> Self {
+        Self {
+            tokens: f64::from(burst),
+            updated: now,
+            refused: 0,
+        }
+    }
+
+    /// Add the tokens earned since the last update, up to `burst`
+    fn refill(&mut self, now: Instant, burst: u32, per_minute: u32) {
+        let earned = now.duration_since(self.updated).as_secs_f64() * rate(per_minute);
+        self.tokens = (self.tokens + earned).min(f64::from(burst));
+        self.updated = now;
+    }
+
+    /// Time until the bucket holds `cost` tokens
+    fn wait(&self, cost: u32, per_minute: u32) -> Duration {
+        let missing = (f64::from(cost) - self.tokens).max(0.0);
+        Duration::from_secs((missing / rate(per_minute)).ceil() as u64)
+    }
+}
+
+/// Tokens per second
+fn rate(per_minute: u32) -> f64 {
+    f64::from(per_minute.max(1)) / 60.0
+}
+
+#[derive(Default)]
+struct LimiterState {
+    users: HashMap<i64, Bucket>,
+    chats: HashMap<i64, Bucket>,
+    /// End of the ban of each banned user
+    bans: HashMap<i64, Instant>,
+}
+
+/// Token bucket rate limits of the users and the group chats
+///
+/// Every command, button and plain message costs tokens, see
+/// `rate_limit.costs`. The buckets refill over time, a request is refused
+/// when the bucket of the user or of its group is short. After
+/// `rate_limit.ban_after` refusals in a row the user is ignored for
+/// `rate_limit.ban_secs`.
+#[derive(Clone, Default)]
+pub struct RateLimiter {
+    state: Arc<Mutex<LimiterState>>,
+}
+
+impl RateLimiter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Take the tokens of a request
+    ///
+    /// # Arguments
+    ///
+    /// * `user_id` - Telegram id of the user
+    /// * `chat_id` - Group chat id, `None` in private chats
+    /// * `cost` - Tokens of the request
+    /// * `config` - Rate limits
+    ///
+    /// # Returns
+    ///
+    /// * `Result<(), Refusal>` - `Ok` when the request goes on, nothing is
+    ///   taken from the buckets otherwise
+    pub fn check(
+        &self,
+        user_id: i64,
+        chat_id: Option<i64>,
+        cost: u32,
+        config: &RateLimitConfig,
+    ) -> Result<(), Refusal> {
+        self.check_at(Instant::now(), user_id, chat_id, cost, config)
+    }
+
+    fn check_at(
+        &self,
+        now: Instant,
+        user_id: i64,
+        chat_id: Option<i64>,
+        cost: u32,
+        config: &RateLimitConfig,
+    ) -> Result<(), Refusal> {
+        let mut state = self.state.lock().unwrap();
+        match state.bans.get(&user_id) {
+            Some(until) if now < *until => return Err(Refusal::Silent),
+            Some(_) => {
+                state.bans.remove(&user_id);
+            }
+            None => {}
+        }
+        if state.users.len() > PRUNE_ABOVE || state.chats.len() > PRUNE_ABOVE {
+            prune(&mut state, now, config);
+        }
+
+        let mut user = *state
+            .users
+            .entry(user_id)
+            .or_insert_with(|| Bucket::full(config.user_burst, now));
+        user.refill(now, config.user_burst, config.user_per_minute);
+        let mut chat = chat_id.map(|chat_id| {
+            let mut chat = *state
+                .chats
+                .entry(chat_id)
+                .or_insert_with(|| Bucket::full(config.chat_burst, now));
+            chat.refill(now, config.chat_burst, config.chat_per_minute);
+            chat
+        });
+
+        let cost_f = f64::from(cost);
+        let user_short = user.tokens < cost_f;
+        let chat_short = chat.is_some_and(|chat| chat.tokens < cost_f);
+        let result = if !user_short && !chat_short {
+            user.tokens -= cost_f;
+            user.refused = 0;
+            if let Some(chat) = chat.as_mut() {
+                chat.tokens -= cost_f;
+                chat.refused = 0;
+            }
+            Ok(())
+        } else {
+            let mut wait = Duration::ZERO;
+            let mut first = false;
+            if user_short {
+                user.refused += 1;
+                first |= user.refused == 1;
+                wait = wait.max(user.wait(cost, config.user_per_minute));
+            }
+            if let Some(chat) = chat.as_mut().filter(|_| chat_short) {
+                chat.refused += 1;
+                first |= chat.refused == 1;
+                wait = wait.max(chat.wait(cost, config.chat_per_minute));
+            }
+
+            if config.ban_after > 0 && user.refused >= config.ban_after {
+                let ban = Duration::from_secs(config.ban_secs);
+                state.bans.insert(user_id, now + ban);
+                user = Bucket::full(config.user_burst, now);
+                Err(Refusal::Banned(ban))
+            } else if first {
+                Err(Refusal::SlowDown(wait))
+            } else {
+                Err(Refusal::Silent)
+            }
+        };
+
+        state.users.insert(user_id, user);
+        if let (Some(chat_id), Some(chat)) = (chat_id, chat) {
+            state.chats.insert(chat_id, chat);
+        }
+        result
+    }
+
+    /// Lift the ban of a user and refill its bucket, `false` if it was not banned
+    pub fn unban(&self, user_id: i64) -> bool {
+        let mut state = self.state.lock().unwrap();
+        state.users.remove(&user_id);
+        state.bans.remove(&user_id).is_some()
+    }
+}
+
+/// Drop the buckets refilled since and the ended bans
+fn prune(state: &mut LimiterState, now: Instant, config: &RateLimitConfig) {
+    let full = |bucket: &Bucket, burst: u32, per_minute: u32| {
+        let mut bucket = *bucket;
+        bucket.refill(now, burst, per_minute);
+        bucket.tokens >= f64::from(burst)
+    };
+    state
+        .users
+        .retain(|_, bucket| !full(bucket, config.user_burst, config.user_per_minute));
+    state
+        .chats
+        .retain(|_, bucket| !full(bucket, config.chat_burst, config.chat_per_minute));
+    state.bans.retain(|_, until| now < *until);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config() -> RateLimitConfig {
+        RateLimitConfig {
+            user_burst: 5,
+            user_per_minute: 60,
+            chat_burst: 8,
+            chat_per_minute: 60,
+            ban_after: 3,
+            ban_secs: 600,
+            ..RateLimitConfig::default()
+        }
+    }
+
+    #[test]
+    fn test_token_bucket() {
+        let limiter = RateLimiter::new();
+        let config = config();
+        let start = Instant::now();
+
+        assert_eq!(limiter.check_at(start, 1, None, 3, &config), Ok(()));
+        assert_eq!(limiter.check_at(start, 1, None, 2, &config), Ok(()));
+        assert_eq!(
+            limiter.check_at(start, 1, None, 2, &config),
+            Err(Refusal::SlowDown(Duration::from_secs(2)))
+        );
+        // Only the first refusal in a row is answered
+        assert_eq!(
+            limiter.check_at(start, 1, None, 1, &config),
+            Err(Refusal::Silent)
+        );
+        // Other users have their own bucket
+        assert_eq!(limiter.check_at(start, 2, None, 5, &config), Ok(()));
+
+        // One token a second comes back
+        let later = start + Duration::from_secs(2);
+        assert_eq!(limiter.check_at(later, 1, None, 2, &config), Ok(()));
+    }
+
+    #[test]
+    fn test_chat_bucket() {
+        let limiter = RateLimiter::new();
+        let config = config();
+        let now = Instant::now();
+
+        assert_eq!(limiter.check_at(now, 1, Some(-100), 5, &config), Ok(()));
+        assert_eq!(
+            limiter.check_at(now, 2, Some(-100), 4, &config),
+            Err(Refusal::SlowDown(Duration::from_secs(1)))
+        );
+        assert_eq!(limiter.check_at(now, 2, Some(-100), 3, &config), Ok(()));
+        // The same user is free in a private chat
+        assert_eq!(limiter.check_at(now, 2, None, 2, &config), Ok(()));
+    }
+
+    #[test]
+    fn test_ban_and_unban() {
+        let limiter = RateLimiter::new();
+        let config = config();
+        let now = Instant::now();
+
+        assert_eq!(limiter.check_at(now, 1, None, 5, &config), Ok(()));
+        assert!(matches!(
+            limiter.check_at(now, 1, None, 1, &config),
+            Err(Refusal::SlowDown(_))
+        ));
+        assert_eq!(
+            limiter.check_at(now, 1, None, 1, &config),
+            Err(Refusal::Silent)
+        );
+        assert_eq!(
+            limiter.check_at(now, 1, None, 1, &config),
+            Err(Refusal::Banned(Duration::from_secs(600)))
+        );
+
+        // Banned users are ignored even with tokens back
+        let later = now + Duration::from_secs(60);
+        assert_eq!(
+            limiter.check_at(later, 1, None, 1, &config),
+            Err(Refusal::Silent)
+        );
+        let after_ban = now + Duration::from_secs(601);
+        assert_eq!(limiter.check_at(after_ban, 1, None, 1, &config), Ok(()));
+
+        for _ in 0..3 {
+            let _ = limiter.check_at(after_ban, 1, None, 5, &config);
+        }
+        assert!(limiter.unban(1));
+        assert!(!limiter.unban(1));
+        assert_eq!(limiter.check_at(after_ban, 1, None, 5, &config), Ok(()));
+    }
+}
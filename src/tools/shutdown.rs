@@ -0,0 +1,125 @@
+use log::{info, warn};
+use std::future::Future;
+use std::time::Duration;
+use tokio_util::sync::CancellationToken;
+use tokio_util::task::TaskTracker;
+
+/// Shutdown coordinator
+///
+/// Work that must not be cut off mid-send (charts, broadcasts, the digest
+/// scheduler) is spawned through [`Shutdown::spawn`]. On Ctrl-C or SIGTERM the
+/// token is cancelled, long-running jobs stop at the next safe point and
+/// [`Shutdown::drain`] waits for the rest within a timeout.
+#[derive(Clone, Default)]
+pub struct Shutdown {
+    token: CancellationToken,
+    tracker: TaskTracker,
+}
+
+impl Shutdown {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Spawn a tracked task
+    pub fn spawn<F>(&self, task: F)
+    where
+        F: Future<Output = ()> + Send + 'static,
+    {
+        self.tracker.spawn(task);
+    }
+
+    /// Returns true once shutdown has started
+    pub fn is_cancelled(&self) -> bool {
+        self.token.is_cancelled()
+    }
+
+    /// Resolves when shutdown starts
+    pub async fn cancelled(&self) {
+        self.token.cancelled().await
+    }
+
+    /// Start the shutdown
+    pub fn cancel(&self) {
+        self.token.cancel();
+    }
+
+    /// Wait for the tracked tasks to finish
+    ///
+    /// # Returns
+    ///
+    /// * `bool` - false if tasks were still running after `timeout`
+    pub async fn drain(&self, timeout: Duration) -> bool {
+        self.tracker.close();
+        info!("Waiting for {} running tasks", self.tracker.len());
+        match tokio::time::timeout(timeout, self.tracker.wait()).await {
+            Ok(()) => true,
+            Err(_) => {
+                warn!(
+                    "{} tasks still running after {:?}, exiting anyway",
+                    self.tracker.len(),
+                    timeout
+                );
+                false
+            }
+        }
+    }
+}
+
+/// Resolves on Ctrl-C, or SIGTERM on Unix
+pub async fn wait_for_signal() {
+    #[cfg(unix)]
+    {
+        use tokio::signal::unix::{signal, SignalKind};
+        match signal(SignalKind::terminate()) {
+            Ok(mut sigterm) => {
+                tokio::select! {
+                    _ = tokio::signal::ctrl_c() => {}
+                    _ = sigterm.recv() => {}
+                }
+            }
+            Err(err) => {
+                warn!("Can't listen for SIGTERM: {}", err);
+                let _ = tokio::signal::ctrl_c().await;
+            }
+        }
+    }
+
+    #[cfg(not(unix))]
+    {
+        let _ = tokio::signal::ctrl_c().await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::Arc;
+
+    #[tokio::test]
+    async fn test_drain_waits_for_tasks() {
+        let shutdown = Shutdown::new();
+        let done = Arc::new(AtomicBool::new(false));
+
+        let flag = done.clone();
+        shutdown.spawn(async move {
+            tokio::time::sleep(Duration::from_millis(50)).await;
+            flag.store(true, Ordering::SeqCst);
+        });
+
+        shutdown.cancel();
+        assert!(shutdown.drain(Duration::from_secs(5)).await);
+        assert!(done.load(Ordering::SeqCst));
+    }
+
+    #[tokio::test]
+    async fn test_drain_times_out() {
+        let shutdown = Shutdown::new();
+        shutdown.spawn(async {
+            tokio::time::sleep(Duration::from_secs(60)).await;
+        });
+
+        assert!(!shutdown.drain(Duration::from_millis(20)).await);
+    }
+}
@@ -0,0 +1,266 @@
+use crate::config::Config;
+use crate::tools::shutdown::Shutdown;
+use chrono::{DateTime, Utc};
+use log::{error, info};
+use std::any::Any;
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::future::Future;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use teloxide::prelude::*;
+
+/// Result of a background job run
+pub type JobResult = Result<(), Box<dyn Error + Send + Sync>>;
+
+/// First restart delay, doubled after every failure in a row
+const MIN_BACKOFF: Duration = Duration::from_secs(1);
+/// Upper bound of the restart delay
+const MAX_BACKOFF: Duration = Duration::from_secs(5 * 60);
+/// A run that lasted this long counts as healthy and resets the backoff
+const HEALTHY_RUN: Duration = Duration::from_secs(5 * 60);
+/// Failures in a row after which the admins are notified
+const ALERT_AFTER: u32 = 3;
+
+/// State of a supervised job
+///
+/// # Fields
+///
+/// * `restarts` - Restarts since the bot started
+/// * `failures_in_row` - Failures without a healthy run in between
+/// * `last_failure` - Time and error of the last failure
+///
+#[derive(Clone, Debug, Default)]
+pub struct JobStatus {
+    pub restarts: u32,
+    pub failures_in_row: u32,
+    pub last_failure: Option<(DateTime<Utc>, String)>,
+}
+
+/// Owner of the long-running background jobs
+///
+/// Every job runs in its own task. When it returns an error or panics it is
+/// restarted with exponential backoff and the failure is recorded; admins get
+/// a Telegram message once a job fails `ALERT_AFTER` times in a row. Jobs stop
+/// when shutdown starts.
+#[derive(Clone)]
+pub struct Supervisor {
+    bot: Bot,
+    config: Arc<Config>,
+    shutdown: Shutdown,
+    jobs: Arc<Mutex<BTreeMap<&'static str, JobStatus>>>,
+    min_backoff: Duration,
+}
+
+impl Supervisor {
+    pub fn new(bot: Bot, config: Arc<Config>, shutdown: Shutdown) -> Self {
+        Self {
+            bot,
+            config,
+            shutdown,
+            jobs: Arc::new(Mutex::new(BTreeMap::new())),
+            min_backoff: MIN_BACKOFF,
+        }
+    }
+
+    /// Start a supervised job
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - Job name used in logs, alerts and /jobs
+    /// * `job` - Creates a fresh run of the job, called again on every restart
+    pub fn spawn<F, Fut>(&self, name: &'static str, job: F)
+    where
+        F: Fn() -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = JobResult> + Send + 'static,
+    {
+        if let Ok(mut jobs) = self.jobs.lock() {
+            jobs.insert(name, JobStatus::default());
+        }
+        let supervisor = self.clone();
+        self.shutdown.spawn(supervisor.run(name, job));
+    }
+
+    async fn run<F, Fut>(self, name: &'static str, job: F)
+    where
+        F: Fn() -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = JobResult> + Send + 'static,
+    {
+        let mut backoff = self.min_backoff;
+        loop {
+            let started = Instant::now();
+            let mut handle = tokio::spawn(job());
+            let result = tokio::select! {
+                result = &mut handle => result,
+                _ = self.shutdown.cancelled() => {
+                    handle.abort();
+                    return;
+                }
+            };
+
+            let failure = match result {
+                Ok(Ok(())) => {
+                    info!("Job {} finished", name);
+                    return;
+                }
+                Ok(Err(err)) => err.to_string(),
+                Err(err) if err.is_panic() => {
+                    format!("panicked: {}", panic_message(err.into_panic()))
+                }
+                Err(err) => err.to_string(),
+            };
+
+            let healthy = started.elapsed() >= HEALTHY_RUN;
+            if healthy {
+                backoff = self.min_backoff;
+            }
+            let failures = self.record_failure(name, failure.clone(), healthy);
+            error!(
+                "Job {} failed ({} in a row): {}, restarting in {:?}",
+                name, failures, failure, backoff
+            );
+            if failures == ALERT_AFTER {
+                self.alert(name, failures, &failure).await;
+            }
+
+            tokio::select! {
+                _ = tokio::time::sleep(backoff) => {}
+                _ = self.shutdown.cancelled() => return,
+            }
+            backoff = (backoff * 2).min(MAX_BACKOFF);
+        }
+    }
+
+    /// Record a failure and return the failures in a row, `healthy` if the run before it was
+    fn record_failure(&self, name: &'static str, failure: String, healthy: bool) -> u32 {
+        let mut jobs = match self.jobs.lock() {
+            Ok(jobs) => jobs,
+            Err(_) => return 0,
+        };
+        let status = jobs.entry(name).or_default();
+        if healthy {
+            status.failures_in_row = 0;
+        }
+        status.restarts += 1;
+        status.failures_in_row += 1;
+        status.last_failure = Some((Utc::now(), failure));
+        status.failures_in_row
+    }
+
+    /// Tell the admins that a job keeps failing
+    async fn alert(&self, name: &str, failures: u32, failure: &str) {
+        let text = format!(
+            "Background job {} failed {} times in a row\nLast error: {}",
+            name, failures, failure
+        );
+        for admin in self.config.admin_ids.iter() {
+            if let Err(err) = self.bot.send_message(UserId(*admin), text.clone()).await {
+                error!("Error alerting admin {}: {}", admin, err);
+            }
+        }
+    }
+
+    /// Status of every job
+    pub fn statuses(&self) -> Vec<(&'static str, JobStatus)> {
+        match self.jobs.lock() {
+            Ok(jobs) => jobs
+                .iter()
+                .map(|(name, status)| (*name, status.clone()))
+                .collect(),
+            Err(_) => vec![],
+        }
+    }
+
+    /// Status of every job as text, used by /jobs
+    pub fn report(&self) -> String {
+        let lines: Vec<String> = self
+            .statuses()
+            .into_iter()
+            .map(|(name, status)| match status.last_failure {
+                Some((at, failure)) => format!(
+                    "{}: {} restarts, last failure {} - {}",
+                    name,
+                    status.restarts,
+                    at.format("%Y-%m-%d %H:%M:%S UTC"),
+                    failure
+                ),
+                None => format!("{}: ok", name),
+            })
+            .collect();
+
+        if lines.is_empty() {
+            "No background jobs".to_string()
+        } else {
+            lines.join("\n")
+        }
+    }
+}
+
+/// Text of a panic payload
+fn panic_message(payload: Box<dyn Any + Send>) -> String {
+    if let Some(message) = payload.downcast_ref::<&str>() {
+        message.to_string()
+    } else if let Some(message) = payload.downcast_ref::<String>() {
+        message.clone()
+    } else {
+        "unknown panic".to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicU32, Ordering};
+
+    fn supervisor(shutdown: Shutdown) -> Supervisor {
+        Supervisor {
+            min_backoff: Duration::from_millis(1),
+            ..Supervisor::new(Bot::new("123:abc"), Arc::new(Config::default()), shutdown)
+        }
+    }
+
+    #[tokio::test]
+    async fn test_job_restarted_after_error_and_panic() {
+        let shutdown = Shutdown::new();
+        let supervisor = supervisor(shutdown.clone());
+        let runs = Arc::new(AtomicU32::new(0));
+
+        let counter = runs.clone();
+        supervisor.spawn("flaky", move || {
+            let run = counter.fetch_add(1, Ordering::SeqCst);
+            async move {
+                match run {
+                    0 => Err("upstream down".into()),
+                    1 => panic!("bad data"),
+                    _ => Ok(()),
+                }
+            }
+        });
+
+        // The third run succeeds, so draining ends once the job is done
+        assert!(shutdown.drain(Duration::from_secs(5)).await);
+
+        assert_eq!(runs.load(Ordering::SeqCst), 3);
+        let (name, status) = supervisor.statuses().remove(0);
+        assert_eq!(name, "flaky");
+        assert_eq!(status.restarts, 2);
+        assert_eq!(status.failures_in_row, 2);
+        let (_, failure) = status.last_failure.unwrap();
+        assert_eq!(failure, "panicked: bad data");
+        assert!(supervisor.report().contains("flaky: 2 restarts"));
+    }
+
+    #[tokio::test]
+    async fn test_job_stopped_on_shutdown() {
+        let shutdown = Shutdown::new();
+        let supervisor = supervisor(shutdown.clone());
+        supervisor.spawn("forever", || async {
+            tokio::time::sleep(Duration::from_secs(60)).await;
+            Ok(())
+        });
+
+        shutdown.cancel();
+        assert!(shutdown.drain(Duration::from_secs(5)).await);
+        assert_eq!(supervisor.report(), "forever: ok");
+    }
+}